@@ -1,6 +1,52 @@
+use std::{env, fs, path::PathBuf};
+
+use prost::Message;
+use prost_types::FileDescriptorSet;
+
 fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let out_dir = PathBuf::from(env::var("OUT_DIR")?);
+
+    // The JSON gateway reuses the messages, with missing fields taking their proto defaults.
+    let mut config = prost_build::Config::new();
+    config
+        .type_attribute(
+            ".splitwiser",
+            "#[derive(serde::Serialize, serde::Deserialize)]",
+        )
+        .message_attribute(".splitwiser", "#[serde(default)]");
+
     tonic_build::configure()
         .build_client(false)
-        .compile(&["proto/splitwiser.proto"], &["proto/"])
-        .map_err(Into::into)
+        .file_descriptor_set_path(out_dir.join("descriptors_legacy.bin"))
+        .compile_with_config(
+            config,
+            &[
+                "proto/splitwiser.proto",
+                "proto/grpc/health/v1/health.proto",
+                "proto/grpc/reflection/v1alpha/reflection.proto",
+            ],
+            &["proto/"],
+        )?;
+
+    // Compiled on its own, since the serde attributes above would also match `.splitwiser.v1`,
+    // whose Timestamp fields cannot derive them.
+    tonic_build::configure()
+        .build_client(false)
+        .file_descriptor_set_path(out_dir.join("descriptors_v1.bin"))
+        .compile(&["proto/splitwiser/v1/splitwiser.proto"], &["proto/"])?;
+
+    // One set for reflection, with files imported by both compilations, such as
+    // `google/protobuf/empty.proto`, kept once.
+    let mut merged = FileDescriptorSet::default();
+    for name in ["descriptors_legacy.bin", "descriptors_v1.bin"] {
+        let set = FileDescriptorSet::decode(fs::read(out_dir.join(name))?.as_slice())?;
+        for file in set.file {
+            if !merged.file.iter().any(|seen| seen.name == file.name) {
+                merged.file.push(file);
+            }
+        }
+    }
+    fs::write(out_dir.join("descriptors.bin"), merged.encode_to_vec())?;
+
+    Ok(())
 }
@@ -0,0 +1,581 @@
+//! Command-line client for Splitwiser.
+//!
+//! `splitwiser-cli login` keeps the session in the config directory, so later commands run as
+//! that user. Results print as aligned columns, or as JSON with `--output json`.
+
+mod output;
+mod parse;
+mod session;
+
+use std::{io, path::Path};
+
+use clap::{Parser, Subcommand, ValueEnum};
+use serde_json::{json, Value};
+use splitwiser_client::{
+    AuditEventFilter, AuditEventId, Client, Decision, ExpenseId, Money, NewExpense, NewPayment,
+    NewRevenue, PaymentId, PaymentMethod, ProfileUpdate, SplitMethod, User, UserId,
+};
+use time::OffsetDateTime;
+use time_tz::{timezones, Tz};
+
+use self::{
+    output::{money, optional, timestamp, Format, Output},
+    parse::{UserRef, When},
+    session::Session,
+};
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error(transparent)]
+    Client(#[from] splitwiser_client::Error),
+    #[error("Not logged in to {0}, run `splitwiser-cli login` first")]
+    NotLoggedIn(String),
+    #[error("No user matches {0:?}")]
+    UnknownUser(String),
+    #[error("{0:?} matches several users: {1}")]
+    AmbiguousUser(String, String),
+    #[error("{0}")]
+    InvalidTime(String),
+    #[error("No config directory found, set SPLITWISER_SESSION")]
+    NoConfigDir,
+    #[error("Could not access the session file: {0}")]
+    Io(#[from] io::Error),
+    #[error("Invalid session file: {0}")]
+    Session(#[from] serde_json::Error),
+}
+
+#[derive(Parser)]
+#[command(
+    name = "splitwiser-cli",
+    version,
+    about = "Shares expenses over Splitwiser"
+)]
+struct Cli {
+    /// Server to talk to.
+    #[arg(
+        long,
+        global = true,
+        env = "SPLITWISER_SERVER",
+        default_value = "http://127.0.0.1:50051"
+    )]
+    server: String,
+    #[arg(long, short, global = true, value_enum, default_value_t = Format::Table)]
+    output: Format,
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Creates an account and logs in. The password is read from SPLITWISER_PASSWORD or
+    /// prompted for.
+    Register {
+        email: String,
+        /// Label for the session, shown by `user sessions`.
+        #[arg(long)]
+        device: Option<String>,
+    },
+    /// Logs in, keeping the session for later commands.
+    Login {
+        email: String,
+        #[arg(long)]
+        device: Option<String>,
+    },
+    /// Ends the session.
+    Logout,
+    #[command(subcommand)]
+    User(UserCommand),
+    #[command(subcommand)]
+    Revenue(RevenueCommand),
+    #[command(subcommand)]
+    Expense(ExpenseCommand),
+    #[command(subcommand)]
+    Payment(PaymentCommand),
+    /// What you owe another user; negative when they owe you.
+    Balance {
+        user: UserRef,
+        /// Counts payments they have not confirmed yet.
+        #[arg(long)]
+        include_unconfirmed: bool,
+    },
+    /// Changes to revenues, payments and expenses you take part in, newest first.
+    Statement {
+        /// Only changes made by this user.
+        #[arg(long)]
+        by: Option<UserRef>,
+        /// Only changes older than this entry, for paging.
+        #[arg(long)]
+        before: Option<AuditEventId>,
+        #[arg(long)]
+        limit: Option<u32>,
+    },
+}
+
+#[derive(Subcommand)]
+enum UserCommand {
+    /// Shows a profile, your own by default.
+    Show { user: Option<UserRef> },
+    /// Finds users by display name prefix or whole e-mail address.
+    Search {
+        query: String,
+        #[arg(long)]
+        limit: Option<u32>,
+    },
+    /// Changes your profile.
+    Update {
+        #[arg(long)]
+        name: Option<String>,
+        #[arg(long)]
+        email: Option<String>,
+        /// Language tag such as pt-BR.
+        #[arg(long)]
+        locale: Option<String>,
+        /// IANA name such as America/Sao_Paulo, used for dates.
+        #[arg(long)]
+        timezone: Option<String>,
+        #[arg(long)]
+        avatar_url: Option<String>,
+    },
+    /// Lists your sessions.
+    Sessions,
+}
+
+#[derive(Subcommand)]
+enum RevenueCommand {
+    /// Records income, which proportional expenses are split by.
+    Add {
+        #[arg(value_parser = parse::amount)]
+        amount: Money,
+        description: Option<String>,
+        #[arg(long, value_parser = parse::when, default_value = "now")]
+        at: When,
+    },
+}
+
+#[derive(Subcommand)]
+enum ExpenseCommand {
+    /// Records an expense you paid and share with another user.
+    Add {
+        #[arg(value_parser = parse::amount)]
+        amount: Money,
+        description: Option<String>,
+        #[arg(long)]
+        with: UserRef,
+        #[arg(long, value_enum, default_value_t = Split::Even)]
+        split: Split,
+        #[arg(long, default_value_t = 1)]
+        installments: u32,
+        /// Day of the first installment.
+        #[arg(long, value_parser = parse::when, default_value = "today")]
+        from: When,
+        /// The other user paid, so you owe them your share.
+        #[arg(long)]
+        they_paid: bool,
+    },
+    Show {
+        id: ExpenseId,
+    },
+    Delete {
+        id: ExpenseId,
+    },
+}
+
+#[derive(Subcommand)]
+enum PaymentCommand {
+    /// Records a payment you made, which the payee then confirms or rejects.
+    Add {
+        #[arg(value_parser = parse::amount)]
+        amount: Money,
+        #[arg(long)]
+        to: UserRef,
+        #[arg(long, value_enum, default_value_t = Method::Pix)]
+        method: Method,
+        #[arg(long, value_parser = parse::when, default_value = "now")]
+        at: When,
+        /// Receipt or transaction id.
+        #[arg(long)]
+        proof: Option<String>,
+    },
+    /// Confirms a payment made to you.
+    Confirm { id: PaymentId },
+    /// Rejects a payment made to you.
+    Reject { id: PaymentId },
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Split {
+    /// Half each.
+    Even,
+    /// By each user's revenue.
+    Proportional,
+    /// All of it.
+    Full,
+}
+
+impl From<Split> for SplitMethod {
+    fn from(split: Split) -> Self {
+        match split {
+            Split::Even => Self::Even,
+            Split::Proportional => Self::Proportional,
+            Split::Full => Self::Full,
+        }
+    }
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Method {
+    Cash,
+    BankTransfer,
+    Pix,
+    Lightning,
+}
+
+impl From<Method> for PaymentMethod {
+    fn from(method: Method) -> Self {
+        match method {
+            Method::Cash => Self::Cash,
+            Method::BankTransfer => Self::BankTransfer,
+            Method::Pix => Self::Pix,
+            Method::Lightning => Self::Lightning,
+        }
+    }
+}
+
+#[tokio::main(flavor = "current_thread")]
+async fn main() {
+    let cli = Cli::parse();
+
+    match run(&cli).await {
+        Ok(output) => print!("{}", output.render(cli.output)),
+        Err(e) => {
+            eprintln!("error: {e}");
+            std::process::exit(1);
+        }
+    }
+}
+
+async fn run(cli: &Cli) -> Result<Output, Error> {
+    let path = Session::path()?;
+
+    match &cli.command {
+        Command::Register { email, device } => {
+            let client = Client::connect(&cli.server).await?;
+            let (id, tokens) = client
+                .create_user(email, &password()?, device.as_deref())
+                .await?;
+            Session::new(&cli.server, &tokens).save(&path)?;
+
+            Ok(Output::Record(vec![("id", json!(id.0))]))
+        }
+        Command::Login { email, device } => {
+            let client = Client::connect(&cli.server).await?;
+            let tokens = client.login(email, &password()?, device.as_deref()).await?;
+            Session::new(&cli.server, &tokens).save(&path)?;
+
+            Ok(Output::Done(format!("Logged in as {email}")))
+        }
+        Command::Logout => {
+            if let Ok(context) = Context::load(&cli.server, &path).await {
+                context.client.logout().await?;
+            }
+            Session::delete(&path)?;
+
+            Ok(Output::Done("Logged out".to_owned()))
+        }
+        command => {
+            let context = Context::load(&cli.server, &path).await?;
+            context.run(command).await
+        }
+    }
+}
+
+fn password() -> Result<String, Error> {
+    match std::env::var("SPLITWISER_PASSWORD") {
+        Ok(password) => Ok(password),
+        Err(_) => Ok(rpassword::prompt_password("Password: ")?),
+    }
+}
+
+/// A client authenticated as the logged-in user.
+struct Context {
+    client: Client,
+    me: UserId,
+}
+
+impl Context {
+    async fn load(server: &str, path: &Path) -> Result<Self, Error> {
+        let mut session = Session::load(path)?
+            .filter(|session| session.server == server)
+            .ok_or_else(|| Error::NotLoggedIn(server.to_owned()))?;
+
+        let client = Client::connect(server).await?;
+        session.authenticate(&client, path).await?;
+
+        Ok(Self {
+            client,
+            me: UserId(session.user_id),
+        })
+    }
+
+    async fn run(&self, command: &Command) -> Result<Output, Error> {
+        let client = &self.client;
+
+        match command {
+            Command::Register { .. } | Command::Login { .. } | Command::Logout => {
+                unreachable!("Handled without a session")
+            }
+            Command::User(UserCommand::Show { user }) => {
+                let id = self.user(user.as_ref().unwrap_or(&UserRef::Me)).await?;
+                Ok(profile(client.get_user(id).await?))
+            }
+            Command::User(UserCommand::Search { query, limit }) => {
+                let users = client.search_users(query, *limit).await?;
+                Ok(Output::Rows {
+                    columns: &["id", "name", "email"],
+                    rows: users
+                        .into_iter()
+                        .map(|user| {
+                            vec![
+                                json!(user.id.0),
+                                optional(user.display_name),
+                                optional(user.email),
+                            ]
+                        })
+                        .collect(),
+                })
+            }
+            Command::User(UserCommand::Update {
+                name,
+                email,
+                locale,
+                timezone,
+                avatar_url,
+            }) => {
+                let user = client
+                    .update_profile(ProfileUpdate {
+                        display_name: name.clone(),
+                        email: email.clone(),
+                        locale: locale.clone(),
+                        timezone: timezone.clone(),
+                        avatar_url: avatar_url.clone(),
+                    })
+                    .await?;
+                Ok(profile(user))
+            }
+            Command::User(UserCommand::Sessions) => {
+                let sessions = client.list_sessions().await?;
+                Ok(Output::Rows {
+                    columns: &["id", "device", "created_at", "expires_at", "current"],
+                    rows: sessions
+                        .into_iter()
+                        .map(|session| {
+                            vec![
+                                json!(session.id.0),
+                                optional(session.device),
+                                timestamp(session.created_at),
+                                timestamp(session.expires_at),
+                                json!(session.current),
+                            ]
+                        })
+                        .collect(),
+                })
+            }
+            Command::Revenue(RevenueCommand::Add {
+                amount,
+                description,
+                at,
+            }) => {
+                let id = client
+                    .create_revenue(NewRevenue {
+                        amount: *amount,
+                        incoming_at: self.resolve(*at).await?,
+                        description: description.clone(),
+                    })
+                    .await?;
+                Ok(Output::Record(vec![("id", json!(id.0))]))
+            }
+            Command::Expense(ExpenseCommand::Add {
+                amount,
+                description,
+                with,
+                split,
+                installments,
+                from,
+                they_paid,
+            }) => {
+                let other = self.user(with).await?;
+                let (chargee, charged) = if *they_paid {
+                    (other, self.me)
+                } else {
+                    (self.me, other)
+                };
+
+                let id = client
+                    .create_expense(NewExpense {
+                        amount: *amount,
+                        description: description.clone(),
+                        chargee,
+                        charged,
+                        begin_charging_at: self.resolve(*from).await?,
+                        installments: *installments,
+                        method: (*split).into(),
+                    })
+                    .await?;
+                Ok(Output::Record(vec![("id", json!(id.0))]))
+            }
+            Command::Expense(ExpenseCommand::Show { id }) => {
+                let expense = client.get_expense(*id).await?;
+                Ok(Output::Record(vec![
+                    ("id", json!(expense.id.0)),
+                    ("amount", money(expense.amount)),
+                    ("description", optional(expense.description)),
+                    ("paid_by", json!(expense.chargee.0)),
+                    ("shared_with", json!(expense.charged.0)),
+                    ("split", json!(expense.method.as_str_name())),
+                    ("first_installment", timestamp(expense.begin_charging_at)),
+                    ("created_by", json!(expense.created_by.0)),
+                    ("created_at", timestamp(expense.created_at)),
+                ]))
+            }
+            Command::Expense(ExpenseCommand::Delete { id }) => {
+                client.delete_expense(*id).await?;
+                Ok(Output::Done(format!("Deleted expense {id}")))
+            }
+            Command::Payment(PaymentCommand::Add {
+                amount,
+                to,
+                method,
+                at,
+                proof,
+            }) => {
+                let id = client
+                    .create_payment(NewPayment {
+                        amount: *amount,
+                        payee: self.user(to).await?,
+                        payer: self.me,
+                        payed_at: self.resolve(*at).await?,
+                        method: (*method).into(),
+                        proof: proof.clone(),
+                    })
+                    .await?;
+                Ok(Output::Record(vec![("id", json!(id.0))]))
+            }
+            Command::Payment(PaymentCommand::Confirm { id }) => {
+                client.review_payment(*id, Decision::Confirm).await?;
+                Ok(Output::Done(format!("Confirmed payment {id}")))
+            }
+            Command::Payment(PaymentCommand::Reject { id }) => {
+                client.review_payment(*id, Decision::Reject).await?;
+                Ok(Output::Done(format!("Rejected payment {id}")))
+            }
+            Command::Balance {
+                user,
+                include_unconfirmed,
+            } => {
+                let other = self.user(user).await?;
+                let owed = client.balance(other, *include_unconfirmed).await?;
+                Ok(Output::Record(vec![
+                    ("user", json!(other.0)),
+                    ("you_owe", money(owed)),
+                ]))
+            }
+            Command::Statement { by, before, limit } => {
+                let actor = match by {
+                    Some(by) => Some(self.user(by).await?),
+                    None => None,
+                };
+                let events = client
+                    .list_audit_events(AuditEventFilter {
+                        entity: None,
+                        actor,
+                        before: *before,
+                        limit: *limit,
+                    })
+                    .await?;
+
+                Ok(Output::Rows {
+                    columns: &["id", "at", "by", "change", "entry", "entry_id"],
+                    rows: events
+                        .into_iter()
+                        .map(|event| {
+                            vec![
+                                json!(event.id.0),
+                                timestamp(event.created_at),
+                                json!(event.actor.map(|actor| actor.0)),
+                                json!(event.rpc),
+                                json!(event.entity_type.as_str_name()),
+                                json!(event.entity_id),
+                            ]
+                        })
+                        .collect(),
+                })
+            }
+        }
+    }
+
+    /// Resolves a user given by id, `me`, or a display name or e-mail address that matches
+    /// exactly one user.
+    async fn user(&self, user: &UserRef) -> Result<UserId, Error> {
+        let query = match user {
+            UserRef::Me => return Ok(self.me),
+            UserRef::Id(id) => return Ok(*id),
+            UserRef::Query(query) => query,
+        };
+
+        let users = self.client.search_users(query, None).await?;
+        let exact: Vec<_> = users
+            .iter()
+            .filter(|user| {
+                user.display_name
+                    .iter()
+                    .chain(&user.email)
+                    .any(|name| name.eq_ignore_ascii_case(query))
+            })
+            .collect();
+
+        match (exact.as_slice(), users.as_slice()) {
+            ([user], _) => Ok(user.id),
+            ([], [user]) => Ok(user.id),
+            ([], []) => Err(Error::UnknownUser(query.clone())),
+            (_, candidates) => Err(Error::AmbiguousUser(
+                query.clone(),
+                candidates
+                    .iter()
+                    .map(|user| {
+                        let name = user.display_name.as_deref().unwrap_or("unnamed");
+                        format!("{name} ({})", user.id)
+                    })
+                    .collect::<Vec<_>>()
+                    .join(", "),
+            )),
+        }
+    }
+
+    /// Places `when` in your profile's timezone.
+    async fn resolve(&self, when: When) -> Result<OffsetDateTime, Error> {
+        let now = OffsetDateTime::now_utc();
+        if when == When::Now {
+            return Ok(now);
+        }
+
+        let me = self.client.get_user(self.me).await?;
+        when.resolve(now, timezone(&me.timezone))
+            .map_err(Error::InvalidTime)
+    }
+}
+
+fn timezone(name: &str) -> &'static Tz {
+    timezones::get_by_name(name).unwrap_or(timezones::db::UTC)
+}
+
+fn profile(user: User) -> Output {
+    Output::Record(vec![
+        ("id", json!(user.id.0)),
+        ("name", optional(user.display_name)),
+        ("email", optional(user.email)),
+        ("locale", Value::String(user.locale)),
+        ("timezone", Value::String(user.timezone)),
+        ("avatar_url", optional(user.avatar_url)),
+        ("created_at", timestamp(user.created_at)),
+    ])
+}
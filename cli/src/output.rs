@@ -0,0 +1,162 @@
+use serde_json::{Map, Value};
+use splitwiser_client::Money;
+use time::{format_description::well_known::Rfc3339, OffsetDateTime};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum Format {
+    /// Aligned columns for reading.
+    Table,
+    /// One JSON document, for scripts.
+    Json,
+}
+
+/// What a command prints, rendered in the chosen [`Format`].
+pub enum Output {
+    /// A list, printed as columns or a JSON array of objects.
+    Rows {
+        columns: &'static [&'static str],
+        rows: Vec<Vec<Value>>,
+    },
+    /// A single object, printed as one `field  value` line per field.
+    Record(Vec<(&'static str, Value)>),
+    /// A confirmation, printed as is or as `{}`.
+    Done(String),
+}
+
+impl Output {
+    pub fn render(&self, format: Format) -> String {
+        match format {
+            Format::Table => self.table(),
+            Format::Json => {
+                serde_json::to_string_pretty(&self.json()).expect("Values serialize") + "\n"
+            }
+        }
+    }
+
+    fn table(&self) -> String {
+        match self {
+            Self::Rows { columns, rows } => {
+                let header = columns.iter().map(|column| column.to_uppercase()).collect();
+                let mut lines = vec![header];
+                lines.extend(rows.iter().map(|row| row.iter().map(cell).collect()));
+                aligned(&lines)
+            }
+            Self::Record(fields) => aligned(
+                &fields
+                    .iter()
+                    .map(|(field, value)| vec![(*field).to_owned(), cell(value)])
+                    .collect::<Vec<_>>(),
+            ),
+            Self::Done(message) => format!("{message}\n"),
+        }
+    }
+
+    fn json(&self) -> Value {
+        match self {
+            Self::Rows { columns, rows } => rows
+                .iter()
+                .map(|row| {
+                    let object: Map<_, _> = columns
+                        .iter()
+                        .map(|column| (*column).to_owned())
+                        .zip(row.iter().cloned())
+                        .collect();
+                    Value::Object(object)
+                })
+                .collect(),
+            Self::Record(fields) => Value::Object(
+                fields
+                    .iter()
+                    .map(|(field, value)| ((*field).to_owned(), value.clone()))
+                    .collect(),
+            ),
+            Self::Done(_) => Value::Object(Map::new()),
+        }
+    }
+}
+
+fn cell(value: &Value) -> String {
+    match value {
+        Value::Null => "-".to_owned(),
+        Value::String(s) => s.clone(),
+        other => other.to_string(),
+    }
+}
+
+/// Pads every column but the last to its widest cell.
+fn aligned(lines: &[Vec<String>]) -> String {
+    let mut widths = Vec::new();
+    for line in lines {
+        for (i, cell) in line.iter().enumerate() {
+            let width = cell.chars().count();
+            match widths.get_mut(i) {
+                Some(max) if *max < width => *max = width,
+                Some(_) => {}
+                None => widths.push(width),
+            }
+        }
+    }
+
+    let mut out = String::new();
+    for line in lines {
+        let last = line.len().saturating_sub(1);
+        for (i, cell) in line.iter().enumerate() {
+            out.push_str(cell);
+            if i < last {
+                let padding = widths[i] - cell.chars().count() + 2;
+                out.extend(std::iter::repeat_n(' ', padding));
+            }
+        }
+        out.push('\n');
+    }
+    out
+}
+
+/// Amounts are strings, so JSON keeps them exact.
+pub fn money(amount: Money) -> Value {
+    Value::String(amount.to_string())
+}
+
+pub fn timestamp(at: OffsetDateTime) -> Value {
+    Value::String(at.format(&Rfc3339).expect("Timestamps format as RFC 3339"))
+}
+
+pub fn optional<T: Into<Value>>(value: Option<T>) -> Value {
+    value.map_or(Value::Null, Into::into)
+}
+
+#[cfg(test)]
+mod test {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn renders_tables_and_json() {
+        let output = Output::Rows {
+            columns: &["id", "amount", "description"],
+            rows: vec![
+                vec![
+                    json!(1),
+                    money(Money::from_cents(12_050)),
+                    json!("groceries"),
+                ],
+                vec![json!(12), money(Money::from_cents(500)), Value::Null],
+            ],
+        };
+
+        assert_eq!(
+            output.render(Format::Table),
+            "ID  AMOUNT  DESCRIPTION\n\
+             1   120.50  groceries\n\
+             12  5.00    -\n"
+        );
+        assert_eq!(
+            serde_json::from_str::<Value>(&output.render(Format::Json)).unwrap(),
+            json!([
+                {"id": 1, "amount": "120.50", "description": "groceries"},
+                {"id": 12, "amount": "5.00", "description": null},
+            ])
+        );
+    }
+}
@@ -0,0 +1,169 @@
+use std::str::FromStr;
+
+use splitwiser_client::{Money, UserId};
+use time::{macros::format_description, Date, Duration, OffsetDateTime, PrimitiveDateTime, Time};
+use time_tz::{OffsetDateTimeExt, PrimitiveDateTimeExt, TimeZone, Tz};
+
+/// Parses amounts such as `120.50`, `120,50` or `R$ 120`.
+pub fn amount(s: &str) -> Result<Money, String> {
+    let s = s.trim();
+    let s = s.strip_prefix("R$").unwrap_or(s);
+    let amount: Money = s.parse().map_err(|e| format!("{e}"))?;
+
+    if amount.cents() <= 0 {
+        return Err("Amount must be positive".to_owned());
+    }
+    Ok(amount)
+}
+
+/// A moment given on the command line, resolved in the caller's profile timezone.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum When {
+    Now,
+    /// Midnight of the day this many days after today.
+    DaysFromToday(i64),
+    /// Midnight of a day.
+    Day(Date),
+    At(PrimitiveDateTime),
+}
+
+impl When {
+    /// Fails for local times skipped by a daylight saving transition, which name no moment.
+    pub fn resolve(self, now: OffsetDateTime, timezone: &Tz) -> Result<OffsetDateTime, String> {
+        let local = match self {
+            Self::Now => return Ok(now),
+            Self::DaysFromToday(days) => {
+                (now.to_timezone(timezone).date() + Duration::days(days)).midnight()
+            }
+            Self::Day(date) => date.midnight(),
+            Self::At(at) => at,
+        };
+
+        local
+            .assume_timezone(timezone)
+            .take_first()
+            .ok_or_else(|| format!("{local} does not exist in {}", timezone.name()))
+    }
+}
+
+/// Accepts `now`, `today`, `yesterday`, `tomorrow`, `N days ago`, `2024-03-01`, `01/03/2024`
+/// (day first) and `2024-03-01 14:30`.
+pub fn when(s: &str) -> Result<When, String> {
+    let s = s.trim().to_lowercase();
+
+    let days = match s.as_str() {
+        "now" => return Ok(When::Now),
+        "today" => Some(0),
+        "yesterday" => Some(-1),
+        "tomorrow" => Some(1),
+        _ => s
+            .strip_suffix(" days ago")
+            .or_else(|| s.strip_suffix(" day ago"))
+            .and_then(|n| n.parse::<i64>().ok())
+            .map(|n| -n),
+    };
+    if let Some(days) = days {
+        return Ok(When::DaysFromToday(days));
+    }
+
+    let iso = format_description!("[year]-[month]-[day]");
+    let day_first = format_description!("[day]/[month]/[year]");
+    let time = format_description!("[hour]:[minute]");
+
+    if let Ok(date) = Date::parse(&s, iso).or_else(|_| Date::parse(&s, day_first)) {
+        return Ok(When::Day(date));
+    }
+
+    let (date, at) = s
+        .split_once([' ', 't'])
+        .ok_or_else(|| format!("Unrecognised date {s:?}"))?;
+    let date = Date::parse(date, iso)
+        .or_else(|_| Date::parse(date, day_first))
+        .map_err(|_| format!("Unrecognised date {s:?}"))?;
+    let at = Time::parse(at, time).map_err(|_| format!("Unrecognised time in {s:?}"))?;
+
+    Ok(When::At(PrimitiveDateTime::new(date, at)))
+}
+
+/// A user named on the command line: `me`, an id, or a display name or e-mail to search for.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum UserRef {
+    Me,
+    Id(UserId),
+    Query(String),
+}
+
+impl FromStr for UserRef {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+
+        Ok(if s == "me" {
+            Self::Me
+        } else if let Ok(id) = s.parse() {
+            Self::Id(id)
+        } else if s.is_empty() {
+            return Err("Expected a user".to_owned());
+        } else {
+            Self::Query(s.to_owned())
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use time::macros::{date, datetime};
+    use time_tz::timezones::db::america::SAO_PAULO;
+
+    use super::*;
+
+    #[test]
+    fn parses_amounts() {
+        assert_eq!(amount("R$ 120,50"), Ok(Money::from_cents(12_050)));
+        assert_eq!(amount("3"), Ok(Money::from_cents(300)));
+        assert!(amount("0").is_err());
+        assert!(amount("-5").is_err());
+        assert!(amount("five").is_err());
+    }
+
+    #[test]
+    fn parses_dates() {
+        assert_eq!(when("2024-03-01"), Ok(When::Day(date!(2024 - 03 - 01))));
+        assert_eq!(when("01/03/2024"), Ok(When::Day(date!(2024 - 03 - 01))));
+        assert_eq!(
+            when("2024-03-01 14:30"),
+            Ok(When::At(datetime!(2024-03-01 14:30)))
+        );
+        assert_eq!(when("Today"), Ok(When::DaysFromToday(0)));
+        assert_eq!(when("3 days ago"), Ok(When::DaysFromToday(-3)));
+        assert!(when("next tuesday").is_err());
+
+        let now = datetime!(2024-03-02 01:00 UTC);
+        assert_eq!(
+            When::Day(date!(2024 - 03 - 01)).resolve(now, SAO_PAULO),
+            Ok(datetime!(2024-03-01 03:00 UTC))
+        );
+        // Still the 1st in São Paulo.
+        assert_eq!(
+            When::DaysFromToday(0).resolve(now, SAO_PAULO),
+            Ok(datetime!(2024-03-01 03:00 UTC))
+        );
+
+        // Clocks went from midnight straight to 01:00.
+        assert!(When::Day(date!(2018 - 11 - 04))
+            .resolve(now, SAO_PAULO)
+            .is_err());
+        assert_eq!(
+            When::At(datetime!(2018-11-04 01:00)).resolve(now, SAO_PAULO),
+            Ok(datetime!(2018-11-04 03:00 UTC))
+        );
+    }
+
+    #[test]
+    fn parses_users() {
+        assert_eq!("me".parse(), Ok(UserRef::Me));
+        assert_eq!("42".parse(), Ok(UserRef::Id(UserId(42))));
+        assert_eq!("alice".parse(), Ok(UserRef::Query("alice".to_owned())));
+    }
+}
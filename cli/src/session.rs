@@ -0,0 +1,132 @@
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use serde::{Deserialize, Serialize};
+use splitwiser_client::{AuthTokens, Client};
+use time::{Duration, OffsetDateTime};
+
+use crate::Error;
+
+/// Access tokens this close to expiring are refreshed before use.
+const REFRESH_MARGIN: Duration = Duration::seconds(30);
+
+/// Tokens of the signed-in user, kept between invocations.
+#[derive(Serialize, Deserialize)]
+pub struct Session {
+    pub server: String,
+    pub user_id: i32,
+    access_token: String,
+    access_token_expires_at: i64,
+    refresh_token: String,
+}
+
+impl Session {
+    pub fn new(server: &str, tokens: &AuthTokens) -> Self {
+        Self {
+            server: server.to_owned(),
+            user_id: tokens.user_id.0,
+            access_token: tokens.access_token.clone(),
+            access_token_expires_at: tokens.access_token_expires_at.unix_timestamp(),
+            refresh_token: tokens.refresh_token.clone(),
+        }
+    }
+
+    /// `$SPLITWISER_SESSION`, or `splitwiser/session.json` in the user's config directory.
+    pub fn path() -> Result<PathBuf, Error> {
+        if let Some(path) = std::env::var_os("SPLITWISER_SESSION") {
+            return Ok(path.into());
+        }
+
+        let config = std::env::var_os("XDG_CONFIG_HOME")
+            .map(PathBuf::from)
+            .or_else(|| std::env::var_os("HOME").map(|home| Path::new(&home).join(".config")))
+            .ok_or(Error::NoConfigDir)?;
+
+        Ok(config.join("splitwiser").join("session.json"))
+    }
+
+    pub fn load(path: &Path) -> Result<Option<Self>, Error> {
+        match fs::read(path) {
+            Ok(raw) => Ok(Some(serde_json::from_slice(&raw)?)),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Writes the session readable only by the current user, since it holds a refresh token. The
+    /// file is written beside `path` and renamed over it, so an existing file's permissions never
+    /// apply and a failed write leaves the old session intact.
+    pub fn save(&self, path: &Path) -> Result<(), Error> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+
+        let mut partial = path.as_os_str().to_owned();
+        partial.push(".partial");
+        let partial = PathBuf::from(partial);
+
+        let file = fs::File::create(&partial)?;
+        #[cfg(unix)]
+        file.set_permissions(std::os::unix::fs::PermissionsExt::from_mode(0o600))?;
+        serde_json::to_writer_pretty(&file, self)?;
+        file.sync_all()?;
+        fs::rename(&partial, path)?;
+        Ok(())
+    }
+
+    pub fn delete(path: &Path) -> Result<(), Error> {
+        match fs::remove_file(path) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(()),
+        }
+    }
+
+    /// Authenticates `client`, first refreshing the tokens if they are about to expire.
+    pub async fn authenticate(&mut self, client: &Client, path: &Path) -> Result<(), Error> {
+        let expires_at = OffsetDateTime::from_unix_timestamp(self.access_token_expires_at)
+            .unwrap_or(OffsetDateTime::UNIX_EPOCH);
+
+        if expires_at - REFRESH_MARGIN < OffsetDateTime::now_utc() {
+            let tokens = client.refresh_session(&self.refresh_token).await?;
+            *self = Self::new(&self.server, &tokens);
+            self.save(path)?;
+        }
+
+        client.set_access_token(Some(&self.access_token))?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[cfg(unix)]
+    #[test]
+    fn saves_sessions_readable_only_by_the_owner() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = std::env::temp_dir().join(format!("splitwiser-cli-{}", std::process::id()));
+        let path = dir.join("session.json");
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(&path, "{}").unwrap();
+        fs::set_permissions(&path, fs::Permissions::from_mode(0o644)).unwrap();
+
+        let session = Session {
+            server: "http://localhost".to_owned(),
+            user_id: 1,
+            access_token: "access".to_owned(),
+            access_token_expires_at: 0,
+            refresh_token: "refresh".to_owned(),
+        };
+        session.save(&path).unwrap();
+
+        let mode = fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+        assert_eq!(Session::load(&path).unwrap().unwrap().user_id, 1);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,6 @@
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    tonic_build::configure()
+        .build_server(false)
+        .compile(&["../proto/splitwiser.proto"], &["../proto/"])
+        .map_err(Into::into)
+}
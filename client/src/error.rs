@@ -0,0 +1,111 @@
+use std::time::Duration;
+
+use tonic::{Code, Status};
+
+use crate::Money;
+
+/// Failures of a call, with the status codes the service uses mapped to variants. Each carries
+/// the server's message.
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("Invalid argument: {0}")]
+    InvalidArgument(String),
+    #[error("Not authenticated: {0}")]
+    Unauthenticated(String),
+    #[error("Permission denied: {0}")]
+    PermissionDenied(String),
+    #[error("Not found: {0}")]
+    NotFound(String),
+    #[error("Already exists: {0}")]
+    AlreadyExists(String),
+    #[error("Failed precondition: {0}")]
+    FailedPrecondition(String),
+    #[error("Out of range: {0}")]
+    OutOfRange(String),
+    /// The rate limit was hit; the server says when to try again.
+    #[error("Rate limited: {message}")]
+    RateLimited {
+        message: String,
+        retry_after: Option<Duration>,
+    },
+    #[error("Deadline exceeded: {0}")]
+    DeadlineExceeded(String),
+    #[error("Unavailable: {0}")]
+    Unavailable(String),
+    #[error("Not supported by the server: {0}")]
+    Unimplemented(String),
+    #[error("Server error: {0}")]
+    Internal(String),
+    /// Any other status.
+    #[error("Unexpected status: {0}")]
+    Other(Box<Status>),
+    #[error("Invalid endpoint {0:?}")]
+    InvalidEndpoint(String),
+    #[error("Could not connect: {0}")]
+    Transport(#[from] tonic::transport::Error),
+    #[error("Amounts must not be negative, got {0}")]
+    NegativeAmount(Money),
+    #[error("Access token is not a valid header value")]
+    InvalidAccessToken,
+    #[error("Invalid response: {0}")]
+    InvalidResponse(&'static str),
+}
+
+impl From<Status> for Error {
+    fn from(status: Status) -> Self {
+        let message = status.message().to_owned();
+
+        match status.code() {
+            Code::InvalidArgument => Self::InvalidArgument(message),
+            Code::Unauthenticated => Self::Unauthenticated(message),
+            Code::PermissionDenied => Self::PermissionDenied(message),
+            Code::NotFound => Self::NotFound(message),
+            Code::AlreadyExists => Self::AlreadyExists(message),
+            Code::FailedPrecondition => Self::FailedPrecondition(message),
+            Code::OutOfRange => Self::OutOfRange(message),
+            Code::ResourceExhausted => Self::RateLimited {
+                retry_after: status
+                    .metadata()
+                    .get("retry-after")
+                    .and_then(|value| value.to_str().ok())
+                    .and_then(|value| value.parse().ok())
+                    .map(Duration::from_secs),
+                message,
+            },
+            Code::DeadlineExceeded => Self::DeadlineExceeded(message),
+            Code::Unavailable => Self::Unavailable(message),
+            Code::Unimplemented => Self::Unimplemented(message),
+            Code::Internal => Self::Internal(message),
+            _ => Self::Other(Box::new(status)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use tonic::metadata::MetadataValue;
+
+    use super::*;
+
+    #[test]
+    fn maps_status_codes() {
+        assert!(matches!(
+            Error::from(Status::not_found("User not found")),
+            Error::NotFound(message) if message == "User not found"
+        ));
+
+        let mut exhausted = Status::resource_exhausted("Slow down");
+        exhausted
+            .metadata_mut()
+            .insert("retry-after", MetadataValue::from(3u64));
+        assert!(matches!(
+            Error::from(exhausted),
+            Error::RateLimited { retry_after: Some(after), .. } if after == Duration::from_secs(3)
+        ));
+
+        assert!(matches!(
+            Error::from(Status::data_loss("Gone")),
+            Error::Other(status) if status.code() == Code::DataLoss
+        ));
+    }
+}
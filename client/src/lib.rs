@@ -0,0 +1,464 @@
+//! Async client for the Splitwiser gRPC service.
+//!
+//! [`Client`] wraps the generated stub, taking and returning domain types and mapping status
+//! codes to [`Error`]. Calls that sign in keep the returned access token and send it with every
+//! later call. The raw stub and messages remain available in [`proto`].
+
+use std::sync::{Arc, RwLock};
+
+use tonic::{
+    metadata::{Ascii, MetadataValue},
+    service::{interceptor::InterceptedService, Interceptor},
+    transport::{Channel, Endpoint},
+    Request, Status,
+};
+
+use self::proto::splitwiser_client::SplitwiserClient;
+
+mod error;
+mod types;
+
+pub use self::{error::Error, types::*};
+
+pub mod proto {
+    tonic::include_proto!("splitwiser");
+}
+
+/// Adds the current access token, if any, to each request.
+#[derive(Clone, Default)]
+struct Bearer(Arc<RwLock<Option<MetadataValue<Ascii>>>>);
+
+impl Interceptor for Bearer {
+    fn call(&mut self, mut request: Request<()>) -> Result<Request<()>, Status> {
+        let token = self.0.read().expect("Token lock poisoned").clone();
+        if let Some(token) = token {
+            request.metadata_mut().insert("authorization", token);
+        }
+        Ok(request)
+    }
+}
+
+/// A connection to the service. Clones share the connection and the access token.
+#[derive(Clone)]
+pub struct Client {
+    inner: SplitwiserClient<InterceptedService<Channel, Bearer>>,
+    bearer: Bearer,
+}
+
+impl Client {
+    /// Connects to `endpoint`, such as `http://localhost:50051`. With the `tls` feature,
+    /// `https://` endpoints are verified against the platform's root certificates.
+    pub async fn connect(endpoint: &str) -> Result<Self, Error> {
+        let endpoint = Endpoint::from_shared(endpoint.to_owned())
+            .map_err(|_| Error::InvalidEndpoint(endpoint.to_owned()))?;
+
+        #[cfg(feature = "tls")]
+        let endpoint = if endpoint.uri().scheme_str() == Some("https") {
+            endpoint.tls_config(tonic::transport::ClientTlsConfig::new())?
+        } else {
+            endpoint
+        };
+
+        Ok(Self::from_channel(endpoint.connect().await?))
+    }
+
+    /// Uses an already configured channel, for custom TLS or timeouts.
+    pub fn from_channel(channel: Channel) -> Self {
+        let bearer = Bearer::default();
+
+        Self {
+            inner: SplitwiserClient::with_interceptor(channel, bearer.clone()),
+            bearer,
+        }
+    }
+
+    /// Sends `token` with later calls, or nothing when `None`.
+    pub fn set_access_token(&self, token: Option<&str>) -> Result<(), Error> {
+        let token = token
+            .map(|token| format!("Bearer {token}").parse())
+            .transpose()
+            .map_err(|_| Error::InvalidAccessToken)?;
+
+        *self.bearer.0.write().expect("Token lock poisoned") = token;
+        Ok(())
+    }
+
+    fn signed_in(&self, tokens: proto::AuthTokens) -> Result<AuthTokens, Error> {
+        let tokens = AuthTokens::try_from(tokens)?;
+        self.set_access_token(Some(&tokens.access_token))?;
+        Ok(tokens)
+    }
+
+    /// Registers a user and signs in as them.
+    pub async fn create_user(
+        &self,
+        email: &str,
+        password: &str,
+        device: Option<&str>,
+    ) -> Result<(UserId, AuthTokens), Error> {
+        let created = self
+            .inner
+            .clone()
+            .create_user(proto::CreateUserRequest {
+                email: email.to_owned(),
+                password: password.to_owned(),
+                device: device.map(ToOwned::to_owned),
+            })
+            .await?
+            .into_inner();
+
+        let tokens = created
+            .tokens
+            .ok_or(Error::InvalidResponse("Missing tokens"))?;
+        Ok((created.id.into(), self.signed_in(tokens)?))
+    }
+
+    pub async fn login(
+        &self,
+        email: &str,
+        password: &str,
+        device: Option<&str>,
+    ) -> Result<AuthTokens, Error> {
+        let tokens = self
+            .inner
+            .clone()
+            .login(proto::LoginRequest {
+                email: email.to_owned(),
+                password: password.to_owned(),
+                device: device.map(ToOwned::to_owned),
+            })
+            .await?
+            .into_inner();
+
+        self.signed_in(tokens)
+    }
+
+    /// Exchanges a refresh token, which is then spent, for new tokens.
+    pub async fn refresh_session(&self, refresh_token: &str) -> Result<AuthTokens, Error> {
+        let tokens = self
+            .inner
+            .clone()
+            .refresh_session(proto::RefreshSessionRequest {
+                refresh_token: refresh_token.to_owned(),
+            })
+            .await?
+            .into_inner();
+
+        self.signed_in(tokens)
+    }
+
+    /// Ends the current session and forgets its access token.
+    pub async fn logout(&self) -> Result<(), Error> {
+        self.inner.clone().logout(()).await?;
+        self.set_access_token(None)
+    }
+
+    pub async fn list_sessions(&self) -> Result<Vec<Session>, Error> {
+        self.inner
+            .clone()
+            .list_sessions(())
+            .await?
+            .into_inner()
+            .sessions
+            .into_iter()
+            .map(Session::try_from)
+            .collect()
+    }
+
+    pub async fn revoke_session(&self, id: SessionId) -> Result<(), Error> {
+        self.inner
+            .clone()
+            .revoke_session(proto::Id { id: id.0 })
+            .await?;
+        Ok(())
+    }
+
+    pub async fn update_profile(&self, update: ProfileUpdate) -> Result<User, Error> {
+        self.inner
+            .clone()
+            .update_profile(proto::UpdateProfileRequest {
+                display_name: update.display_name,
+                email: update.email,
+                locale: update.locale,
+                timezone: update.timezone,
+                avatar_url: update.avatar_url,
+            })
+            .await?
+            .into_inner()
+            .try_into()
+    }
+
+    pub async fn get_user(&self, id: UserId) -> Result<User, Error> {
+        self.inner
+            .clone()
+            .get_user(proto::Id { id: id.0 })
+            .await?
+            .into_inner()
+            .try_into()
+    }
+
+    /// Finds users by display name prefix or whole e-mail address.
+    pub async fn search_users(&self, query: &str, limit: Option<u32>) -> Result<Vec<User>, Error> {
+        self.inner
+            .clone()
+            .search_users(proto::SearchUsersRequest {
+                query: query.to_owned(),
+                limit: limit.unwrap_or_default(),
+            })
+            .await?
+            .into_inner()
+            .users
+            .into_iter()
+            .map(User::try_from)
+            .collect()
+    }
+
+    pub async fn create_revenue(&self, revenue: NewRevenue) -> Result<RevenueId, Error> {
+        let id = self
+            .inner
+            .clone()
+            .create_revenue(proto::CreateRevenueRequest {
+                amount_cents: revenue.amount.unsigned()?,
+                incoming_at: revenue.incoming_at.unix_timestamp(),
+                description: revenue.description,
+            })
+            .await?
+            .into_inner();
+
+        Ok(id.id.into())
+    }
+
+    pub async fn create_payment(&self, payment: NewPayment) -> Result<PaymentId, Error> {
+        let id = self
+            .inner
+            .clone()
+            .create_payment(proto::CreatePaymentRequest {
+                amount_cents: payment.amount.unsigned()?,
+                payee_user_id: payment.payee.0,
+                payer_user_id: payment.payer.0,
+                payed_at: payment.payed_at.unix_timestamp(),
+                method: payment.method.into(),
+                proof: payment.proof,
+            })
+            .await?
+            .into_inner();
+
+        Ok(id.id.into())
+    }
+
+    /// Confirms or rejects a payment made to the caller.
+    pub async fn review_payment(&self, id: PaymentId, decision: Decision) -> Result<(), Error> {
+        self.inner
+            .clone()
+            .confirm_payment(proto::ConfirmPaymentRequest {
+                payment_id: id.0,
+                decision: decision.into(),
+            })
+            .await?;
+        Ok(())
+    }
+
+    pub async fn create_expense(&self, expense: NewExpense) -> Result<ExpenseId, Error> {
+        let id = self
+            .inner
+            .clone()
+            .create_expense(proto::CreateExpenseRequest {
+                amount_cents: expense.amount.unsigned()?,
+                description: expense.description,
+                chargee_user_id: expense.chargee.0,
+                charged_user_id: expense.charged.0,
+                begin_charging_at: expense.begin_charging_at.unix_timestamp(),
+                installments: expense.installments,
+                method: expense.method.into(),
+            })
+            .await?
+            .into_inner();
+
+        Ok(id.id.into())
+    }
+
+    pub async fn get_expense(&self, id: ExpenseId) -> Result<Expense, Error> {
+        self.inner
+            .clone()
+            .get_expense(proto::Id { id: id.0 })
+            .await?
+            .into_inner()
+            .try_into()
+    }
+
+    pub async fn delete_expense(&self, id: ExpenseId) -> Result<(), Error> {
+        self.inner
+            .clone()
+            .delete_expense(proto::Id { id: id.0 })
+            .await?;
+        Ok(())
+    }
+
+    /// What the caller owes `other`, negative when `other` owes the caller.
+    pub async fn balance(&self, other: UserId, include_unconfirmed: bool) -> Result<Money, Error> {
+        let balance = self
+            .inner
+            .clone()
+            .get_balance(proto::GetBalanceRequest {
+                other_user_id: other.0,
+                include_unconfirmed,
+            })
+            .await?
+            .into_inner();
+
+        Ok(Money::from_cents(balance.owed_cents))
+    }
+
+    /// Asks for a Lightning invoice settling what the caller owes `payee`, at `price_per_btc`.
+    pub async fn request_settlement_invoice(
+        &self,
+        payee: UserId,
+        price_per_btc: Money,
+    ) -> Result<SettlementInvoice, Error> {
+        self.inner
+            .clone()
+            .request_settlement_invoice(proto::RequestSettlementInvoiceRequest {
+                payee_user_id: payee.0,
+                cents_per_btc: price_per_btc.unsigned()?,
+            })
+            .await?
+            .into_inner()
+            .try_into()
+    }
+
+    pub async fn get_settlement_invoice(
+        &self,
+        id: SettlementInvoiceId,
+    ) -> Result<SettlementInvoice, Error> {
+        self.inner
+            .clone()
+            .get_settlement_invoice(proto::Id { id: id.0 })
+            .await?
+            .into_inner()
+            .try_into()
+    }
+
+    pub async fn set_pix_account(
+        &self,
+        key: &str,
+        merchant_name: &str,
+        merchant_city: &str,
+    ) -> Result<(), Error> {
+        self.inner
+            .clone()
+            .set_pix_account(proto::SetPixAccountRequest {
+                key: key.to_owned(),
+                merchant_name: merchant_name.to_owned(),
+                merchant_city: merchant_city.to_owned(),
+            })
+            .await?;
+        Ok(())
+    }
+
+    /// A Pix BR Code paying what the caller owes `payee`: static unless `location` names a
+    /// dynamic charge.
+    pub async fn pix_payload(
+        &self,
+        payee: UserId,
+        txid: Option<&str>,
+        location: Option<&str>,
+    ) -> Result<PixPayload, Error> {
+        let payload = self
+            .inner
+            .clone()
+            .get_pix_payload(proto::GetPixPayloadRequest {
+                payee_user_id: payee.0,
+                txid: txid.map(ToOwned::to_owned),
+                location: location.map(ToOwned::to_owned),
+            })
+            .await?
+            .into_inner();
+
+        Ok(PixPayload {
+            amount: Money::from_unsigned(payload.amount_cents)?,
+            payload: payload.payload,
+        })
+    }
+
+    pub async fn set_bitcoin_address(&self, address: &str) -> Result<(), Error> {
+        self.inner
+            .clone()
+            .set_bitcoin_address(proto::SetBitcoinAddressRequest {
+                address: address.to_owned(),
+            })
+            .await?;
+        Ok(())
+    }
+
+    /// A BIP21 URI paying what the caller owes `payee`, at `price_per_btc`.
+    pub async fn bitcoin_payment_uri(
+        &self,
+        payee: UserId,
+        price_per_btc: Money,
+    ) -> Result<BitcoinPaymentUri, Error> {
+        let uri = self
+            .inner
+            .clone()
+            .get_bitcoin_payment_uri(proto::GetBitcoinPaymentUriRequest {
+                payee_user_id: payee.0,
+                cents_per_btc: price_per_btc.unsigned()?,
+            })
+            .await?
+            .into_inner();
+
+        Ok(BitcoinPaymentUri {
+            amount: Money::from_unsigned(uri.amount_cents)?,
+            amount_sats: uri.amount_sats,
+            uri: uri.uri,
+        })
+    }
+
+    pub async fn render_qr_code(
+        &self,
+        payload: &str,
+        format: QrFormat,
+        error_correction: ErrorCorrection,
+    ) -> Result<QrCode, Error> {
+        let code = self
+            .inner
+            .clone()
+            .render_qr_code(proto::RenderQrCodeRequest {
+                payload: payload.to_owned(),
+                format: format.into(),
+                error_correction: error_correction.into(),
+            })
+            .await?
+            .into_inner();
+
+        Ok(QrCode {
+            content: code.content,
+            content_type: code.content_type,
+        })
+    }
+
+    /// Changes to entries the caller takes part in, newest first.
+    pub async fn list_audit_events(
+        &self,
+        filter: AuditEventFilter,
+    ) -> Result<Vec<AuditEvent>, Error> {
+        let (entity_type, entity_id) = filter
+            .entity
+            .map_or((None, None), |(kind, id)| (Some(kind.into()), Some(id)));
+
+        self.inner
+            .clone()
+            .list_audit_events(proto::ListAuditEventsRequest {
+                entity_type,
+                entity_id,
+                actor_user_id: filter.actor.map(|actor| actor.0),
+                before_id: filter.before.map(|before| before.0),
+                limit: filter.limit.unwrap_or_default(),
+            })
+            .await?
+            .into_inner()
+            .events
+            .into_iter()
+            .map(AuditEvent::try_from)
+            .collect()
+    }
+}
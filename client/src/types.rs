@@ -0,0 +1,411 @@
+use std::{fmt, str::FromStr};
+
+use time::OffsetDateTime;
+
+use crate::{proto, Error};
+
+pub use crate::proto::{
+    audit_event::EntityType,
+    confirm_payment_request::Decision,
+    create_expense_request::Method as SplitMethod,
+    create_payment_request::Method as PaymentMethod,
+    render_qr_code_request::{ErrorCorrection, Format as QrFormat},
+    settlement_invoice::Status as SettlementStatus,
+};
+
+macro_rules! ids {
+    ($($id:ident),+) => {
+        $(
+            #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+            pub struct $id(pub i32);
+
+            impl From<i32> for $id {
+                fn from(id: i32) -> Self {
+                    Self(id)
+                }
+            }
+
+            impl fmt::Display for $id {
+                fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                    self.0.fmt(f)
+                }
+            }
+
+            impl FromStr for $id {
+                type Err = std::num::ParseIntError;
+
+                fn from_str(s: &str) -> Result<Self, Self::Err> {
+                    s.parse().map(Self)
+                }
+            }
+        )+
+    };
+}
+
+ids!(
+    UserId,
+    RevenueId,
+    PaymentId,
+    ExpenseId,
+    SettlementInvoiceId,
+    SessionId,
+    AuditEventId
+);
+
+/// An amount of reais, kept in cents.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Money {
+    cents: i64,
+}
+
+impl Money {
+    pub const fn from_cents(cents: i64) -> Self {
+        Self { cents }
+    }
+
+    pub const fn cents(self) -> i64 {
+        self.cents
+    }
+
+    pub(crate) fn from_unsigned(cents: u64) -> Result<Self, Error> {
+        i64::try_from(cents)
+            .map(Self::from_cents)
+            .map_err(|_| Error::InvalidResponse("Amount out of range"))
+    }
+
+    /// The amount as the unsigned cents the service expects.
+    pub(crate) fn unsigned(self) -> Result<u64, Error> {
+        u64::try_from(self.cents).map_err(|_| Error::NegativeAmount(self))
+    }
+}
+
+/// Formats as reais with two decimals, such as `120.50` or `-0.05`.
+impl fmt::Display for Money {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let sign = if self.cents < 0 { "-" } else { "" };
+        let cents = self.cents.unsigned_abs();
+        write!(f, "{sign}{}.{:02}", cents / 100, cents % 100)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum ParseMoneyError {
+    #[error("Expected an amount such as 120.50")]
+    Malformed,
+    #[error("Amounts have at most two decimal places")]
+    TooPrecise,
+    #[error("Amount is too large")]
+    Overflow,
+}
+
+/// Parses amounts such as `120`, `120.5`, `120,50` or `-3.20`.
+impl FromStr for Money {
+    type Err = ParseMoneyError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (negative, digits) = match s.trim().strip_prefix('-') {
+            Some(rest) => (true, rest),
+            None => (false, s.trim()),
+        };
+        let (units, fraction) = digits.split_once(['.', ',']).unwrap_or((digits, ""));
+
+        let is_numeric = |part: &str| part.bytes().all(|b| b.is_ascii_digit());
+        if units.is_empty() || !is_numeric(units) || !is_numeric(fraction) {
+            return Err(ParseMoneyError::Malformed);
+        }
+        if fraction.len() > 2 {
+            return Err(ParseMoneyError::TooPrecise);
+        }
+
+        let units: i64 = units.parse().map_err(|_| ParseMoneyError::Overflow)?;
+        let fraction: i64 = format!("{fraction:0<2}").parse().expect("Two ASCII digits");
+        let cents = units
+            .checked_mul(100)
+            .and_then(|cents| cents.checked_add(fraction))
+            .ok_or(ParseMoneyError::Overflow)?;
+
+        Ok(Self::from_cents(if negative { -cents } else { cents }))
+    }
+}
+
+pub(crate) fn timestamp(seconds: i64) -> Result<OffsetDateTime, Error> {
+    OffsetDateTime::from_unix_timestamp(seconds)
+        .map_err(|_| Error::InvalidResponse("Timestamp out of range"))
+}
+
+#[derive(Debug, Clone)]
+pub struct AuthTokens {
+    pub user_id: UserId,
+    pub session_id: SessionId,
+    pub access_token: String,
+    pub access_token_expires_at: OffsetDateTime,
+    pub refresh_token: String,
+    pub refresh_token_expires_at: OffsetDateTime,
+}
+
+impl TryFrom<proto::AuthTokens> for AuthTokens {
+    type Error = Error;
+
+    fn try_from(tokens: proto::AuthTokens) -> Result<Self, Self::Error> {
+        Ok(Self {
+            user_id: tokens.user_id.into(),
+            session_id: tokens.session_id.into(),
+            access_token: tokens.access_token,
+            access_token_expires_at: timestamp(tokens.access_token_expires_at)?,
+            refresh_token: tokens.refresh_token,
+            refresh_token_expires_at: timestamp(tokens.refresh_token_expires_at)?,
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Session {
+    pub id: SessionId,
+    pub device: Option<String>,
+    pub created_at: OffsetDateTime,
+    pub refreshed_at: OffsetDateTime,
+    pub expires_at: OffsetDateTime,
+    /// Whether this is the session of the client's access token.
+    pub current: bool,
+}
+
+impl TryFrom<proto::Session> for Session {
+    type Error = Error;
+
+    fn try_from(session: proto::Session) -> Result<Self, Self::Error> {
+        Ok(Self {
+            id: session.id.into(),
+            device: session.device,
+            created_at: timestamp(session.created_at)?,
+            refreshed_at: timestamp(session.refreshed_at)?,
+            expires_at: timestamp(session.expires_at)?,
+            current: session.current,
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct User {
+    pub id: UserId,
+    pub display_name: Option<String>,
+    /// Only present on the caller's own profile.
+    pub email: Option<String>,
+    pub locale: String,
+    pub timezone: String,
+    pub avatar_url: Option<String>,
+    pub created_at: OffsetDateTime,
+}
+
+impl TryFrom<proto::User> for User {
+    type Error = Error;
+
+    fn try_from(user: proto::User) -> Result<Self, Self::Error> {
+        Ok(Self {
+            id: user.id.into(),
+            display_name: user.display_name,
+            email: user.email,
+            locale: user.locale,
+            timezone: user.timezone,
+            avatar_url: user.avatar_url,
+            created_at: timestamp(user.created_at)?,
+        })
+    }
+}
+
+/// Changes to the caller's profile; `None` leaves a field unchanged.
+#[derive(Debug, Clone, Default)]
+pub struct ProfileUpdate {
+    pub display_name: Option<String>,
+    pub email: Option<String>,
+    pub locale: Option<String>,
+    pub timezone: Option<String>,
+    pub avatar_url: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct NewRevenue {
+    pub amount: Money,
+    pub incoming_at: OffsetDateTime,
+    pub description: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct NewPayment {
+    pub amount: Money,
+    pub payee: UserId,
+    pub payer: UserId,
+    pub payed_at: OffsetDateTime,
+    pub method: PaymentMethod,
+    pub proof: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct NewExpense {
+    pub amount: Money,
+    pub description: Option<String>,
+    pub chargee: UserId,
+    pub charged: UserId,
+    pub begin_charging_at: OffsetDateTime,
+    pub installments: u32,
+    pub method: SplitMethod,
+}
+
+#[derive(Debug, Clone)]
+pub struct Expense {
+    pub id: ExpenseId,
+    pub created_by: UserId,
+    pub amount: Money,
+    pub description: Option<String>,
+    pub chargee: UserId,
+    pub charged: UserId,
+    pub begin_charging_at: OffsetDateTime,
+    pub method: SplitMethod,
+    pub created_at: OffsetDateTime,
+}
+
+impl TryFrom<proto::Expense> for Expense {
+    type Error = Error;
+
+    fn try_from(expense: proto::Expense) -> Result<Self, Self::Error> {
+        Ok(Self {
+            id: expense.id.into(),
+            created_by: expense.created_by.into(),
+            amount: Money::from_unsigned(expense.amount_cents)?,
+            method: expense.method(),
+            description: expense.description,
+            chargee: expense.chargee_user_id.into(),
+            charged: expense.charged_user_id.into(),
+            begin_charging_at: timestamp(expense.begin_charging_at)?,
+            created_at: timestamp(expense.created_at)?,
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct SettlementInvoice {
+    pub id: SettlementInvoiceId,
+    pub payer: UserId,
+    pub payee: UserId,
+    pub amount: Money,
+    pub amount_msats: u64,
+    pub payment_hash: String,
+    /// BOLT11 invoice to pay.
+    pub payment_request: String,
+    pub status: SettlementStatus,
+    pub expires_at: OffsetDateTime,
+    pub paid_at: Option<OffsetDateTime>,
+    pub payment: Option<PaymentId>,
+}
+
+impl TryFrom<proto::SettlementInvoice> for SettlementInvoice {
+    type Error = Error;
+
+    fn try_from(invoice: proto::SettlementInvoice) -> Result<Self, Self::Error> {
+        Ok(Self {
+            id: invoice.id.into(),
+            payer: invoice.payer_user_id.into(),
+            payee: invoice.payee_user_id.into(),
+            amount: Money::from_unsigned(invoice.amount_cents)?,
+            amount_msats: invoice.amount_msats,
+            status: invoice.status(),
+            payment_hash: invoice.payment_hash,
+            payment_request: invoice.payment_request,
+            expires_at: timestamp(invoice.expires_at)?,
+            paid_at: invoice.paid_at.map(timestamp).transpose()?,
+            payment: invoice.user_payment_id.map(PaymentId),
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct PixPayload {
+    /// BR Code to copy and paste or render as a QR code.
+    pub payload: String,
+    pub amount: Money,
+}
+
+#[derive(Debug, Clone)]
+pub struct BitcoinPaymentUri {
+    /// BIP21 URI.
+    pub uri: String,
+    pub amount: Money,
+    pub amount_sats: u64,
+}
+
+#[derive(Debug, Clone)]
+pub struct QrCode {
+    pub content: Vec<u8>,
+    pub content_type: String,
+}
+
+/// Filters for [`crate::Client::list_audit_events`]; all are optional.
+#[derive(Debug, Clone, Default)]
+pub struct AuditEventFilter {
+    pub entity: Option<(EntityType, i32)>,
+    pub actor: Option<UserId>,
+    /// Returns events older than this one, for paging backwards.
+    pub before: Option<AuditEventId>,
+    /// Defaults to 50, at most 200.
+    pub limit: Option<u32>,
+}
+
+#[derive(Debug, Clone)]
+pub struct AuditEvent {
+    pub id: AuditEventId,
+    /// Absent for operator actions.
+    pub actor: Option<UserId>,
+    /// Name of the call that made the change, such as `DeleteExpense`.
+    pub rpc: String,
+    pub entity_type: EntityType,
+    pub entity_id: i32,
+    /// JSON snapshots of the entity, absent for creations and deletions respectively.
+    pub before: Option<String>,
+    pub after: Option<String>,
+    pub created_at: OffsetDateTime,
+}
+
+impl TryFrom<proto::AuditEvent> for AuditEvent {
+    type Error = Error;
+
+    fn try_from(event: proto::AuditEvent) -> Result<Self, Self::Error> {
+        Ok(Self {
+            id: event.id.into(),
+            actor: event.actor_user_id.map(UserId::from),
+            entity_type: event.entity_type(),
+            rpc: event.rpc,
+            entity_id: event.entity_id,
+            before: event.before,
+            after: event.after,
+            created_at: timestamp(event.created_at)?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parses_and_formats_money() {
+        for (input, cents) in [
+            ("120.50", 12_050),
+            ("120,5", 12_050),
+            ("120", 12_000),
+            ("0.05", 5),
+            ("-3.20", -320),
+        ] {
+            assert_eq!(input.parse::<Money>(), Ok(Money::from_cents(cents)));
+        }
+
+        assert_eq!(Money::from_cents(12_050).to_string(), "120.50");
+        assert_eq!(Money::from_cents(-5).to_string(), "-0.05");
+
+        assert_eq!("1.234".parse::<Money>(), Err(ParseMoneyError::TooPrecise));
+        assert_eq!("R$ 5".parse::<Money>(), Err(ParseMoneyError::Malformed));
+        assert_eq!(".5".parse::<Money>(), Err(ParseMoneyError::Malformed));
+        assert_eq!(
+            "99999999999999999999".parse::<Money>(),
+            Err(ParseMoneyError::Overflow)
+        );
+    }
+}
@@ -1,47 +1,343 @@
-use diesel::{r2d2::ConnectionManager, PgConnection, QueryResult};
+use std::{
+    fmt::Display,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, RwLock,
+    },
+    time::{Duration, Instant},
+};
+
+use diesel::{
+    r2d2::{ConnectionManager, CustomizeConnection},
+    QueryResult, RunQueryDsl,
+};
+use once_cell::sync::Lazy;
 use r2d2::{Pool, PooledConnection};
+use tracing::field;
+
+use crate::{deadline, metrics};
 
 type ManagedConn = ConnectionManager<PgConnection>;
 type PooledConn = PooledConnection<ManagedConn>;
 
+/// How often [`Db::close`] checks for connections still in use.
+const CLOSE_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// How many times [`Db::write`] runs a transaction that keeps failing to serialize.
+const MAX_ATTEMPTS: u32 = 3;
+
 #[derive(Clone)]
-pub struct Db(pub(crate) Pool<ManagedConn>);
+pub struct Db(
+    /// Taken by [`Db::close`], after which every clone fails to hand out connections.
+    Arc<RwLock<Option<Pool<ManagedConn>>>>,
+    /// The statement timeout connections are opened with; zero when disabled.
+    Duration,
+);
 pub type Error = diesel::result::Error;
+pub use diesel::{result::DatabaseErrorKind, PgConnection};
+
+/// Errors returned from [`Db::write`] transactions, which are retried when they failed only
+/// because a concurrent transaction touched the same rows.
+pub trait TransactionError: From<Error> {
+    fn is_serialization_failure(&self) -> bool;
+}
+
+impl TransactionError for Error {
+    fn is_serialization_failure(&self) -> bool {
+        matches!(
+            self,
+            Error::DatabaseError(DatabaseErrorKind::SerializationFailure, _)
+        )
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct PoolState {
+    pub connections: u32,
+    pub idle_connections: u32,
+    pub max_size: u32,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct PoolConfig {
+    /// Must be positive.
+    pub max_size: u32,
+    /// How long to wait for a connection before failing. Must be positive.
+    pub connection_timeout: Duration,
+    /// Longest any statement may run, set on each connection as it is opened; zero disables it.
+    pub statement_timeout: Duration,
+}
 
-pub fn build(database_url: &str) -> Result<Db, r2d2::Error> {
+pub fn build(database_url: &str, config: PoolConfig) -> Result<Db, r2d2::Error> {
     let pool = Pool::<ManagedConn>::builder()
-        .max_size(2)
+        .max_size(config.max_size)
+        .connection_timeout(config.connection_timeout)
+        .connection_customizer(Box::new(StatementTimeout(config.statement_timeout)))
         .build(ConnectionManager::new(database_url))?;
 
-    Ok(Db(pool))
+    // Registered up front, since it is otherwise only scraped once something increments it.
+    Lazy::force(&metrics::TRANSACTION_RETRIES);
+
+    Ok(Db::new(pool, config.statement_timeout))
+}
+
+#[derive(Debug)]
+struct StatementTimeout(Duration);
+
+impl CustomizeConnection<PgConnection, diesel::r2d2::Error> for StatementTimeout {
+    fn on_acquire(&self, conn: &mut PgConnection) -> Result<(), diesel::r2d2::Error> {
+        diesel::sql_query(format!("SET statement_timeout = {}", self.0.as_millis()))
+            .execute(conn)
+            .map(drop)
+            .map_err(diesel::r2d2::Error::QueryError)
+    }
 }
 
 impl Db {
+    pub(crate) fn new(pool: Pool<ManagedConn>, statement_timeout: Duration) -> Self {
+        Self(Arc::new(RwLock::new(Some(pool))), statement_timeout)
+    }
+
+    /// The pool, unless [`Db::close`] has taken it.
+    pub(crate) fn pool(&self) -> Option<Pool<ManagedConn>> {
+        self.0.read().expect("Pool lock poisoned").clone()
+    }
+
+    /// Runs `f` in a transaction inside a `db.write` span, which records how long it waited for a
+    /// connection, how long the transaction took and any error. The same timings and the outcome
+    /// are exported as metrics.
+    ///
+    /// Transactions that fail to serialize against a concurrent one are run again, up to
+    /// [`MAX_ATTEMPTS`] times in all, on the same connection, so `f` may be called more than once.
+    ///
+    /// Within a [`deadline::scope`] the wait for a connection and every statement are cut short
+    /// at the deadline. If the deadline has passed, or the returned future was dropped, by the
+    /// time `f` finishes, the transaction is rolled back rather than committed. Either way it
+    /// fails with a database error, as when Postgres cancels a statement for its timeout.
     pub async fn write<R, E, F>(&self, f: F) -> Result<R, E>
     where
         R: 'static + Send,
-        E: 'static + Send + From<diesel::result::Error>,
-        F: 'static + Send + FnOnce(&mut PgConnection) -> Result<R, E>,
+        E: 'static + Send + Display + TransactionError,
+        F: 'static + Send + FnMut(&mut PgConnection) -> Result<R, E>,
     {
         let db = self.clone();
-        tokio::task::spawn_blocking(move || write(&mut *db.conn_or_rollback()?, f))
-            .await
-            .unwrap()
+        let interrupt = Interrupt {
+            deadline: deadline::current(),
+            cancelled: Arc::default(),
+        };
+        let _cancel_on_drop = CancelOnDrop(interrupt.cancelled.clone());
+        let span = tracing::info_span!(
+            "db.write",
+            pool_wait_ms = field::Empty,
+            transaction_ms = field::Empty,
+            error = field::Empty,
+        );
+
+        // The span closes, and is logged, here rather than on the blocking thread, so that it
+        // is reported within the caller's span.
+        let blocking_span = span.clone();
+        tokio::task::spawn_blocking(move || {
+            let span = blocking_span;
+            let _entered = span.enter();
+
+            let started = Instant::now();
+            metrics::POOL_WAITERS.inc();
+            let conn = interrupt.check().and_then(|()| db.conn(&interrupt));
+            metrics::POOL_WAITERS.dec();
+            metrics::POOL_WAIT_SECONDS.observe(started.elapsed().as_secs_f64());
+
+            let result = conn.map_err(E::from).and_then(|mut conn| {
+                span.record("pool_wait_ms", elapsed_ms(started));
+
+                let started = Instant::now();
+                let mut f = f;
+                let mut attempts = 1;
+                let result = loop {
+                    let statement_timeout = interrupt.statement_timeout(db.1);
+                    let result: Result<R, E> = write(&mut conn, |conn| {
+                        if let Some(timeout) = statement_timeout {
+                            set_local_statement_timeout(conn, timeout)?;
+                        }
+                        let result = f(conn)?;
+                        interrupt.check()?;
+                        Ok(result)
+                    });
+                    match result {
+                        Err(e)
+                            if e.is_serialization_failure()
+                                && attempts < MAX_ATTEMPTS
+                                && interrupt.check().is_ok() =>
+                        {
+                            attempts += 1;
+                            metrics::TRANSACTION_RETRIES.inc();
+                        }
+                        result => break result,
+                    }
+                };
+                span.record("transaction_ms", elapsed_ms(started));
+                metrics::TRANSACTION_SECONDS.observe(started.elapsed().as_secs_f64());
+                result
+            });
+
+            let outcome = match &result {
+                Ok(_) => "committed",
+                Err(e) => {
+                    span.record("error", field::display(e));
+                    "rolled_back"
+                }
+            };
+            metrics::TRANSACTIONS.with_label_values(&[outcome]).inc();
+            result
+        })
+        .await
+        .unwrap()
     }
 
-    fn conn_or_rollback(&self) -> QueryResult<PooledConn> {
-        self.0
-            .get()
-            .map_err(|_| diesel::result::Error::RollbackTransaction)
+    /// Whether a pooled connection can run a trivial query within `timeout`.
+    pub async fn ping(&self, timeout: Duration) -> bool {
+        let db = self.clone();
+        let ping = tokio::task::spawn_blocking(move || {
+            let mut conn = db.pool()?.get_timeout(timeout).ok()?;
+            diesel::sql_query("SELECT 1").execute(&mut *conn).ok()
+        });
+
+        matches!(tokio::time::timeout(timeout, ping).await, Ok(Ok(Some(_))))
     }
+
+    /// All zeros once the pool is closed.
+    pub fn state(&self) -> PoolState {
+        let Some(pool) = self.pool() else {
+            return PoolState {
+                connections: 0,
+                idle_connections: 0,
+                max_size: 0,
+            };
+        };
+        let state = pool.state();
+
+        PoolState {
+            connections: state.connections,
+            idle_connections: state.idle_connections,
+            max_size: pool.max_size(),
+        }
+    }
+
+    /// Copies the current [`Db::state`] into the pool gauges, so they are fresh when scraped.
+    pub fn record_pool_metrics(&self) {
+        let state = self.state();
+
+        metrics::POOL_CONNECTIONS.set(state.connections.into());
+        metrics::POOL_IDLE_CONNECTIONS.set(state.idle_connections.into());
+        metrics::POOL_MAX_SIZE.set(state.max_size.into());
+    }
+
+    /// Waits up to `timeout` for every checked-out connection to return to the pool, then closes
+    /// it for every clone of this handle. Idle connections are closed right away and those still
+    /// in use once they are returned. Returns whether the pool drained in time.
+    pub async fn close(self, timeout: Duration) -> bool {
+        let Some(pool) = self.pool() else {
+            return true;
+        };
+
+        let drained = async {
+            loop {
+                let state = pool.state();
+                if state.idle_connections == state.connections {
+                    return;
+                }
+                tokio::time::sleep(CLOSE_POLL_INTERVAL).await;
+            }
+        };
+        let drained = tokio::time::timeout(timeout, drained).await.is_ok();
+
+        // Connections hold a handle to the pool, which disconnects once the last one is gone.
+        self.0.write().expect("Pool lock poisoned").take();
+        drained
+    }
+
+    /// A pooled connection, waiting no longer than the pool's timeout or the deadline.
+    fn conn(&self, interrupt: &Interrupt) -> QueryResult<PooledConn> {
+        let pool = self.pool().ok_or_else(closed)?;
+        let pooled = match interrupt.remaining() {
+            Some(remaining) => pool.get_timeout(remaining.min(pool.connection_timeout())),
+            None => pool.get(),
+        };
+
+        pooled.map_err(|_| match interrupt.check() {
+            Ok(()) => diesel::result::Error::RollbackTransaction,
+            Err(e) => e,
+        })
+    }
+}
+
+fn closed() -> Error {
+    Error::DatabaseError(
+        DatabaseErrorKind::ClosedConnection,
+        Box::new("Database pool closed".to_owned()),
+    )
+}
+
+fn deadline_exceeded() -> Error {
+    Error::DatabaseError(
+        DatabaseErrorKind::Unknown,
+        Box::new("Deadline exceeded".to_owned()),
+    )
+}
+
+/// Why the blocking side of [`Db::write`] should stop early.
+struct Interrupt {
+    deadline: Option<Instant>,
+    /// Set once the caller drops the [`Db::write`] future.
+    cancelled: Arc<AtomicBool>,
+}
+
+impl Interrupt {
+    fn remaining(&self) -> Option<Duration> {
+        self.deadline
+            .map(|deadline| deadline.saturating_duration_since(Instant::now()))
+    }
+
+    fn check(&self) -> QueryResult<()> {
+        if self.cancelled.load(Ordering::Relaxed) || self.remaining() == Some(Duration::ZERO) {
+            Err(deadline_exceeded())
+        } else {
+            Ok(())
+        }
+    }
+
+    /// The time left, when it is shorter than the timeout the connection already has.
+    fn statement_timeout(&self, configured: Duration) -> Option<Duration> {
+        self.remaining()
+            .filter(|remaining| configured.is_zero() || *remaining < configured)
+    }
+}
+
+struct CancelOnDrop(Arc<AtomicBool>);
+
+impl Drop for CancelOnDrop {
+    fn drop(&mut self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+}
+
+/// Lowers `statement_timeout` until the end of the current transaction.
+fn set_local_statement_timeout(conn: &mut PgConnection, timeout: Duration) -> QueryResult<()> {
+    // Zero would disable the timeout instead.
+    let millis = timeout.as_millis().max(1);
+    diesel::sql_query(format!("SET LOCAL statement_timeout = {millis}"))
+        .execute(conn)
+        .map(drop)
+}
+
+fn elapsed_ms(since: Instant) -> f64 {
+    since.elapsed().as_secs_f64() * 1000.0
 }
 
 #[cfg(not(feature = "test"))]
 fn write<R, E, F>(conn: &mut PgConnection, f: F) -> Result<R, E>
 where
-    R: 'static + Send,
-    E: 'static + Send + From<diesel::result::Error>,
-    F: 'static + Send + FnOnce(&mut PgConnection) -> Result<R, E>,
+    E: From<diesel::result::Error>,
+    F: FnOnce(&mut PgConnection) -> Result<R, E>,
 {
     let builder = conn.build_transaction();
     let mut transaction = builder.repeatable_read().read_write();
@@ -51,9 +347,169 @@ where
 #[cfg(feature = "test")]
 fn write<R, E, F>(conn: &mut PgConnection, f: F) -> Result<R, E>
 where
-    R: 'static + Send,
-    E: 'static + Send + From<diesel::result::Error>,
-    F: 'static + Send + FnOnce(&mut PgConnection) -> Result<R, E>,
+    E: From<diesel::result::Error>,
+    F: FnOnce(&mut PgConnection) -> Result<R, E>,
 {
     diesel::Connection::transaction(conn, f)
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[tokio::test]
+    async fn close_waits_for_checked_out_connections() {
+        let db = crate::test::db();
+        let other = db.clone();
+
+        let conn = db.pool().unwrap().get().unwrap();
+        assert!(!db.close(Duration::from_millis(100)).await);
+        drop(conn);
+
+        // Closed for every handle, even though it did not drain in time.
+        assert!(!other.ping(Duration::from_millis(100)).await);
+        assert_eq!(other.state().connections, 0);
+        assert!(other.write(|_| Ok::<_, Error>(())).await.is_err());
+        assert!(other.close(Duration::from_millis(100)).await);
+
+        assert!(crate::test::db().close(Duration::from_millis(100)).await);
+    }
+
+    #[test]
+    fn build_applies_statement_timeout() {
+        let db = build(
+            &crate::test::URL,
+            PoolConfig {
+                max_size: 1,
+                connection_timeout: Duration::from_secs(5),
+                statement_timeout: Duration::from_millis(1500),
+            },
+        )
+        .unwrap();
+
+        #[derive(diesel::QueryableByName)]
+        struct Setting {
+            #[diesel(sql_type = diesel::sql_types::Text)]
+            statement_timeout: String,
+        }
+
+        let setting: Setting = diesel::sql_query("SHOW statement_timeout")
+            .get_result(&mut *db.pool().unwrap().get().unwrap())
+            .unwrap();
+        assert_eq!(setting.statement_timeout, "1500ms");
+    }
+
+    #[tokio::test]
+    async fn write_counts_transactions_by_outcome() {
+        let db = crate::test::db();
+        let count = |outcome| metrics::TRANSACTIONS.with_label_values(&[outcome]).get();
+        let (committed, rolled_back) = (count("committed"), count("rolled_back"));
+
+        db.write(|_| Ok::<_, Error>(())).await.unwrap();
+        db.write(|_| Err::<(), _>(Error::NotFound))
+            .await
+            .unwrap_err();
+
+        assert_eq!(count("committed"), committed + 1);
+        assert_eq!(count("rolled_back"), rolled_back + 1);
+
+        db.record_pool_metrics();
+        assert_eq!(metrics::POOL_MAX_SIZE.get(), i64::from(db.state().max_size));
+    }
+
+    #[tokio::test]
+    async fn write_retries_serialization_failures() {
+        let db = crate::test::db();
+        let retries = metrics::TRANSACTION_RETRIES.get();
+        let conflict = || {
+            Error::DatabaseError(
+                DatabaseErrorKind::SerializationFailure,
+                Box::new("could not serialize access".to_owned()),
+            )
+        };
+
+        let mut attempts = 0;
+        let result = db
+            .write(move |_| {
+                attempts += 1;
+                if attempts < MAX_ATTEMPTS {
+                    Err(conflict())
+                } else {
+                    Ok(attempts)
+                }
+            })
+            .await;
+        assert_eq!(result.unwrap(), MAX_ATTEMPTS);
+
+        let result = db.write(move |_| Err::<(), _>(conflict())).await;
+        assert!(result.unwrap_err().is_serialization_failure());
+
+        assert_eq!(
+            metrics::TRANSACTION_RETRIES.get(),
+            retries + 2 * u64::from(MAX_ATTEMPTS - 1)
+        );
+    }
+
+    #[tokio::test]
+    async fn write_stops_retrying_at_the_deadline() {
+        let db = crate::test::db();
+        let attempts = Arc::new(std::sync::atomic::AtomicU32::new(0));
+        let counted = attempts.clone();
+
+        let result = deadline::scope(
+            Instant::now() + Duration::from_millis(50),
+            db.write(move |_| {
+                counted.fetch_add(1, Ordering::Relaxed);
+                std::thread::sleep(Duration::from_millis(100));
+                Err::<(), _>(Error::DatabaseError(
+                    DatabaseErrorKind::SerializationFailure,
+                    Box::new("could not serialize access".to_owned()),
+                ))
+            }),
+        )
+        .await;
+
+        assert!(result.unwrap_err().is_serialization_failure());
+        assert_eq!(attempts.load(Ordering::Relaxed), 1);
+    }
+
+    #[tokio::test]
+    async fn write_cuts_statements_short_at_the_deadline() {
+        let db = crate::test::db();
+        let started = Instant::now();
+
+        let result = deadline::scope(
+            started + Duration::from_millis(100),
+            db.write(|conn| diesel::sql_query("SELECT pg_sleep(5)").execute(conn)),
+        )
+        .await;
+
+        assert!(matches!(result, Err(Error::DatabaseError(..))));
+        assert!(started.elapsed() < Duration::from_secs(1));
+    }
+
+    #[tokio::test]
+    async fn write_rolls_back_once_the_caller_goes_away() {
+        let db = crate::test::db();
+        let (created, was_created) = tokio::sync::oneshot::channel();
+        let mut created = Some(created);
+
+        let write = db.write(move |conn| {
+            let id = crate::queries::users::create(conn, time::OffsetDateTime::now_utc())?;
+            created.take().unwrap().send(id).unwrap();
+            std::thread::sleep(Duration::from_millis(100));
+            Ok::<_, Error>(())
+        });
+        let id = tokio::select! {
+            id = was_created => id.unwrap(),
+            _ = write => unreachable!("The write outlasts the insert"),
+        };
+
+        // Waits for the abandoned write to give its connection back.
+        let found = db
+            .write(move |conn| crate::queries::users::find_by_id(conn, *id))
+            .await
+            .unwrap();
+        assert!(found.is_none());
+    }
+}
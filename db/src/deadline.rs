@@ -0,0 +1,18 @@
+//! Request deadlines, carried into [`crate::Db::write`] without threading them through callers.
+
+use std::{future::Future, time::Instant};
+
+tokio::task_local! {
+    static DEADLINE: Instant;
+}
+
+/// Runs `f` with every [`crate::Db::write`] it makes bounded by `deadline`: waiting for a
+/// connection, each statement and the commit all give up once it passes.
+pub async fn scope<F: Future>(deadline: Instant, f: F) -> F::Output {
+    DEADLINE.scope(deadline, f).await
+}
+
+/// The deadline of the enclosing [`scope`], if any.
+pub(crate) fn current() -> Option<Instant> {
+    DEADLINE.try_with(|deadline| *deadline).ok()
+}
@@ -1,8 +1,12 @@
 mod db;
+pub mod deadline;
+mod metrics;
 pub mod queries;
 pub mod types;
 
-pub use self::db::{build, Db, Error};
+pub use self::db::{
+    build, DatabaseErrorKind, Db, Error, PgConnection, PoolConfig, PoolState, TransactionError,
+};
 pub use ::schema::{enums, schema};
 
 #[cfg(any(test, feature = "test"))]
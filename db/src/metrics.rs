@@ -0,0 +1,75 @@
+//! Pool and transaction metrics, registered in the default Prometheus registry.
+
+use once_cell::sync::Lazy;
+use prometheus::{
+    register_histogram, register_int_counter, register_int_counter_vec, register_int_gauge,
+    Histogram, IntCounter, IntCounterVec, IntGauge,
+};
+
+pub(crate) static POOL_WAIT_SECONDS: Lazy<Histogram> = Lazy::new(|| {
+    register_histogram!(
+        "splitwiser_db_pool_wait_seconds",
+        "Time spent waiting for a pooled connection."
+    )
+    .unwrap()
+});
+
+pub(crate) static POOL_WAITERS: Lazy<IntGauge> = Lazy::new(|| {
+    register_int_gauge!(
+        "splitwiser_db_pool_waiters",
+        "Writes currently waiting for a pooled connection."
+    )
+    .unwrap()
+});
+
+pub(crate) static POOL_CONNECTIONS: Lazy<IntGauge> = Lazy::new(|| {
+    register_int_gauge!(
+        "splitwiser_db_pool_connections",
+        "Connections currently open, idle or in use."
+    )
+    .unwrap()
+});
+
+pub(crate) static POOL_IDLE_CONNECTIONS: Lazy<IntGauge> = Lazy::new(|| {
+    register_int_gauge!(
+        "splitwiser_db_pool_idle_connections",
+        "Open connections not checked out."
+    )
+    .unwrap()
+});
+
+pub(crate) static POOL_MAX_SIZE: Lazy<IntGauge> = Lazy::new(|| {
+    register_int_gauge!(
+        "splitwiser_db_pool_max_size",
+        "Most connections the pool will open."
+    )
+    .unwrap()
+});
+
+pub(crate) static TRANSACTION_SECONDS: Lazy<Histogram> = Lazy::new(|| {
+    register_histogram!(
+        "splitwiser_db_transaction_seconds",
+        "Time spent running write transactions, including the commit."
+    )
+    .unwrap()
+});
+
+/// Write transactions by `outcome`, either `committed` or `rolled_back`, counted once however
+/// many times they were retried.
+pub(crate) static TRANSACTIONS: Lazy<IntCounterVec> = Lazy::new(|| {
+    register_int_counter_vec!(
+        "splitwiser_db_transactions_total",
+        "Write transactions by outcome.",
+        &["outcome"]
+    )
+    .unwrap()
+});
+
+/// Write transactions run again after failing to serialize against a concurrent one.
+pub(crate) static TRANSACTION_RETRIES: Lazy<IntCounter> = Lazy::new(|| {
+    register_int_counter!(
+        "splitwiser_db_transaction_retries_total",
+        "Write transactions retried after a serialization failure."
+    )
+    .unwrap()
+});
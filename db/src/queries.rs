@@ -1,3 +1,8 @@
+pub mod audit_events;
+pub mod balances;
+pub mod integrity;
+pub mod sessions;
+pub mod settlement_invoices;
 pub mod user_expense_installments;
 pub mod user_expenses;
 pub mod user_payments;
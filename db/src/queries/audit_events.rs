@@ -0,0 +1,182 @@
+use diesel::{
+    ExpressionMethods, PgArrayExpressionMethods, PgConnection, QueryDsl, QueryResult, Queryable,
+    RunQueryDsl,
+};
+use schema::{enums::AuditEntityType, schema::audit_events};
+use serde_json::Value;
+use time::OffsetDateTime;
+
+use crate::types::AuditEventId;
+
+#[derive(Debug, Queryable)]
+pub struct AuditEvent {
+    pub id: AuditEventId,
+    /// Absent for operator actions.
+    pub actor_user_id: Option<i32>,
+    pub rpc: String,
+    pub entity_type: AuditEntityType,
+    pub entity_id: i32,
+    pub participant_user_ids: Vec<i32>,
+    pub before: Option<Value>,
+    pub after: Option<Value>,
+    pub created_at: OffsetDateTime,
+}
+
+pub struct CreateParams<'a> {
+    pub actor_user_id: Option<i32>,
+    pub rpc: &'a str,
+    pub entity_type: AuditEntityType,
+    pub entity_id: i32,
+    pub participant_user_ids: &'a [i32],
+    pub before: Option<&'a Value>,
+    pub after: Option<&'a Value>,
+    pub created_at: OffsetDateTime,
+}
+
+pub fn create(conn: &mut PgConnection, p: &CreateParams) -> QueryResult<AuditEventId> {
+    diesel::insert_into(audit_events::table)
+        .values((
+            audit_events::actor_user_id.eq(p.actor_user_id),
+            audit_events::rpc.eq(p.rpc),
+            audit_events::entity_type.eq(p.entity_type),
+            audit_events::entity_id.eq(p.entity_id),
+            audit_events::participant_user_ids.eq(p.participant_user_ids),
+            audit_events::before.eq(p.before),
+            audit_events::after.eq(p.after),
+            audit_events::created_at.eq(p.created_at),
+        ))
+        .returning(audit_events::id)
+        .get_result(conn)
+}
+
+pub struct ListParams {
+    /// Only events this user participates in are returned.
+    pub participant_user_id: i32,
+    pub entity: Option<(AuditEntityType, i32)>,
+    pub actor_user_id: Option<i32>,
+    /// Returns events older than this one, for paging backwards.
+    pub before_id: Option<i32>,
+    pub limit: i64,
+}
+
+/// Lists matching events, newest first.
+pub fn list(conn: &mut PgConnection, p: &ListParams) -> QueryResult<Vec<AuditEvent>> {
+    let mut query = audit_events::table
+        .filter(audit_events::participant_user_ids.contains(vec![p.participant_user_id]))
+        .into_boxed();
+
+    if let Some((entity_type, entity_id)) = p.entity {
+        query = query
+            .filter(audit_events::entity_type.eq(entity_type))
+            .filter(audit_events::entity_id.eq(entity_id));
+    }
+    if let Some(actor_user_id) = p.actor_user_id {
+        query = query.filter(audit_events::actor_user_id.eq(actor_user_id));
+    }
+    if let Some(before_id) = p.before_id {
+        query = query.filter(audit_events::id.lt(before_id));
+    }
+
+    query
+        .order(audit_events::id.desc())
+        .limit(p.limit)
+        .load(conn)
+}
+
+#[cfg(test)]
+mod test {
+    use serde_json::json;
+
+    use super::*;
+    use crate::{queries::users, test};
+
+    fn record(conn: &mut PgConnection, actor: i32, participants: &[i32], entity_id: i32) {
+        create(
+            conn,
+            &CreateParams {
+                actor_user_id: Some(actor),
+                rpc: "CreateExpense",
+                entity_type: AuditEntityType::UserExpense,
+                entity_id,
+                participant_user_ids: participants,
+                before: None,
+                after: Some(&json!({ "id": entity_id })),
+                created_at: OffsetDateTime::now_utc(),
+            },
+        )
+        .unwrap();
+    }
+
+    fn params(participant_user_id: i32) -> ListParams {
+        ListParams {
+            participant_user_id,
+            entity: None,
+            actor_user_id: None,
+            before_id: None,
+            limit: 10,
+        }
+    }
+
+    #[test]
+    fn lists_events_of_participants() {
+        let mut conn = test::conn();
+        let u0 = *users::create(&mut conn, OffsetDateTime::now_utc()).unwrap();
+        let u1 = *users::create(&mut conn, OffsetDateTime::now_utc()).unwrap();
+        let u2 = *users::create(&mut conn, OffsetDateTime::now_utc()).unwrap();
+
+        record(&mut conn, u0, &[u0, u1], 1);
+        record(&mut conn, u1, &[u0, u1], 2);
+        record(&mut conn, u2, &[u2], 3);
+
+        let events = list(&mut conn, &params(u0)).unwrap();
+        assert_eq!(
+            events.iter().map(|e| e.entity_id).collect::<Vec<_>>(),
+            [2, 1]
+        );
+        assert_eq!(events[0].after, Some(json!({ "id": 2 })));
+
+        let by_actor = list(
+            &mut conn,
+            &ListParams {
+                actor_user_id: Some(u1),
+                ..params(u0)
+            },
+        )
+        .unwrap();
+        assert_eq!(by_actor.len(), 1);
+
+        let by_entity = list(
+            &mut conn,
+            &ListParams {
+                entity: Some((AuditEntityType::UserExpense, 3)),
+                ..params(u0)
+            },
+        )
+        .unwrap();
+        assert!(by_entity.is_empty());
+
+        let older = list(
+            &mut conn,
+            &ListParams {
+                before_id: Some(*events[0].id),
+                ..params(u1)
+            },
+        )
+        .unwrap();
+        assert_eq!(older.len(), 1);
+        assert_eq!(older[0].entity_id, 1);
+    }
+
+    #[test]
+    fn events_are_append_only() {
+        let mut conn = test::conn();
+        let u0 = *users::create(&mut conn, OffsetDateTime::now_utc()).unwrap();
+        record(&mut conn, u0, &[u0], 1);
+
+        let res = diesel::delete(audit_events::table).execute(&mut conn);
+        assert!(matches!(
+            res,
+            Err(diesel::result::Error::DatabaseError(_, _))
+        ));
+    }
+}
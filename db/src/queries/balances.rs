@@ -0,0 +1,287 @@
+use diesel::{
+    BoolExpressionMethods, ExpressionMethods, PgConnection, QueryDsl, QueryResult, RunQueryDsl,
+};
+use schema::{
+    enums::{UserExpensesChargeMethod, UserPaymentStatus},
+    schema::{user_expense_installments, user_expenses, user_payments, user_revenues},
+};
+use time::OffsetDateTime;
+
+/// Net amount, in cents, that `debtor` owes `creditor` as of `as_of`.
+///
+/// The charged user of an expense owes the chargee its share of every installment already
+/// charged, and payments between the two are discounted. Rejected payments never count, and
+/// pending ones only when `include_unconfirmed` is set. A negative result means `creditor` is
+/// the one who owes.
+pub fn owed(
+    conn: &mut PgConnection,
+    debtor: i32,
+    creditor: i32,
+    as_of: OffsetDateTime,
+    include_unconfirmed: bool,
+) -> QueryResult<i64> {
+    let installments: Vec<(i32, UserExpensesChargeMethod, i64)> = user_expense_installments::table
+        .inner_join(user_expenses::table)
+        .filter(
+            user_expenses::charged_user_id
+                .eq(debtor)
+                .and(user_expenses::chargee_user_id.eq(creditor))
+                .or(user_expenses::charged_user_id
+                    .eq(creditor)
+                    .and(user_expenses::chargee_user_id.eq(debtor))),
+        )
+        .filter(user_expense_installments::charged_at.le(as_of))
+        .select((
+            user_expenses::charged_user_id,
+            user_expenses::charge_method,
+            user_expense_installments::amount_cents,
+        ))
+        .load(conn)?;
+
+    let debtor_revenue = revenue(conn, debtor, as_of)?;
+    let creditor_revenue = revenue(conn, creditor, as_of)?;
+
+    let charged = installments
+        .into_iter()
+        .map(|(charged_user_id, method, amount_cents)| {
+            let (charged_revenue, chargee_revenue) = if charged_user_id == debtor {
+                (debtor_revenue, creditor_revenue)
+            } else {
+                (creditor_revenue, debtor_revenue)
+            };
+
+            let share = share(method, amount_cents, charged_revenue, chargee_revenue);
+
+            if charged_user_id == debtor {
+                share
+            } else {
+                -share
+            }
+        })
+        .sum::<i64>();
+
+    let statuses: &[UserPaymentStatus] = if include_unconfirmed {
+        &[UserPaymentStatus::Confirmed, UserPaymentStatus::Pending]
+    } else {
+        &[UserPaymentStatus::Confirmed]
+    };
+
+    let payments: Vec<(i32, i64)> = user_payments::table
+        .filter(
+            user_payments::payer_user_id
+                .eq(debtor)
+                .and(user_payments::payee_user_id.eq(creditor))
+                .or(user_payments::payer_user_id
+                    .eq(creditor)
+                    .and(user_payments::payee_user_id.eq(debtor))),
+        )
+        .filter(user_payments::payed_at.le(as_of))
+        .filter(user_payments::status.eq_any(statuses))
+        .select((user_payments::payer_user_id, user_payments::amount_cents))
+        .load(conn)?;
+
+    let payed = payments
+        .into_iter()
+        .map(|(payer_user_id, amount_cents)| {
+            if payer_user_id == debtor {
+                amount_cents
+            } else {
+                -amount_cents
+            }
+        })
+        .sum::<i64>();
+
+    Ok(charged - payed)
+}
+
+fn revenue(conn: &mut PgConnection, user_id: i32, as_of: OffsetDateTime) -> QueryResult<i64> {
+    let amounts: Vec<i64> = user_revenues::table
+        .filter(user_revenues::user_id.eq(user_id))
+        .filter(user_revenues::incoming_at.le(as_of))
+        .select(user_revenues::amount_cents)
+        .load(conn)?;
+
+    Ok(amounts.into_iter().sum())
+}
+
+fn share(
+    method: UserExpensesChargeMethod,
+    amount_cents: i64,
+    charged_revenue: i64,
+    chargee_revenue: i64,
+) -> i64 {
+    let total_revenue = charged_revenue + chargee_revenue;
+
+    match method {
+        UserExpensesChargeMethod::Full => amount_cents,
+        UserExpensesChargeMethod::Even => amount_cents / 2,
+        UserExpensesChargeMethod::Proportional if total_revenue == 0 => amount_cents / 2,
+        UserExpensesChargeMethod::Proportional => {
+            (i128::from(amount_cents) * i128::from(charged_revenue) / i128::from(total_revenue))
+                as i64
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        queries::{user_expense_installments, user_payments, user_revenues, users},
+        test,
+    };
+    use time::Duration;
+
+    fn expense(
+        conn: &mut PgConnection,
+        chargee_user_id: i32,
+        charged_user_id: i32,
+        amount_cents: i64,
+        charge_method: UserExpensesChargeMethod,
+    ) {
+        let now = OffsetDateTime::now_utc();
+
+        let user_expense_id = crate::queries::user_expenses::create(
+            conn,
+            &crate::queries::user_expenses::CreateParams {
+                created_by: chargee_user_id,
+                amount_cents,
+                description: None,
+                chargee_user_id,
+                charged_user_id,
+                begin_charging_at: now,
+                charge_method,
+                created_at: now,
+            },
+        )
+        .unwrap();
+
+        user_expense_installments::create(
+            conn,
+            &[
+                user_expense_installments::CreateParams {
+                    user_expense_id,
+                    charged_at: now - Duration::days(1),
+                    amount_cents: amount_cents / 2,
+                },
+                user_expense_installments::CreateParams {
+                    user_expense_id,
+                    charged_at: now + Duration::weeks(4),
+                    amount_cents: amount_cents / 2,
+                },
+            ],
+        )
+        .unwrap();
+    }
+
+    fn revenue(conn: &mut PgConnection, user_id: i32, amount_cents: i64) {
+        user_revenues::create(
+            conn,
+            &user_revenues::CreateParams {
+                user_id,
+                amount_cents,
+                description: None,
+                incoming_at: OffsetDateTime::now_utc() - Duration::days(2),
+                created_at: OffsetDateTime::now_utc(),
+            },
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn only_charged_installments_are_owed() {
+        let mut conn = test::conn();
+        let u0 = *users::create(&mut conn, OffsetDateTime::now_utc()).unwrap();
+        let u1 = *users::create(&mut conn, OffsetDateTime::now_utc()).unwrap();
+
+        expense(&mut conn, u0, u1, 1000, UserExpensesChargeMethod::Full);
+
+        let now = OffsetDateTime::now_utc();
+        assert_eq!(owed(&mut conn, u1, u0, now, true).unwrap(), 500);
+        assert_eq!(owed(&mut conn, u0, u1, now, true).unwrap(), -500);
+    }
+
+    #[test]
+    fn expenses_in_both_directions_are_netted() {
+        let mut conn = test::conn();
+        let u0 = *users::create(&mut conn, OffsetDateTime::now_utc()).unwrap();
+        let u1 = *users::create(&mut conn, OffsetDateTime::now_utc()).unwrap();
+
+        expense(&mut conn, u0, u1, 1000, UserExpensesChargeMethod::Even);
+        expense(&mut conn, u1, u0, 400, UserExpensesChargeMethod::Full);
+
+        let now = OffsetDateTime::now_utc();
+        assert_eq!(owed(&mut conn, u1, u0, now, true).unwrap(), 250 - 200);
+    }
+
+    #[test]
+    fn proportional_expenses_follow_revenues() {
+        let mut conn = test::conn();
+        let u0 = *users::create(&mut conn, OffsetDateTime::now_utc()).unwrap();
+        let u1 = *users::create(&mut conn, OffsetDateTime::now_utc()).unwrap();
+
+        revenue(&mut conn, u0, 3000);
+        revenue(&mut conn, u1, 1000);
+        expense(
+            &mut conn,
+            u0,
+            u1,
+            1000,
+            UserExpensesChargeMethod::Proportional,
+        );
+
+        let now = OffsetDateTime::now_utc();
+        assert_eq!(owed(&mut conn, u1, u0, now, true).unwrap(), 125);
+    }
+
+    fn payment(
+        conn: &mut PgConnection,
+        payer_user_id: i32,
+        payee_user_id: i32,
+        status: UserPaymentStatus,
+    ) {
+        user_payments::create(
+            conn,
+            &user_payments::CreateParams {
+                created_by: payer_user_id,
+                amount_cents: 300,
+                payee_user_id,
+                payer_user_id,
+                payed_at: OffsetDateTime::now_utc() - Duration::hours(1),
+                status,
+                method: crate::enums::UserPaymentMethod::Cash,
+                proof: None,
+                created_at: OffsetDateTime::now_utc(),
+            },
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn payments_are_discounted() {
+        let mut conn = test::conn();
+        let u0 = *users::create(&mut conn, OffsetDateTime::now_utc()).unwrap();
+        let u1 = *users::create(&mut conn, OffsetDateTime::now_utc()).unwrap();
+
+        expense(&mut conn, u0, u1, 1000, UserExpensesChargeMethod::Full);
+        payment(&mut conn, u1, u0, UserPaymentStatus::Confirmed);
+
+        let now = OffsetDateTime::now_utc();
+        assert_eq!(owed(&mut conn, u1, u0, now, true).unwrap(), 200);
+    }
+
+    #[test]
+    fn unconfirmed_payments_are_optional() {
+        let mut conn = test::conn();
+        let u0 = *users::create(&mut conn, OffsetDateTime::now_utc()).unwrap();
+        let u1 = *users::create(&mut conn, OffsetDateTime::now_utc()).unwrap();
+
+        expense(&mut conn, u0, u1, 1000, UserExpensesChargeMethod::Full);
+        payment(&mut conn, u1, u0, UserPaymentStatus::Pending);
+        payment(&mut conn, u1, u0, UserPaymentStatus::Rejected);
+
+        let now = OffsetDateTime::now_utc();
+        assert_eq!(owed(&mut conn, u1, u0, now, true).unwrap(), 200);
+        assert_eq!(owed(&mut conn, u1, u0, now, false).unwrap(), 500);
+    }
+}
@@ -0,0 +1,167 @@
+use diesel::{
+    sql_types::{BigInt, Integer},
+    ExpressionMethods, PgConnection, QueryDsl, QueryResult, QueryableByName, RunQueryDsl,
+};
+use schema::{
+    enums::{SettlementInvoiceStatus, UserPaymentStatus},
+    schema::{settlement_invoices, user_payments},
+};
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum Issue {
+    /// Installments should add up to the expense amount, falling short by less than a cent each
+    /// since amounts are split with integer division.
+    UnbalancedInstallments {
+        expense_id: i32,
+        amount_cents: i64,
+        installments_cents: i64,
+        installments: i64,
+    },
+    /// A settlement invoice was paid but no payment was recorded for it.
+    UnrecordedSettlement { invoice_id: i32 },
+    /// A payment is still pending although it was reviewed.
+    ReviewedPendingPayment { payment_id: i32 },
+}
+
+pub fn check(conn: &mut PgConnection) -> QueryResult<Vec<Issue>> {
+    let mut issues = unbalanced_installments(conn)?;
+
+    issues.extend(
+        settlement_invoices::table
+            .filter(settlement_invoices::status.eq(SettlementInvoiceStatus::Paid))
+            .filter(settlement_invoices::user_payment_id.is_null())
+            .select(settlement_invoices::id)
+            .order(settlement_invoices::id)
+            .load(conn)?
+            .into_iter()
+            .map(|invoice_id| Issue::UnrecordedSettlement { invoice_id }),
+    );
+
+    issues.extend(
+        user_payments::table
+            .filter(user_payments::status.eq(UserPaymentStatus::Pending))
+            .filter(user_payments::reviewed_at.is_not_null())
+            .select(user_payments::id)
+            .order(user_payments::id)
+            .load(conn)?
+            .into_iter()
+            .map(|payment_id| Issue::ReviewedPendingPayment { payment_id }),
+    );
+
+    Ok(issues)
+}
+
+/// Only the offending expenses leave the database, however many installments there are.
+fn unbalanced_installments(conn: &mut PgConnection) -> QueryResult<Vec<Issue>> {
+    #[derive(QueryableByName)]
+    struct Row {
+        #[diesel(sql_type = Integer)]
+        expense_id: i32,
+        #[diesel(sql_type = BigInt)]
+        amount_cents: i64,
+        #[diesel(sql_type = BigInt)]
+        installments_cents: i64,
+        #[diesel(sql_type = BigInt)]
+        installments: i64,
+    }
+
+    let rows: Vec<Row> = diesel::sql_query(
+        "SELECT user_expenses.id AS expense_id,
+                user_expenses.amount_cents,
+                COALESCE(SUM(user_expense_installments.amount_cents), 0)::BIGINT
+                    AS installments_cents,
+                COUNT(user_expense_installments.id) AS installments
+         FROM user_expenses
+         LEFT JOIN user_expense_installments
+             ON user_expense_installments.user_expense_id = user_expenses.id
+         GROUP BY user_expenses.id
+         HAVING user_expenses.amount_cents
+                - COALESCE(SUM(user_expense_installments.amount_cents), 0)
+             NOT BETWEEN 0 AND COUNT(user_expense_installments.id) - 1
+         ORDER BY user_expenses.id",
+    )
+    .load(conn)?;
+
+    Ok(rows
+        .into_iter()
+        .map(|row| Issue::UnbalancedInstallments {
+            expense_id: row.expense_id,
+            amount_cents: row.amount_cents,
+            installments_cents: row.installments_cents,
+            installments: row.installments,
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod test {
+    use schema::enums::UserExpensesChargeMethod;
+    use time::OffsetDateTime;
+
+    use super::*;
+    use crate::{
+        queries::{user_expense_installments, user_expenses, users},
+        test,
+    };
+
+    fn expense(conn: &mut PgConnection, amount_cents: i64, installments: &[i64]) -> i32 {
+        let u0 = *users::create(conn, OffsetDateTime::now_utc()).unwrap();
+        let u1 = *users::create(conn, OffsetDateTime::now_utc()).unwrap();
+
+        let user_expense_id = user_expenses::create(
+            conn,
+            &user_expenses::CreateParams {
+                created_by: u0,
+                amount_cents,
+                description: None,
+                chargee_user_id: u0,
+                charged_user_id: u1,
+                begin_charging_at: OffsetDateTime::now_utc(),
+                charge_method: UserExpensesChargeMethod::Even,
+                created_at: OffsetDateTime::now_utc(),
+            },
+        )
+        .unwrap();
+
+        let installments: Vec<_> = installments
+            .iter()
+            .map(|&amount_cents| user_expense_installments::CreateParams {
+                user_expense_id,
+                charged_at: OffsetDateTime::now_utc(),
+                amount_cents,
+            })
+            .collect();
+        user_expense_installments::create(conn, &installments).unwrap();
+
+        *user_expense_id
+    }
+
+    #[test]
+    fn finds_unbalanced_installments() {
+        let mut conn = test::conn();
+        let balanced = expense(&mut conn, 100, &[33, 33, 33]);
+        let short = expense(&mut conn, 100, &[33, 33]);
+        let empty = expense(&mut conn, 100, &[]);
+        let over = expense(&mut conn, 100, &[50, 51]);
+
+        let issues = check(&mut conn).unwrap();
+        let unbalanced: Vec<_> = issues
+            .iter()
+            .filter_map(|issue| match issue {
+                Issue::UnbalancedInstallments { expense_id, .. } => Some(*expense_id),
+                _ => None,
+            })
+            .collect();
+
+        assert!(!unbalanced.contains(&balanced));
+        assert!(unbalanced.contains(&short));
+        assert!(unbalanced.contains(&empty));
+        assert!(unbalanced.contains(&over));
+        assert!(issues.contains(&Issue::UnbalancedInstallments {
+            expense_id: short,
+            amount_cents: 100,
+            installments_cents: 66,
+            installments: 2,
+        }));
+    }
+}
@@ -0,0 +1,232 @@
+use diesel::{
+    BoolExpressionMethods, ExpressionMethods, OptionalExtension, PgConnection, QueryDsl,
+    QueryResult, Queryable, RunQueryDsl,
+};
+use schema::schema::{sessions, users};
+use time::OffsetDateTime;
+
+use crate::types::SessionId;
+
+#[derive(Debug, Queryable)]
+pub struct Session {
+    pub id: SessionId,
+    pub user_id: i32,
+    pub refresh_token_hash: String,
+    pub previous_refresh_token_hash: Option<String>,
+    pub device: Option<String>,
+    pub created_at: OffsetDateTime,
+    pub refreshed_at: OffsetDateTime,
+    pub expires_at: OffsetDateTime,
+    pub revoked_at: Option<OffsetDateTime>,
+}
+
+pub struct CreateParams<'a> {
+    pub user_id: i32,
+    pub refresh_token_hash: &'a str,
+    pub device: Option<&'a str>,
+    pub created_at: OffsetDateTime,
+    pub expires_at: OffsetDateTime,
+}
+
+pub fn create(conn: &mut PgConnection, p: &CreateParams) -> QueryResult<Session> {
+    diesel::insert_into(sessions::table)
+        .values((
+            sessions::user_id.eq(p.user_id),
+            sessions::refresh_token_hash.eq(p.refresh_token_hash),
+            sessions::device.eq(p.device),
+            sessions::created_at.eq(p.created_at),
+            sessions::refreshed_at.eq(p.created_at),
+            sessions::expires_at.eq(p.expires_at),
+        ))
+        .get_result(conn)
+}
+
+/// Finds the session a refresh token belongs to, whether it is the current token or the one it
+/// replaced.
+pub fn find_by_refresh_token_hash(
+    conn: &mut PgConnection,
+    hash: &str,
+) -> QueryResult<Option<Session>> {
+    sessions::table
+        .filter(
+            sessions::refresh_token_hash
+                .eq(hash)
+                .or(sessions::previous_refresh_token_hash.eq(hash)),
+        )
+        .get_result(conn)
+        .optional()
+}
+
+pub struct RotateParams<'a> {
+    pub id: i32,
+    pub old_hash: &'a str,
+    pub new_hash: &'a str,
+    pub refreshed_at: OffsetDateTime,
+    pub expires_at: OffsetDateTime,
+}
+
+/// Only rotates from the current token of a live session, so concurrent refreshes with the same
+/// token cannot both succeed.
+pub fn rotate(conn: &mut PgConnection, p: &RotateParams) -> QueryResult<Option<Session>> {
+    diesel::update(sessions::table)
+        .filter(sessions::id.eq(p.id))
+        .filter(sessions::refresh_token_hash.eq(p.old_hash))
+        .filter(sessions::revoked_at.is_null())
+        .set((
+            sessions::previous_refresh_token_hash.eq(p.old_hash),
+            sessions::refresh_token_hash.eq(p.new_hash),
+            sessions::refreshed_at.eq(p.refreshed_at),
+            sessions::expires_at.eq(p.expires_at),
+        ))
+        .get_result(conn)
+        .optional()
+}
+
+pub fn revoke(
+    conn: &mut PgConnection,
+    id: i32,
+    user_id: i32,
+    revoked_at: OffsetDateTime,
+) -> QueryResult<Option<SessionId>> {
+    diesel::update(sessions::table)
+        .filter(sessions::id.eq(id))
+        .filter(sessions::user_id.eq(user_id))
+        .filter(sessions::revoked_at.is_null())
+        .set(sessions::revoked_at.eq(revoked_at))
+        .returning(sessions::id)
+        .get_result(conn)
+        .optional()
+}
+
+pub fn revoke_all(
+    conn: &mut PgConnection,
+    user_id: i32,
+    revoked_at: OffsetDateTime,
+) -> QueryResult<usize> {
+    diesel::update(sessions::table)
+        .filter(sessions::user_id.eq(user_id))
+        .filter(sessions::revoked_at.is_null())
+        .set(sessions::revoked_at.eq(revoked_at))
+        .execute(conn)
+}
+
+/// Whether access tokens issued for session `id` of `user_id` are still good, which also requires
+/// the user to be active.
+pub fn is_live(
+    conn: &mut PgConnection,
+    id: i32,
+    user_id: i32,
+    now: OffsetDateTime,
+) -> QueryResult<bool> {
+    diesel::select(diesel::dsl::exists(
+        sessions::table
+            .inner_join(users::table)
+            .filter(sessions::id.eq(id))
+            .filter(sessions::user_id.eq(user_id))
+            .filter(sessions::revoked_at.is_null())
+            .filter(sessions::expires_at.gt(now))
+            .filter(users::deactivated_at.is_null()),
+    ))
+    .get_result(conn)
+}
+
+pub fn list_active(
+    conn: &mut PgConnection,
+    user_id: i32,
+    now: OffsetDateTime,
+) -> QueryResult<Vec<Session>> {
+    sessions::table
+        .filter(sessions::user_id.eq(user_id))
+        .filter(sessions::revoked_at.is_null())
+        .filter(sessions::expires_at.gt(now))
+        .order(sessions::id)
+        .load(conn)
+}
+
+#[cfg(test)]
+mod test {
+    use time::Duration;
+
+    use super::*;
+    use crate::{queries::users, test};
+
+    fn setup(conn: &mut PgConnection) -> Session {
+        let user_id = *users::create(conn, OffsetDateTime::now_utc()).unwrap();
+
+        create(
+            conn,
+            &CreateParams {
+                user_id,
+                refresh_token_hash: "h0",
+                device: Some("phone"),
+                created_at: OffsetDateTime::now_utc(),
+                expires_at: OffsetDateTime::now_utc() + Duration::days(1),
+            },
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn rotation_keeps_previous_hash() {
+        let mut conn = test::conn();
+        let session = setup(&mut conn);
+
+        let params = RotateParams {
+            id: *session.id,
+            old_hash: "h0",
+            new_hash: "h1",
+            refreshed_at: OffsetDateTime::now_utc(),
+            expires_at: OffsetDateTime::now_utc() + Duration::days(1),
+        };
+        let rotated = rotate(&mut conn, &params).unwrap().unwrap();
+        assert_eq!(rotated.refresh_token_hash, "h1");
+
+        assert!(rotate(&mut conn, &params).unwrap().is_none());
+
+        let by_old = find_by_refresh_token_hash(&mut conn, "h0")
+            .unwrap()
+            .unwrap();
+        assert_eq!(*by_old.id, *session.id);
+        assert_eq!(by_old.previous_refresh_token_hash.as_deref(), Some("h0"));
+    }
+
+    #[test]
+    fn revoked_sessions_are_not_listed() {
+        let mut conn = test::conn();
+        let session = setup(&mut conn);
+        let now = OffsetDateTime::now_utc();
+
+        assert_eq!(
+            list_active(&mut conn, session.user_id, now).unwrap().len(),
+            1
+        );
+        assert!(is_live(&mut conn, *session.id, session.user_id, now).unwrap());
+        assert!(!is_live(&mut conn, *session.id, session.user_id + 1, now).unwrap());
+
+        assert!(revoke(&mut conn, *session.id, session.user_id + 1, now)
+            .unwrap()
+            .is_none());
+        assert!(revoke(&mut conn, *session.id, session.user_id, now)
+            .unwrap()
+            .is_some());
+        assert!(revoke(&mut conn, *session.id, session.user_id, now)
+            .unwrap()
+            .is_none());
+
+        assert!(list_active(&mut conn, session.user_id, now)
+            .unwrap()
+            .is_empty());
+        assert!(!is_live(&mut conn, *session.id, session.user_id, now).unwrap());
+    }
+
+    #[test]
+    fn sessions_of_deactivated_users_are_not_live() {
+        let mut conn = test::conn();
+        let session = setup(&mut conn);
+        let now = OffsetDateTime::now_utc();
+
+        users::deactivate(&mut conn, session.user_id, now).unwrap();
+
+        assert!(!is_live(&mut conn, *session.id, session.user_id, now).unwrap());
+    }
+}
@@ -0,0 +1,229 @@
+use diesel::{
+    ExpressionMethods, OptionalExtension, PgConnection, QueryDsl, QueryResult, Queryable,
+    RunQueryDsl,
+};
+use schema::{enums::SettlementInvoiceStatus, schema::settlement_invoices};
+use time::OffsetDateTime;
+
+use crate::types::{SettlementInvoiceId, UserPaymentId};
+
+#[derive(Debug, Queryable)]
+pub struct SettlementInvoice {
+    pub id: SettlementInvoiceId,
+    pub payer_user_id: i32,
+    pub payee_user_id: i32,
+    pub amount_cents: i64,
+    pub amount_msats: i64,
+    pub payment_hash: String,
+    pub payment_request: String,
+    pub status: SettlementInvoiceStatus,
+    pub user_payment_id: Option<UserPaymentId>,
+    pub expires_at: OffsetDateTime,
+    pub paid_at: Option<OffsetDateTime>,
+    pub created_at: OffsetDateTime,
+}
+
+pub struct CreateParams<'a> {
+    pub payer_user_id: i32,
+    pub payee_user_id: i32,
+    pub amount_cents: i64,
+    pub amount_msats: i64,
+    pub payment_hash: &'a str,
+    pub payment_request: &'a str,
+    pub expires_at: OffsetDateTime,
+    pub created_at: OffsetDateTime,
+}
+
+pub fn create(conn: &mut PgConnection, p: &CreateParams) -> QueryResult<SettlementInvoice> {
+    diesel::insert_into(settlement_invoices::table)
+        .values((
+            settlement_invoices::payer_user_id.eq(p.payer_user_id),
+            settlement_invoices::payee_user_id.eq(p.payee_user_id),
+            settlement_invoices::amount_cents.eq(p.amount_cents),
+            settlement_invoices::amount_msats.eq(p.amount_msats),
+            settlement_invoices::payment_hash.eq(p.payment_hash),
+            settlement_invoices::payment_request.eq(p.payment_request),
+            settlement_invoices::status.eq(SettlementInvoiceStatus::Pending),
+            settlement_invoices::expires_at.eq(p.expires_at),
+            settlement_invoices::created_at.eq(p.created_at),
+        ))
+        .get_result(conn)
+}
+
+pub fn find_by_id(conn: &mut PgConnection, id: i32) -> QueryResult<Option<SettlementInvoice>> {
+    settlement_invoices::table
+        .filter(settlement_invoices::id.eq(id))
+        .get_result(conn)
+        .optional()
+}
+
+/// The unique index allowing a single pending invoice from a payer to a payee.
+pub const ONE_PENDING: &str = "settlement_invoices_one_pending";
+
+/// There is at most one, which the [`ONE_PENDING`] index enforces.
+pub fn find_pending(
+    conn: &mut PgConnection,
+    payer_user_id: i32,
+    payee_user_id: i32,
+) -> QueryResult<Option<SettlementInvoice>> {
+    settlement_invoices::table
+        .filter(settlement_invoices::payer_user_id.eq(payer_user_id))
+        .filter(settlement_invoices::payee_user_id.eq(payee_user_id))
+        .filter(settlement_invoices::status.eq(SettlementInvoiceStatus::Pending))
+        .get_result(conn)
+        .optional()
+}
+
+pub fn list_pending(conn: &mut PgConnection) -> QueryResult<Vec<SettlementInvoice>> {
+    settlement_invoices::table
+        .filter(settlement_invoices::status.eq(SettlementInvoiceStatus::Pending))
+        .order(settlement_invoices::id)
+        .load(conn)
+}
+
+/// Only pending invoices transition, so concurrent watchers cannot record the same payment
+/// twice.
+pub fn mark_paid(
+    conn: &mut PgConnection,
+    id: i32,
+    user_payment_id: UserPaymentId,
+    paid_at: OffsetDateTime,
+) -> QueryResult<Option<SettlementInvoice>> {
+    diesel::update(settlement_invoices::table)
+        .filter(settlement_invoices::id.eq(id))
+        .filter(settlement_invoices::status.eq(SettlementInvoiceStatus::Pending))
+        .set((
+            settlement_invoices::status.eq(SettlementInvoiceStatus::Paid),
+            settlement_invoices::user_payment_id.eq(*user_payment_id),
+            settlement_invoices::paid_at.eq(paid_at),
+        ))
+        .get_result(conn)
+        .optional()
+}
+
+pub fn mark_expired(conn: &mut PgConnection, id: i32) -> QueryResult<Option<SettlementInvoice>> {
+    diesel::update(settlement_invoices::table)
+        .filter(settlement_invoices::id.eq(id))
+        .filter(settlement_invoices::status.eq(SettlementInvoiceStatus::Pending))
+        .set(settlement_invoices::status.eq(SettlementInvoiceStatus::Expired))
+        .get_result(conn)
+        .optional()
+}
+
+#[cfg(test)]
+mod test {
+    use diesel::Connection;
+
+    use super::*;
+    use crate::{
+        queries::{user_payments, users},
+        test,
+    };
+
+    fn setup(conn: &mut PgConnection, amount_msats: i64) -> QueryResult<SettlementInvoice> {
+        let u0 = *users::create(conn, OffsetDateTime::now_utc()).unwrap();
+        let u1 = *users::create(conn, OffsetDateTime::now_utc()).unwrap();
+
+        super::create(
+            conn,
+            &super::CreateParams {
+                payer_user_id: u0,
+                payee_user_id: u1,
+                amount_cents: 100,
+                amount_msats,
+                payment_hash: "hash",
+                payment_request: "lnbc1",
+                expires_at: OffsetDateTime::now_utc(),
+                created_at: OffsetDateTime::now_utc(),
+            },
+        )
+    }
+
+    #[test]
+    fn amount_msats_should_be_greater_than_zero() {
+        let res = setup(&mut test::conn(), 0);
+        assert!(matches!(
+            res.err(),
+            Some(diesel::result::Error::DatabaseError(_, _))
+        ));
+
+        let res = setup(&mut test::conn(), 1);
+        assert!(res.is_ok());
+    }
+
+    #[test]
+    fn only_one_invoice_per_pair_is_pending() {
+        let mut conn = test::conn();
+        let invoice = setup(&mut conn, 1).unwrap();
+
+        let mut params = super::CreateParams {
+            payer_user_id: invoice.payer_user_id,
+            payee_user_id: invoice.payee_user_id,
+            amount_cents: 100,
+            amount_msats: 1,
+            payment_hash: "other",
+            payment_request: "lnbc2",
+            expires_at: OffsetDateTime::now_utc(),
+            created_at: OffsetDateTime::now_utc(),
+        };
+        // In a savepoint, since the failed insert aborts it.
+        let res = conn.transaction(|conn| super::create(conn, &params));
+        assert!(matches!(
+            res,
+            Err(diesel::result::Error::DatabaseError(_, info))
+                if info.constraint_name() == Some(ONE_PENDING)
+        ));
+
+        mark_expired(&mut conn, *invoice.id).unwrap();
+        params.payment_hash = "another";
+        let pending = super::create(&mut conn, &params).unwrap();
+        assert_eq!(
+            *find_pending(&mut conn, invoice.payer_user_id, invoice.payee_user_id)
+                .unwrap()
+                .unwrap()
+                .id,
+            *pending.id
+        );
+    }
+
+    #[test]
+    fn mark_paid_only_once() {
+        let mut conn = test::conn();
+        let invoice = setup(&mut conn, 1).unwrap();
+
+        let user_payment_id = user_payments::create(
+            &mut conn,
+            &user_payments::CreateParams {
+                created_by: invoice.payer_user_id,
+                amount_cents: invoice.amount_cents,
+                payee_user_id: invoice.payee_user_id,
+                payer_user_id: invoice.payer_user_id,
+                payed_at: OffsetDateTime::now_utc(),
+                status: crate::enums::UserPaymentStatus::Confirmed,
+                method: crate::enums::UserPaymentMethod::Lightning,
+                proof: None,
+                created_at: OffsetDateTime::now_utc(),
+            },
+        )
+        .unwrap();
+
+        let paid = mark_paid(
+            &mut conn,
+            *invoice.id,
+            user_payment_id,
+            OffsetDateTime::now_utc(),
+        )
+        .unwrap();
+        assert_eq!(paid.unwrap().status, SettlementInvoiceStatus::Paid);
+
+        let again = mark_paid(
+            &mut conn,
+            *invoice.id,
+            user_payment_id,
+            OffsetDateTime::now_utc(),
+        )
+        .unwrap();
+        assert!(again.is_none());
+        assert!(list_pending(&mut conn).unwrap().is_empty());
+    }
+}
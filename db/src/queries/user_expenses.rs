@@ -1,9 +1,25 @@
-use diesel::{ExpressionMethods, PgConnection, QueryResult, RunQueryDsl};
+use diesel::{
+    ExpressionMethods, OptionalExtension, PgConnection, QueryDsl, QueryResult, Queryable,
+    RunQueryDsl,
+};
 use schema::{enums::UserExpensesChargeMethod, schema::user_expenses};
 use time::OffsetDateTime;
 
 use crate::types::UserExpenseId;
 
+#[derive(Debug, Queryable)]
+pub struct UserExpense {
+    pub id: UserExpenseId,
+    pub created_by: i32,
+    pub amount_cents: i64,
+    pub description: Option<String>,
+    pub chargee_user_id: i32,
+    pub charged_user_id: i32,
+    pub charge_method: UserExpensesChargeMethod,
+    pub begin_charging_at: OffsetDateTime,
+    pub created_at: OffsetDateTime,
+}
+
 pub struct CreateParams<'a> {
     pub created_by: i32,
     pub amount_cents: i64,
@@ -31,12 +47,33 @@ pub fn create(conn: &mut PgConnection, p: &CreateParams) -> QueryResult<UserExpe
         .get_result(conn)
 }
 
-pub fn delete(conn: &mut PgConnection, id: i32, user_id: i32) -> QueryResult<UserExpenseId> {
+pub fn find_by_id(conn: &mut PgConnection, id: i32) -> QueryResult<Option<UserExpense>> {
+    user_expenses::table
+        .filter(user_expenses::id.eq(id))
+        .get_result(conn)
+        .optional()
+}
+
+pub fn update_description(
+    conn: &mut PgConnection,
+    id: i32,
+    description: Option<&str>,
+) -> QueryResult<Option<UserExpense>> {
+    diesel::update(user_expenses::table)
+        .filter(user_expenses::id.eq(id))
+        .set(user_expenses::description.eq(description))
+        .get_result(conn)
+        .optional()
+}
+
+/// Deletes an entry regardless of who created it, for operators and for callers the access
+/// policy already allowed to.
+pub fn force_delete(conn: &mut PgConnection, id: i32) -> QueryResult<Option<UserExpenseId>> {
     diesel::delete(user_expenses::table)
         .filter(user_expenses::id.eq(id))
-        .filter(user_expenses::created_by.eq(user_id))
         .returning(user_expenses::id)
         .get_result(conn)
+        .optional()
 }
 
 #[cfg(test)]
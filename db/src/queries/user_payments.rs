@@ -1,16 +1,40 @@
-use diesel::{ExpressionMethods, PgConnection, QueryResult, RunQueryDsl};
-use schema::schema::user_payments;
+use diesel::{
+    ExpressionMethods, OptionalExtension, PgConnection, QueryDsl, QueryResult, Queryable,
+    RunQueryDsl,
+};
+use schema::{
+    enums::{UserPaymentMethod, UserPaymentStatus},
+    schema::user_payments,
+};
 use time::OffsetDateTime;
 
 use crate::types::UserPaymentId;
 
-pub struct CreateParams {
+#[derive(Debug, Queryable)]
+pub struct UserPayment {
+    pub id: UserPaymentId,
     pub created_by: i32,
     pub amount_cents: i64,
     pub payee_user_id: i32,
     pub payer_user_id: i32,
     pub payed_at: OffsetDateTime,
     pub created_at: OffsetDateTime,
+    pub status: UserPaymentStatus,
+    pub method: UserPaymentMethod,
+    pub proof: Option<String>,
+    pub reviewed_at: Option<OffsetDateTime>,
+}
+
+pub struct CreateParams<'a> {
+    pub created_by: i32,
+    pub amount_cents: i64,
+    pub payee_user_id: i32,
+    pub payer_user_id: i32,
+    pub payed_at: OffsetDateTime,
+    pub status: UserPaymentStatus,
+    pub method: UserPaymentMethod,
+    pub proof: Option<&'a str>,
+    pub created_at: OffsetDateTime,
 }
 
 pub fn create(conn: &mut PgConnection, p: &CreateParams) -> QueryResult<UserPaymentId> {
@@ -21,12 +45,22 @@ pub fn create(conn: &mut PgConnection, p: &CreateParams) -> QueryResult<UserPaym
             user_payments::payee_user_id.eq(p.payee_user_id),
             user_payments::payer_user_id.eq(p.payer_user_id),
             user_payments::payed_at.eq(p.payed_at),
+            user_payments::status.eq(p.status),
+            user_payments::method.eq(p.method),
+            user_payments::proof.eq(p.proof),
             user_payments::created_at.eq(p.created_at),
         ))
         .returning(user_payments::id)
         .get_result(conn)
 }
 
+pub fn find_by_id(conn: &mut PgConnection, id: i32) -> QueryResult<Option<UserPayment>> {
+    user_payments::table
+        .filter(user_payments::id.eq(id))
+        .get_result(conn)
+        .optional()
+}
+
 pub fn delete(conn: &mut PgConnection, id: i32, user_id: i32) -> QueryResult<UserPaymentId> {
     diesel::delete(user_payments::table)
         .filter(user_payments::id.eq(id))
@@ -35,6 +69,36 @@ pub fn delete(conn: &mut PgConnection, id: i32, user_id: i32) -> QueryResult<Use
         .get_result(conn)
 }
 
+/// Deletes an entry regardless of who created it, for operators.
+pub fn force_delete(conn: &mut PgConnection, id: i32) -> QueryResult<Option<UserPaymentId>> {
+    diesel::delete(user_payments::table)
+        .filter(user_payments::id.eq(id))
+        .returning(user_payments::id)
+        .get_result(conn)
+        .optional()
+}
+
+/// Moves a pending payment to `status` on behalf of its payee.
+pub fn review(
+    conn: &mut PgConnection,
+    id: i32,
+    payee_user_id: i32,
+    status: UserPaymentStatus,
+    reviewed_at: OffsetDateTime,
+) -> QueryResult<Option<UserPaymentId>> {
+    diesel::update(user_payments::table)
+        .filter(user_payments::id.eq(id))
+        .filter(user_payments::payee_user_id.eq(payee_user_id))
+        .filter(user_payments::status.eq(UserPaymentStatus::Pending))
+        .set((
+            user_payments::status.eq(status),
+            user_payments::reviewed_at.eq(reviewed_at),
+        ))
+        .returning(user_payments::id)
+        .get_result(conn)
+        .optional()
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -58,6 +122,9 @@ mod test {
                     payee_user_id: u0,
                     payer_user_id: u1,
                     payed_at: OffsetDateTime::now_utc(),
+                    status: UserPaymentStatus::Pending,
+                    method: UserPaymentMethod::Cash,
+                    proof: None,
                     created_at: OffsetDateTime::now_utc(),
                 },
             )
@@ -94,6 +161,9 @@ mod test {
                     payee_user_id: u0,
                     payer_user_id: u0,
                     payed_at: OffsetDateTime::now_utc(),
+                    status: UserPaymentStatus::Pending,
+                    method: UserPaymentMethod::Cash,
+                    proof: None,
                     created_at: OffsetDateTime::now_utc(),
                 },
             );
@@ -118,6 +188,9 @@ mod test {
                     payee_user_id: u0,
                     payer_user_id: u1,
                     payed_at: OffsetDateTime::now_utc(),
+                    status: UserPaymentStatus::Pending,
+                    method: UserPaymentMethod::Cash,
+                    proof: None,
                     created_at: OffsetDateTime::now_utc(),
                 },
             );
@@ -125,4 +198,43 @@ mod test {
             assert!(res.is_ok());
         }
     }
+
+    mod review {
+        use super::*;
+        use crate::queries::users;
+
+        #[test]
+        fn only_payee_reviews_pending_payments() {
+            let mut conn = test::conn();
+            let u0 = *users::create(&mut conn, OffsetDateTime::now_utc()).unwrap();
+            let u1 = *users::create(&mut conn, OffsetDateTime::now_utc()).unwrap();
+
+            let id = *super::create(
+                &mut conn,
+                &super::CreateParams {
+                    created_by: u1,
+                    amount_cents: 1,
+                    payee_user_id: u0,
+                    payer_user_id: u1,
+                    payed_at: OffsetDateTime::now_utc(),
+                    status: UserPaymentStatus::Pending,
+                    method: UserPaymentMethod::Pix,
+                    proof: Some("E00000000202304210000000000000"),
+                    created_at: OffsetDateTime::now_utc(),
+                },
+            )
+            .unwrap();
+
+            let now = OffsetDateTime::now_utc();
+
+            let res = review(&mut conn, id, u1, UserPaymentStatus::Confirmed, now).unwrap();
+            assert!(res.is_none());
+
+            let res = review(&mut conn, id, u0, UserPaymentStatus::Confirmed, now).unwrap();
+            assert!(res.is_some());
+
+            let res = review(&mut conn, id, u0, UserPaymentStatus::Rejected, now).unwrap();
+            assert!(res.is_none());
+        }
+    }
 }
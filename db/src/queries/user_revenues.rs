@@ -1,9 +1,22 @@
-use diesel::{ExpressionMethods, PgConnection, QueryResult, RunQueryDsl};
+use diesel::{
+    ExpressionMethods, OptionalExtension, PgConnection, QueryDsl, QueryResult, Queryable,
+    RunQueryDsl,
+};
 use schema::schema::user_revenues;
 use time::OffsetDateTime;
 
 use crate::types::UserRevenueId;
 
+#[derive(Debug, Queryable)]
+pub struct UserRevenue {
+    pub id: UserRevenueId,
+    pub user_id: i32,
+    pub amount_cents: i64,
+    pub incoming_at: OffsetDateTime,
+    pub description: Option<String>,
+    pub created_at: OffsetDateTime,
+}
+
 pub struct CreateParams<'a> {
     pub user_id: i32,
     pub amount_cents: i64,
@@ -25,6 +38,13 @@ pub fn create(conn: &mut PgConnection, p: &CreateParams) -> QueryResult<UserReve
         .get_result(conn)
 }
 
+pub fn find_by_id(conn: &mut PgConnection, id: i32) -> QueryResult<Option<UserRevenue>> {
+    user_revenues::table
+        .filter(user_revenues::id.eq(id))
+        .get_result(conn)
+        .optional()
+}
+
 pub fn delete(conn: &mut PgConnection, id: i32, user_id: i32) -> QueryResult<UserRevenueId> {
     diesel::delete(user_revenues::table)
         .filter(user_revenues::id.eq(id))
@@ -33,6 +53,15 @@ pub fn delete(conn: &mut PgConnection, id: i32, user_id: i32) -> QueryResult<Use
         .get_result(conn)
 }
 
+/// Deletes an entry regardless of who created it, for operators.
+pub fn force_delete(conn: &mut PgConnection, id: i32) -> QueryResult<Option<UserRevenueId>> {
+    diesel::delete(user_revenues::table)
+        .filter(user_revenues::id.eq(id))
+        .returning(user_revenues::id)
+        .get_result(conn)
+        .optional()
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -1,5 +1,6 @@
 use diesel::{
-    ExpressionMethods, OptionalExtension, PgConnection, QueryDsl, QueryResult, RunQueryDsl,
+    AsChangeset, ExpressionMethods, OptionalExtension, PgConnection, PgTextExpressionMethods,
+    QueryDsl, QueryResult, Queryable, RunQueryDsl,
 };
 use schema::schema::users;
 use time::OffsetDateTime;
@@ -20,3 +21,423 @@ pub fn find_by_id(conn: &mut PgConnection, id: i32) -> QueryResult<Option<UserId
         .get_result(conn)
         .optional()
 }
+
+#[derive(Debug)]
+pub struct PixAccount {
+    pub key: String,
+    pub merchant_name: String,
+    pub merchant_city: String,
+}
+
+pub fn set_pix_account(
+    conn: &mut PgConnection,
+    id: i32,
+    account: &PixAccount,
+) -> QueryResult<Option<UserId>> {
+    diesel::update(users::table)
+        .filter(users::id.eq(id))
+        .set((
+            users::pix_key.eq(&account.key),
+            users::pix_merchant_name.eq(&account.merchant_name),
+            users::pix_merchant_city.eq(&account.merchant_city),
+        ))
+        .returning(users::id)
+        .get_result(conn)
+        .optional()
+}
+
+pub fn find_pix_account(conn: &mut PgConnection, id: i32) -> QueryResult<Option<PixAccount>> {
+    let account: Option<(Option<String>, Option<String>, Option<String>)> = users::table
+        .filter(users::id.eq(id))
+        .select((
+            users::pix_key,
+            users::pix_merchant_name,
+            users::pix_merchant_city,
+        ))
+        .get_result(conn)
+        .optional()?;
+
+    Ok(match account {
+        Some((Some(key), Some(merchant_name), Some(merchant_city))) => Some(PixAccount {
+            key,
+            merchant_name,
+            merchant_city,
+        }),
+        _ => None,
+    })
+}
+
+pub fn set_bitcoin_address(
+    conn: &mut PgConnection,
+    id: i32,
+    address: &str,
+) -> QueryResult<Option<UserId>> {
+    diesel::update(users::table)
+        .filter(users::id.eq(id))
+        .set(users::bitcoin_address.eq(address))
+        .returning(users::id)
+        .get_result(conn)
+        .optional()
+}
+
+pub fn find_bitcoin_address(conn: &mut PgConnection, id: i32) -> QueryResult<Option<String>> {
+    users::table
+        .filter(users::id.eq(id))
+        .select(users::bitcoin_address)
+        .get_result::<Option<String>>(conn)
+        .optional()
+        .map(Option::flatten)
+}
+
+#[derive(Debug, Queryable)]
+pub struct Profile {
+    pub id: UserId,
+    pub display_name: Option<String>,
+    pub email: Option<String>,
+    pub locale: String,
+    pub timezone: String,
+    pub avatar_url: Option<String>,
+    pub created_at: OffsetDateTime,
+}
+
+type ProfileColumns = (
+    users::id,
+    users::display_name,
+    users::email,
+    users::locale,
+    users::timezone,
+    users::avatar_url,
+    users::created_at,
+);
+
+const PROFILE_COLUMNS: ProfileColumns = (
+    users::id,
+    users::display_name,
+    users::email,
+    users::locale,
+    users::timezone,
+    users::avatar_url,
+    users::created_at,
+);
+
+/// Fields left as `None` keep their current value.
+#[derive(Default, AsChangeset)]
+#[diesel(table_name = users)]
+pub struct UpdateProfileParams<'a> {
+    pub display_name: Option<&'a str>,
+    pub email: Option<&'a str>,
+    pub locale: Option<&'a str>,
+    pub timezone: Option<&'a str>,
+    pub avatar_url: Option<&'a str>,
+}
+
+pub fn find_profile(conn: &mut PgConnection, id: i32) -> QueryResult<Option<Profile>> {
+    users::table
+        .filter(users::id.eq(id))
+        .select(PROFILE_COLUMNS)
+        .get_result(conn)
+        .optional()
+}
+
+pub fn update_profile(
+    conn: &mut PgConnection,
+    id: i32,
+    p: &UpdateProfileParams,
+) -> QueryResult<Option<Profile>> {
+    // Diesel refuses empty changesets.
+    if p.display_name.is_none()
+        && p.email.is_none()
+        && p.locale.is_none()
+        && p.timezone.is_none()
+        && p.avatar_url.is_none()
+    {
+        return find_profile(conn, id);
+    }
+
+    diesel::update(users::table)
+        .filter(users::id.eq(id))
+        .set(p)
+        .returning(PROFILE_COLUMNS)
+        .get_result(conn)
+        .optional()
+}
+
+/// Matches an exact, already lowercased e-mail, or display names starting with `query`.
+pub fn search(conn: &mut PgConnection, query: &str, limit: i64) -> QueryResult<Vec<Profile>> {
+    let base = users::table
+        .select(PROFILE_COLUMNS)
+        .order(users::id)
+        .limit(limit);
+
+    if query.contains('@') {
+        base.filter(users::email.eq(query)).load(conn)
+    } else {
+        let escaped = query
+            .replace('\\', "\\\\")
+            .replace('%', "\\%")
+            .replace('_', "\\_");
+
+        base.filter(users::display_name.ilike(format!("{escaped}%")))
+            .load(conn)
+    }
+}
+
+pub fn find_timezone(conn: &mut PgConnection, id: i32) -> QueryResult<Option<String>> {
+    users::table
+        .filter(users::id.eq(id))
+        .select(users::timezone)
+        .get_result(conn)
+        .optional()
+}
+
+pub fn set_password_hash(
+    conn: &mut PgConnection,
+    id: i32,
+    password_hash: &str,
+) -> QueryResult<Option<UserId>> {
+    diesel::update(users::table)
+        .filter(users::id.eq(id))
+        .set(users::password_hash.eq(password_hash))
+        .returning(users::id)
+        .get_result(conn)
+        .optional()
+}
+
+/// Returns the active user registered with an already lowercased `email` and their password
+/// hash.
+pub fn find_credentials(
+    conn: &mut PgConnection,
+    email: &str,
+) -> QueryResult<Option<(UserId, Option<String>)>> {
+    users::table
+        .filter(users::email.eq(email))
+        .filter(users::deactivated_at.is_null())
+        .select((users::id, users::password_hash))
+        .get_result(conn)
+        .optional()
+}
+
+#[derive(Debug, Queryable)]
+pub struct Account {
+    pub id: UserId,
+    pub display_name: Option<String>,
+    pub email: Option<String>,
+    pub created_at: OffsetDateTime,
+    pub deactivated_at: Option<OffsetDateTime>,
+    pub admin: bool,
+}
+
+/// Lists users by id, starting after `after_id`.
+pub fn list(
+    conn: &mut PgConnection,
+    after_id: Option<i32>,
+    limit: i64,
+) -> QueryResult<Vec<Account>> {
+    users::table
+        .filter(users::id.gt(after_id.unwrap_or(0)))
+        .select((
+            users::id,
+            users::display_name,
+            users::email,
+            users::created_at,
+            users::deactivated_at,
+            users::admin,
+        ))
+        .order(users::id)
+        .limit(limit)
+        .load(conn)
+}
+
+pub fn deactivate(
+    conn: &mut PgConnection,
+    id: i32,
+    deactivated_at: OffsetDateTime,
+) -> QueryResult<Option<UserId>> {
+    diesel::update(users::table)
+        .filter(users::id.eq(id))
+        .filter(users::deactivated_at.is_null())
+        .set(users::deactivated_at.eq(deactivated_at))
+        .returning(users::id)
+        .get_result(conn)
+        .optional()
+}
+
+pub fn set_admin(conn: &mut PgConnection, id: i32, admin: bool) -> QueryResult<Option<UserId>> {
+    diesel::update(users::table)
+        .filter(users::id.eq(id))
+        .set(users::admin.eq(admin))
+        .returning(users::id)
+        .get_result(conn)
+        .optional()
+}
+
+/// Whether the user may edit and delete entries created by others. Unknown users are not.
+pub fn is_admin(conn: &mut PgConnection, id: i32) -> QueryResult<bool> {
+    users::table
+        .filter(users::id.eq(id))
+        .select(users::admin)
+        .get_result(conn)
+        .optional()
+        .map(|admin| admin.unwrap_or(false))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test;
+
+    #[test]
+    fn pix_account_round_trip() {
+        let mut conn = test::conn();
+        let id = *create(&mut conn, OffsetDateTime::now_utc()).unwrap();
+
+        assert!(find_pix_account(&mut conn, id).unwrap().is_none());
+
+        let account = PixAccount {
+            key: "fulano@example.com".to_owned(),
+            merchant_name: "Fulano de Tal".to_owned(),
+            merchant_city: "BRASILIA".to_owned(),
+        };
+        assert!(set_pix_account(&mut conn, id, &account).unwrap().is_some());
+
+        let found = find_pix_account(&mut conn, id).unwrap().unwrap();
+        assert_eq!(found.key, account.key);
+        assert_eq!(found.merchant_city, account.merchant_city);
+    }
+
+    #[test]
+    fn bitcoin_address_round_trip() {
+        let mut conn = test::conn();
+        let id = *create(&mut conn, OffsetDateTime::now_utc()).unwrap();
+
+        assert!(find_bitcoin_address(&mut conn, id).unwrap().is_none());
+        assert!(set_bitcoin_address(&mut conn, id, "bc1q")
+            .unwrap()
+            .is_some());
+        assert_eq!(
+            find_bitcoin_address(&mut conn, id).unwrap().as_deref(),
+            Some("bc1q")
+        );
+    }
+
+    #[test]
+    fn profile_update_keeps_unset_fields() {
+        let mut conn = test::conn();
+        let id = *create(&mut conn, OffsetDateTime::now_utc()).unwrap();
+
+        let profile = find_profile(&mut conn, id).unwrap().unwrap();
+        assert_eq!(profile.timezone, "UTC");
+        assert_eq!(profile.locale, "en-US");
+
+        let params = UpdateProfileParams {
+            display_name: Some("Fulano 100%"),
+            timezone: Some("America/Sao_Paulo"),
+            ..Default::default()
+        };
+        update_profile(&mut conn, id, &params).unwrap().unwrap();
+
+        let params = UpdateProfileParams {
+            email: Some("fulano@example.com"),
+            ..Default::default()
+        };
+        let profile = update_profile(&mut conn, id, &params).unwrap().unwrap();
+        assert_eq!(profile.display_name.as_deref(), Some("Fulano 100%"));
+        assert_eq!(profile.timezone, "America/Sao_Paulo");
+
+        let unchanged = update_profile(&mut conn, id, &UpdateProfileParams::default()).unwrap();
+        assert_eq!(
+            unchanged.unwrap().email.as_deref(),
+            Some("fulano@example.com")
+        );
+        assert_eq!(
+            find_timezone(&mut conn, id).unwrap().as_deref(),
+            Some("America/Sao_Paulo")
+        );
+    }
+
+    #[test]
+    fn search_by_name_prefix_or_email() {
+        let mut conn = test::conn();
+        let id = *create(&mut conn, OffsetDateTime::now_utc()).unwrap();
+        let params = UpdateProfileParams {
+            display_name: Some("Fulano 100%"),
+            email: Some("fulano@example.com"),
+            ..Default::default()
+        };
+        update_profile(&mut conn, id, &params).unwrap();
+
+        let ids = |profiles: Vec<Profile>| profiles.iter().map(|p| *p.id).collect::<Vec<_>>();
+        assert_eq!(ids(search(&mut conn, "fulANO", 10).unwrap()), [id]);
+        assert_eq!(ids(search(&mut conn, "Fulano 100%", 10).unwrap()), [id]);
+        assert!(search(&mut conn, "Fulano 1000", 10).unwrap().is_empty());
+        assert!(search(&mut conn, "_ulano", 10).unwrap().is_empty());
+        assert_eq!(
+            ids(search(&mut conn, "fulano@example.com", 10).unwrap()),
+            [id]
+        );
+        assert!(search(&mut conn, "fulano@example", 10).unwrap().is_empty());
+    }
+
+    #[test]
+    fn email_is_unique() {
+        let mut conn = test::conn();
+        let u0 = *create(&mut conn, OffsetDateTime::now_utc()).unwrap();
+        let u1 = *create(&mut conn, OffsetDateTime::now_utc()).unwrap();
+        let params = UpdateProfileParams {
+            email: Some("fulano@example.com"),
+            ..Default::default()
+        };
+
+        assert!(update_profile(&mut conn, u0, &params).is_ok());
+        assert!(matches!(
+            update_profile(&mut conn, u1, &params),
+            Err(diesel::result::Error::DatabaseError(
+                diesel::result::DatabaseErrorKind::UniqueViolation,
+                _
+            ))
+        ));
+    }
+
+    #[test]
+    fn deactivated_users_cannot_log_in() {
+        let mut conn = test::conn();
+        let id = *create(&mut conn, OffsetDateTime::now_utc()).unwrap();
+        update_profile(
+            &mut conn,
+            id,
+            &UpdateProfileParams {
+                email: Some("fulano@example.com"),
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        assert!(find_credentials(&mut conn, "fulano@example.com")
+            .unwrap()
+            .is_some());
+
+        let now = OffsetDateTime::now_utc();
+        assert!(deactivate(&mut conn, id, now).unwrap().is_some());
+        assert!(deactivate(&mut conn, id, now).unwrap().is_none());
+
+        assert!(find_credentials(&mut conn, "fulano@example.com")
+            .unwrap()
+            .is_none());
+        let account = list(&mut conn, Some(id - 1), 1).unwrap().remove(0);
+        assert_eq!(*account.id, id);
+        assert!(account.deactivated_at.is_some());
+    }
+
+    #[test]
+    fn admin_round_trip() {
+        let mut conn = test::conn();
+        let id = *create(&mut conn, OffsetDateTime::now_utc()).unwrap();
+        assert!(!is_admin(&mut conn, id).unwrap());
+
+        assert!(set_admin(&mut conn, id, true).unwrap().is_some());
+        assert!(is_admin(&mut conn, id).unwrap());
+        assert!(list(&mut conn, Some(id - 1), 1).unwrap()[0].admin);
+
+        assert!(set_admin(&mut conn, id + 1, true).unwrap().is_none());
+        assert!(!is_admin(&mut conn, id + 1).unwrap());
+    }
+}
@@ -12,6 +12,12 @@ macro_rules! diesel_ids {
                 }
             }
 
+            impl From<i32> for $id {
+                fn from(id: i32) -> Self {
+                    Self(id)
+                }
+            }
+
             impl diesel::deserialize::FromSql<diesel::sql_types::Integer, diesel::pg::Pg> for $id {
                 fn from_sql(bytes: diesel::backend::RawValue<diesel::pg::Pg>) -> diesel::deserialize::Result<Self> {
                     <i32 as diesel::deserialize::FromSql<diesel::sql_types::Integer, diesel::pg::Pg>>::from_sql(bytes).map(Self)
@@ -26,5 +32,8 @@ diesel_ids!(
     UserRevenueId,
     UserPaymentId,
     UserExpenseId,
-    UserExpenseInstallmentId
+    UserExpenseInstallmentId,
+    SettlementInvoiceId,
+    SessionId,
+    AuditEventId
 );
@@ -0,0 +1,2 @@
+DROP TABLE settlement_invoices;
+DROP TYPE settlement_invoice_status;
@@ -0,0 +1,33 @@
+CREATE TYPE settlement_invoice_status as ENUM (
+    'pending',
+    'paid',
+    'expired'
+);
+
+CREATE TABLE settlement_invoices (
+    id SERIAL PRIMARY KEY,
+
+    payer_user_id INT NOT NULL REFERENCES users(id),
+    payee_user_id INT NOT NULL REFERENCES users(id),
+
+    amount_cents BIGINT NOT NULL,
+    amount_msats BIGINT NOT NULL,
+
+    payment_hash TEXT NOT NULL UNIQUE,
+    payment_request TEXT NOT NULL,
+    status settlement_invoice_status NOT NULL,
+    user_payment_id INT REFERENCES user_payments(id),
+
+    expires_at TIMESTAMPTZ NOT NULL,
+    paid_at TIMESTAMPTZ,
+    created_at TIMESTAMPTZ NOT NULL,
+
+    CONSTRAINT settlement_invoice_amount_cents_is_greater_than_zero CHECK (amount_cents > 0),
+    CONSTRAINT settlement_invoice_amount_msats_is_greater_than_zero CHECK (amount_msats > 0),
+    CONSTRAINT settlement_invoice_payee_is_not_payer CHECK (payee_user_id <> payer_user_id)
+);
+
+-- Paying two invoices for the same balance would settle it twice.
+CREATE UNIQUE INDEX settlement_invoices_one_pending
+    ON settlement_invoices (payer_user_id, payee_user_id)
+    WHERE status = 'pending';
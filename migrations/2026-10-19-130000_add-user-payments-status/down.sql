@@ -0,0 +1,8 @@
+ALTER TABLE user_payments
+    DROP COLUMN status,
+    DROP COLUMN method,
+    DROP COLUMN proof,
+    DROP COLUMN reviewed_at;
+
+DROP TYPE user_payment_method;
+DROP TYPE user_payment_status;
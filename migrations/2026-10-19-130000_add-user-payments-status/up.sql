@@ -0,0 +1,23 @@
+CREATE TYPE user_payment_status as ENUM (
+    'pending',
+    'confirmed',
+    'rejected'
+);
+
+CREATE TYPE user_payment_method as ENUM (
+    'cash',
+    'bank_transfer',
+    'pix',
+    'lightning'
+);
+
+-- Payments recorded before statuses existed were already counted in balances.
+ALTER TABLE user_payments
+    ADD COLUMN status user_payment_status NOT NULL DEFAULT 'confirmed',
+    ADD COLUMN method user_payment_method NOT NULL DEFAULT 'cash',
+    ADD COLUMN proof TEXT,
+    ADD COLUMN reviewed_at TIMESTAMPTZ;
+
+ALTER TABLE user_payments
+    ALTER COLUMN status DROP DEFAULT,
+    ALTER COLUMN method DROP DEFAULT;
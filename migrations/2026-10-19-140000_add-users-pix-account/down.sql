@@ -0,0 +1,5 @@
+ALTER TABLE users
+    DROP CONSTRAINT users_pix_account_is_complete,
+    DROP COLUMN pix_key,
+    DROP COLUMN pix_merchant_name,
+    DROP COLUMN pix_merchant_city;
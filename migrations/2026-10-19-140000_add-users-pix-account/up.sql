@@ -0,0 +1,8 @@
+ALTER TABLE users
+    ADD COLUMN pix_key TEXT,
+    ADD COLUMN pix_merchant_name TEXT,
+    ADD COLUMN pix_merchant_city TEXT,
+    ADD CONSTRAINT users_pix_account_is_complete CHECK (
+        (pix_key IS NULL) = (pix_merchant_name IS NULL)
+        AND (pix_key IS NULL) = (pix_merchant_city IS NULL)
+    );
@@ -0,0 +1,9 @@
+DROP INDEX users_display_name_idx;
+DROP INDEX users_email_key;
+
+ALTER TABLE users
+    DROP COLUMN display_name,
+    DROP COLUMN email,
+    DROP COLUMN locale,
+    DROP COLUMN timezone,
+    DROP COLUMN avatar_url;
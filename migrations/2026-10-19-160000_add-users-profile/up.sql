@@ -0,0 +1,9 @@
+ALTER TABLE users
+    ADD COLUMN display_name TEXT,
+    ADD COLUMN email TEXT,
+    ADD COLUMN locale TEXT NOT NULL DEFAULT 'en-US',
+    ADD COLUMN timezone TEXT NOT NULL DEFAULT 'UTC',
+    ADD COLUMN avatar_url TEXT;
+
+CREATE UNIQUE INDEX users_email_key ON users (email);
+CREATE INDEX users_display_name_idx ON users (lower(display_name) text_pattern_ops);
@@ -0,0 +1,39 @@
+CREATE TYPE audit_entity_type as ENUM (
+    'user_revenue',
+    'user_payment',
+    'user_expense'
+);
+
+CREATE TABLE audit_events (
+    id SERIAL PRIMARY KEY,
+
+    -- Operators act without a user account, so their events have no actor.
+    actor_user_id INT REFERENCES users(id),
+    rpc TEXT NOT NULL,
+    entity_type audit_entity_type NOT NULL,
+    entity_id INT NOT NULL,
+
+    -- Users who may read the event, kept so that it stays visible after the entity is deleted.
+    participant_user_ids INT[] NOT NULL,
+
+    before JSONB,
+    after JSONB,
+
+    created_at TIMESTAMPTZ NOT NULL,
+
+    CHECK (before IS NOT NULL OR after IS NOT NULL)
+);
+
+CREATE INDEX audit_events_entity_idx ON audit_events (entity_type, entity_id);
+CREATE INDEX audit_events_actor_user_id_idx ON audit_events (actor_user_id);
+CREATE INDEX audit_events_participant_user_ids_idx ON audit_events USING GIN (participant_user_ids);
+
+CREATE FUNCTION audit_events_append_only() RETURNS TRIGGER AS $$
+BEGIN
+    RAISE EXCEPTION 'audit_events is append-only';
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER audit_events_append_only
+    BEFORE UPDATE OR DELETE ON audit_events
+    FOR EACH ROW EXECUTE FUNCTION audit_events_append_only();
@@ -5,3 +5,36 @@ pub enum UserExpensesChargeMethod {
     Proportional,
     Full,
 }
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, diesel_derive_enum::DbEnum)]
+#[ExistingTypePath = "crate::schema::sql_types::SettlementInvoiceStatus"]
+pub enum SettlementInvoiceStatus {
+    Pending,
+    Paid,
+    Expired,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, diesel_derive_enum::DbEnum)]
+#[ExistingTypePath = "crate::schema::sql_types::UserPaymentStatus"]
+pub enum UserPaymentStatus {
+    Pending,
+    Confirmed,
+    Rejected,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, diesel_derive_enum::DbEnum)]
+#[ExistingTypePath = "crate::schema::sql_types::UserPaymentMethod"]
+pub enum UserPaymentMethod {
+    Cash,
+    BankTransfer,
+    Pix,
+    Lightning,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, diesel_derive_enum::DbEnum)]
+#[ExistingTypePath = "crate::schema::sql_types::AuditEntityType"]
+pub enum AuditEntityType {
+    UserRevenue,
+    UserPayment,
+    UserExpense,
+}
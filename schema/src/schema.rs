@@ -1,9 +1,78 @@
 // @generated automatically by Diesel CLI.
 
 pub mod sql_types {
+    #[derive(diesel::sql_types::SqlType)]
+    #[diesel(postgres_type(name = "audit_entity_type"))]
+    pub struct AuditEntityType;
+
+    #[derive(diesel::sql_types::SqlType)]
+    #[diesel(postgres_type(name = "settlement_invoice_status"))]
+    pub struct SettlementInvoiceStatus;
+
     #[derive(diesel::sql_types::SqlType)]
     #[diesel(postgres_type(name = "user_expenses_charge_method"))]
     pub struct UserExpensesChargeMethod;
+
+    #[derive(diesel::sql_types::SqlType)]
+    #[diesel(postgres_type(name = "user_payment_method"))]
+    pub struct UserPaymentMethod;
+
+    #[derive(diesel::sql_types::SqlType)]
+    #[diesel(postgres_type(name = "user_payment_status"))]
+    pub struct UserPaymentStatus;
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+    use super::sql_types::AuditEntityType;
+
+    audit_events (id) {
+        id -> Int4,
+        actor_user_id -> Nullable<Int4>,
+        rpc -> Text,
+        entity_type -> AuditEntityType,
+        entity_id -> Int4,
+        participant_user_ids -> Array<Int4>,
+        before -> Nullable<Jsonb>,
+        after -> Nullable<Jsonb>,
+        created_at -> Timestamptz,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+
+    sessions (id) {
+        id -> Int4,
+        user_id -> Int4,
+        refresh_token_hash -> Text,
+        previous_refresh_token_hash -> Nullable<Text>,
+        device -> Nullable<Text>,
+        created_at -> Timestamptz,
+        refreshed_at -> Timestamptz,
+        expires_at -> Timestamptz,
+        revoked_at -> Nullable<Timestamptz>,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+    use super::sql_types::SettlementInvoiceStatus;
+
+    settlement_invoices (id) {
+        id -> Int4,
+        payer_user_id -> Int4,
+        payee_user_id -> Int4,
+        amount_cents -> Int8,
+        amount_msats -> Int8,
+        payment_hash -> Text,
+        payment_request -> Text,
+        status -> SettlementInvoiceStatus,
+        user_payment_id -> Nullable<Int4>,
+        expires_at -> Timestamptz,
+        paid_at -> Nullable<Timestamptz>,
+        created_at -> Timestamptz,
+    }
 }
 
 diesel::table! {
@@ -33,6 +102,10 @@ diesel::table! {
 }
 
 diesel::table! {
+    use diesel::sql_types::*;
+    use super::sql_types::UserPaymentStatus;
+    use super::sql_types::UserPaymentMethod;
+
     user_payments (id) {
         id -> Int4,
         created_by -> Int4,
@@ -41,6 +114,10 @@ diesel::table! {
         payer_user_id -> Int4,
         payed_at -> Timestamptz,
         created_at -> Timestamptz,
+        status -> UserPaymentStatus,
+        method -> UserPaymentMethod,
+        proof -> Nullable<Text>,
+        reviewed_at -> Nullable<Timestamptz>,
     }
 }
 
@@ -60,13 +137,31 @@ diesel::table! {
         id -> Int4,
         lightning_address -> Nullable<Text>,
         created_at -> Timestamptz,
+        pix_key -> Nullable<Text>,
+        pix_merchant_name -> Nullable<Text>,
+        pix_merchant_city -> Nullable<Text>,
+        bitcoin_address -> Nullable<Text>,
+        display_name -> Nullable<Text>,
+        email -> Nullable<Text>,
+        locale -> Text,
+        timezone -> Text,
+        avatar_url -> Nullable<Text>,
+        password_hash -> Nullable<Text>,
+        deactivated_at -> Nullable<Timestamptz>,
+        admin -> Bool,
     }
 }
 
+diesel::joinable!(audit_events -> users (actor_user_id));
+diesel::joinable!(sessions -> users (user_id));
+diesel::joinable!(settlement_invoices -> user_payments (user_payment_id));
 diesel::joinable!(user_expense_installments -> user_expenses (user_expense_id));
 diesel::joinable!(user_revenues -> users (user_id));
 
 diesel::allow_tables_to_appear_in_same_query!(
+    audit_events,
+    sessions,
+    settlement_invoices,
     user_expense_installments,
     user_expenses,
     user_payments,
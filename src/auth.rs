@@ -0,0 +1,217 @@
+use std::sync::OnceLock;
+
+use argon2::{
+    password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, SaltString},
+    Argon2,
+};
+use db::types::{SessionId, UserId};
+use jsonwebtoken::{Algorithm, DecodingKey, EncodingKey, Header, Validation};
+use ring::{digest, rand::SecureRandom};
+use serde::{Deserialize, Serialize};
+use time::{Duration, OffsetDateTime};
+
+pub const ACCESS_TOKEN_TTL: Duration = Duration::minutes(15);
+pub const REFRESH_TOKEN_TTL: Duration = Duration::days(30);
+
+#[derive(Debug, thiserror::Error)]
+pub enum AuthError {
+    #[error("Invalid token: {0}")]
+    InvalidToken(jsonwebtoken::errors::Error),
+}
+
+#[derive(Serialize, Deserialize)]
+struct Claims {
+    sub: i32,
+    sid: i32,
+    iat: i64,
+    exp: i64,
+}
+
+/// The user and session an access token was issued for.
+#[derive(Debug, Clone, Copy)]
+pub struct Caller {
+    pub user_id: UserId,
+    pub session_id: SessionId,
+}
+
+/// Issues and verifies HS256 access tokens whose subject is the caller's user id.
+#[derive(Clone)]
+pub struct Tokens {
+    encoding: EncodingKey,
+    decoding: DecodingKey,
+}
+
+impl Tokens {
+    pub fn new(key: &str) -> Self {
+        Self {
+            encoding: EncodingKey::from_secret(key.as_bytes()),
+            decoding: DecodingKey::from_secret(key.as_bytes()),
+        }
+    }
+
+    pub fn issue(&self, caller: Caller, now: OffsetDateTime) -> String {
+        let claims = Claims {
+            sub: *caller.user_id,
+            sid: *caller.session_id,
+            iat: now.unix_timestamp(),
+            exp: (now + ACCESS_TOKEN_TTL).unix_timestamp(),
+        };
+
+        jsonwebtoken::encode(&Header::new(Algorithm::HS256), &claims, &self.encoding)
+            .expect("Encoding HS256 claims cannot fail")
+    }
+
+    pub fn verify(&self, token: &str) -> Result<Caller, AuthError> {
+        let validation = Validation::new(Algorithm::HS256);
+
+        let claims = jsonwebtoken::decode::<Claims>(token, &self.decoding, &validation)
+            .map_err(AuthError::InvalidToken)?
+            .claims;
+
+        Ok(Caller {
+            user_id: UserId::from(claims.sub),
+            session_id: SessionId::from(claims.sid),
+        })
+    }
+}
+
+pub fn random<const N: usize>() -> [u8; N] {
+    let mut bytes = [0; N];
+    ring::rand::SystemRandom::new()
+        .fill(&mut bytes)
+        .expect("The system random number generator is unavailable");
+    bytes
+}
+
+/// Slow by design; call it from a blocking task.
+pub fn hash_password(password: &str) -> String {
+    let salt = SaltString::encode_b64(&random::<16>()).expect("16 bytes is a valid salt length");
+
+    Argon2::default()
+        .hash_password(password.as_bytes(), &salt)
+        .expect("Argon2 accepts any password with default params")
+        .to_string()
+}
+
+/// Verifies against a throwaway hash when the user has none, so that unknown accounts take as
+/// long to reject as wrong passwords.
+pub fn verify_password(hash: Option<&str>, password: &str) -> bool {
+    static DUMMY: OnceLock<String> = OnceLock::new();
+
+    let (hash, known) = match hash {
+        Some(hash) => (hash, true),
+        None => (DUMMY.get_or_init(|| hash_password("")).as_str(), false),
+    };
+
+    let verified = PasswordHash::new(hash).is_ok_and(|hash| {
+        Argon2::default()
+            .verify_password(password.as_bytes(), &hash)
+            .is_ok()
+    });
+
+    known && verified
+}
+
+/// Returns an opaque refresh token and the hash under which it is stored.
+pub fn new_refresh_token() -> (String, String) {
+    let token = base64::encode_config(random::<32>(), base64::URL_SAFE_NO_PAD);
+    let hash = hash_refresh_token(&token);
+    (token, hash)
+}
+
+pub fn hash_refresh_token(token: &str) -> String {
+    digest::digest(&digest::SHA256, token.as_bytes())
+        .as_ref()
+        .iter()
+        .map(|b| format!("{b:02x}"))
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn caller(user_id: i32) -> Caller {
+        Caller {
+            user_id: UserId::from(user_id),
+            session_id: SessionId::from(1),
+        }
+    }
+
+    #[test]
+    fn verifies_issued_tokens() {
+        let tokens = Tokens::new("secret");
+        let token = tokens.issue(caller(42), OffsetDateTime::now_utc());
+
+        let verified = tokens.verify(&token).unwrap();
+        assert_eq!(*verified.user_id, 42);
+        assert_eq!(*verified.session_id, 1);
+    }
+
+    #[test]
+    fn rejects_tokens_signed_with_another_key() {
+        let token = Tokens::new("other").issue(caller(42), OffsetDateTime::now_utc());
+
+        assert!(Tokens::new("secret").verify(&token).is_err());
+    }
+
+    #[test]
+    fn rejects_expired_tokens() {
+        let tokens = Tokens::new("secret");
+        let issued_at = OffsetDateTime::now_utc() - ACCESS_TOKEN_TTL - Duration::hours(1);
+        let token = tokens.issue(caller(42), issued_at);
+
+        assert!(tokens.verify(&token).is_err());
+    }
+
+    #[test]
+    fn rejects_tampered_tokens() {
+        let tokens = Tokens::new("secret");
+        let token = tokens.issue(caller(42), OffsetDateTime::now_utc());
+        let forged = Tokens::new("secret").issue(caller(7), OffsetDateTime::now_utc());
+
+        let (header_and_claims, _) = forged.rsplit_once('.').unwrap();
+        let (_, signature) = token.rsplit_once('.').unwrap();
+
+        assert!(tokens
+            .verify(&format!("{header_and_claims}.{signature}"))
+            .is_err());
+    }
+
+    #[test]
+    fn rejects_tokens_without_a_session() {
+        let key = EncodingKey::from_secret(b"secret");
+        let now = OffsetDateTime::now_utc();
+        let claims = serde_json::json!({
+            "sub": 42,
+            "iat": now.unix_timestamp(),
+            "exp": (now + Duration::days(30)).unix_timestamp(),
+        });
+        let token = jsonwebtoken::encode(&Header::new(Algorithm::HS256), &claims, &key).unwrap();
+
+        assert!(matches!(
+            Tokens::new("secret").verify(&token),
+            Err(AuthError::InvalidToken(_))
+        ));
+    }
+
+    #[test]
+    fn verifies_passwords() {
+        let hash = hash_password("correct horse");
+
+        assert!(hash.starts_with("$argon2id$"));
+        assert!(verify_password(Some(&hash), "correct horse"));
+        assert!(!verify_password(Some(&hash), "battery staple"));
+        assert!(!verify_password(None, ""));
+    }
+
+    #[test]
+    fn refresh_tokens_are_random_and_hashed() {
+        let (t0, h0) = new_refresh_token();
+        let (t1, _) = new_refresh_token();
+
+        assert_ne!(t0, t1);
+        assert_eq!(hash_refresh_token(&t0), h0);
+        assert_eq!(h0.len(), 64);
+    }
+}
@@ -0,0 +1,160 @@
+use bech32::{FromBase32, Variant};
+
+const SATS_PER_BTC: i64 = 100_000_000;
+
+#[derive(Debug, PartialEq, Eq, thiserror::Error)]
+pub enum BitcoinError {
+    #[error("Invalid bitcoin address")]
+    InvalidAddress,
+}
+
+/// Validates mainnet, testnet and regtest addresses and returns them in canonical form, with
+/// bech32 addresses lowercased.
+pub fn validate_address(address: &str) -> Result<String, BitcoinError> {
+    if let Ok((hrp, data, variant)) = bech32::decode(address) {
+        return validate_segwit(&hrp, &data, variant).map(|()| address.to_ascii_lowercase());
+    }
+
+    let payload = bs58::decode(address)
+        .with_check(None)
+        .into_vec()
+        .map_err(|_| BitcoinError::InvalidAddress)?;
+
+    // P2PKH and P2SH version bytes for mainnet and testnet/regtest.
+    match payload.split_first() {
+        Some((0x00 | 0x05 | 0x6f | 0xc4, hash)) if hash.len() == 20 => Ok(address.to_owned()),
+        _ => Err(BitcoinError::InvalidAddress),
+    }
+}
+
+fn validate_segwit(hrp: &str, data: &[bech32::u5], variant: Variant) -> Result<(), BitcoinError> {
+    if !matches!(hrp, "bc" | "tb" | "bcrt") {
+        return Err(BitcoinError::InvalidAddress);
+    }
+
+    let (version, program) = data.split_first().ok_or(BitcoinError::InvalidAddress)?;
+    let program = Vec::<u8>::from_base32(program).map_err(|_| BitcoinError::InvalidAddress)?;
+
+    let valid = match (version.to_u8(), variant) {
+        (0, Variant::Bech32) => program.len() == 20 || program.len() == 32,
+        (1..=16, Variant::Bech32m) => (2..=40).contains(&program.len()),
+        _ => false,
+    };
+
+    if valid {
+        Ok(())
+    } else {
+        Err(BitcoinError::InvalidAddress)
+    }
+}
+
+/// Builds a BIP21 URI. The amount is expressed in BTC without trailing zeros.
+pub fn payment_uri(address: &str, amount_sats: i64, label: Option<&str>) -> String {
+    let whole = amount_sats / SATS_PER_BTC;
+    let fraction = format!("{:08}", amount_sats % SATS_PER_BTC);
+    let fraction = fraction.trim_end_matches('0');
+
+    let mut uri = if fraction.is_empty() {
+        format!("bitcoin:{address}?amount={whole}")
+    } else {
+        format!("bitcoin:{address}?amount={whole}.{fraction}")
+    };
+
+    if let Some(label) = label {
+        uri += "&label=";
+        uri += &percent_encode(label);
+    }
+
+    uri
+}
+
+fn percent_encode(value: &str) -> String {
+    value
+        .bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                (b as char).to_string()
+            }
+            b => format!("%{b:02X}"),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn accepts_base58_addresses() {
+        for address in [
+            "1BvBMSEYstWetqTFn5Au4m4GFg7xJaNVN2",
+            "3J98t1WpEZ73CNmQviecrnyiWrnqRhWNLy",
+            "mipcBbFg9gMiCh81Kj8tqqdgoZub1ZJRfn",
+        ] {
+            assert_eq!(validate_address(address).as_deref(), Ok(address));
+        }
+    }
+
+    #[test]
+    fn rejects_bad_base58_checksum() {
+        assert_eq!(
+            validate_address("1BvBMSEYstWetqTFn5Au4m4GFg7xJaNVN3"),
+            Err(BitcoinError::InvalidAddress)
+        );
+    }
+
+    #[test]
+    fn accepts_segwit_addresses() {
+        // BIP173 and BIP350 vectors.
+        assert_eq!(
+            validate_address("BC1QW508D6QEJXTDG4Y5R3ZARVARY0C5XW7KV8F3T4").as_deref(),
+            Ok("bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4")
+        );
+        assert!(
+            validate_address("tb1qrp33g0q5c5txsp9arysrx4k6zdkfs4nce4xj0gdcccefvpysxf3q0sl5k7")
+                .is_ok()
+        );
+        assert!(
+            validate_address("bc1p0xlxvlhemja6c4dqv22uapctqupfhlxm9h8z3k2e72q4k9hcz7vqzk5jj0")
+                .is_ok()
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_segwit_addresses() {
+        for address in [
+            // Unknown human readable part.
+            "tc1qw508d6qejxtdg4y5r3zarvary0c5xw7kg3g4ty",
+            // Bad checksum.
+            "bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t5",
+            // Version 0 encoded with bech32m.
+            "bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kemeawh",
+            // Version 1 encoded with bech32.
+            "bc1p0xlxvlhemja6c4dqv22uapctqupfhlxm9h8z3k2e72q4k9hcz7vqh2y7hd",
+        ] {
+            assert_eq!(
+                validate_address(address),
+                Err(BitcoinError::InvalidAddress),
+                "{address}"
+            );
+        }
+    }
+
+    #[test]
+    fn builds_bip21_uris() {
+        let address = "bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4";
+
+        assert_eq!(
+            payment_uri(address, 150_000_000, None),
+            format!("bitcoin:{address}?amount=1.5")
+        );
+        assert_eq!(
+            payment_uri(address, 100_000_000, None),
+            format!("bitcoin:{address}?amount=1")
+        );
+        assert_eq!(
+            payment_uri(address, 1, Some("Rent & bills")),
+            format!("bitcoin:{address}?amount=0.00000001&label=Rent%20%26%20bills")
+        );
+    }
+}
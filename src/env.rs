@@ -1,19 +1,204 @@
-use std::{collections::HashMap, fmt::Debug, net::SocketAddr, ops::Deref, str::FromStr, sync::Arc};
+use std::{
+    collections::{HashMap, HashSet},
+    fmt::{self, Display},
+    io,
+    net::SocketAddr,
+    num::{NonZeroU32, NonZeroU64},
+    ops::Deref,
+    str::FromStr,
+    sync::Arc,
+    time::Duration,
+};
+
+/// Read when `CONFIG_FILE` is unset, if it exists.
+const DEFAULT_CONFIG_FILE: &str = "splitwiser.toml";
+const DEFAULT_PORT: u16 = 50051;
+const DEFAULT_RATE_LIMIT_PER_USER: NonZeroU32 = NonZeroU32::new(120).unwrap();
+const DEFAULT_RATE_LIMIT_PER_IP: NonZeroU32 = NonZeroU32::new(60).unwrap();
+const DEFAULT_SHUTDOWN_GRACE_PERIOD: Duration = Duration::from_secs(20);
+const DEFAULT_DATABASE_CONNECTION_TIMEOUT: Duration = Duration::from_secs(30);
+const DEFAULT_DATABASE_STATEMENT_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Shortest `AUTH_KEY` accepted in prod: 256 bits, the size of an HS256 signature.
+const MIN_PROD_AUTH_KEY_LEN: usize = 32;
+
+/// The kind of deployment, read from `ENV`. It picks the defaults below and what is checked at
+/// startup.
+///
+/// | Setting                 | `dev`             | `test`            | `prod`          |
+/// |-------------------------|-------------------|-------------------|-----------------|
+/// | `SOCKET`                | `127.0.0.1:50051` | `127.0.0.1:50051` | `0.0.0.0:50051` |
+/// | `DATABASE_POOL_SIZE`    | 2                 | 2                 | 10              |
+/// | `REFLECTION`            | on                | on                | off             |
+/// | `LOG_FORMAT`            | pretty            | json              | json            |
+/// | `SHUTDOWN_DRAIN_PERIOD` | 0 s               | 0 s               | 5 s             |
+/// | Lightning without LND   | fake backend      | fake backend      | disabled        |
+/// | `AUTH_KEY` length       | any               | any               | at least 32     |
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Profile {
+    Dev,
+    Test,
+    Prod,
+}
+
+impl Profile {
+    /// Whether a fake Lightning backend stands in when LND is not configured.
+    pub fn fakes_lightning(self) -> bool {
+        self != Self::Prod
+    }
+
+    fn socket(self) -> SocketAddr {
+        match self {
+            Self::Dev | Self::Test => ([127, 0, 0, 1], DEFAULT_PORT).into(),
+            Self::Prod => ([0, 0, 0, 0], DEFAULT_PORT).into(),
+        }
+    }
+
+    fn pool_size(self) -> u32 {
+        match self {
+            Self::Dev | Self::Test => 2,
+            Self::Prod => 10,
+        }
+    }
+
+    fn drain_period(self) -> Duration {
+        match self {
+            Self::Dev | Self::Test => Duration::ZERO,
+            Self::Prod => Duration::from_secs(5),
+        }
+    }
+
+    fn log_format(self) -> LogFormat {
+        match self {
+            Self::Dev => LogFormat::Pretty,
+            Self::Test | Self::Prod => LogFormat::Json,
+        }
+    }
+}
+
+impl FromStr for Profile {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "dev" => Ok(Self::Dev),
+            "test" => Ok(Self::Test),
+            "prod" => Ok(Self::Prod),
+            _ => Err(format!("Unknown profile {s:?}, expected dev, test or prod")),
+        }
+    }
+}
+
+/// How log lines are written to stdout.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LogFormat {
+    Json,
+    Pretty,
+}
+
+impl FromStr for LogFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "json" => Ok(Self::Json),
+            "pretty" => Ok(Self::Pretty),
+            _ => Err(format!("Unknown log format {s:?}")),
+        }
+    }
+}
+
+/// Where a setting was read from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Origin {
+    Environment,
+    DotEnv,
+    ConfigFile(String),
+}
+
+impl Display for Origin {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Environment => write!(f, "the environment"),
+            Self::DotEnv => write!(f, ".env"),
+            Self::ConfigFile(path) => write!(f, "{path}"),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, thiserror::Error)]
+pub enum Problem {
+    #[error("{0} is required")]
+    Missing(&'static str),
+    #[error("{key} from {origin} is invalid: {reason}")]
+    Invalid {
+        key: &'static str,
+        origin: Origin,
+        reason: String,
+    },
+    #[error("{path} has an unknown key {key:?}")]
+    UnknownKey { path: String, key: String },
+    #[error("Could not read {path}: {reason}")]
+    File { path: String, reason: String },
+    #[error("{0}")]
+    Conflict(&'static str),
+}
+
+/// Every problem found while loading, so they can all be fixed at once.
+#[derive(Debug)]
+pub struct Error(pub Vec<Problem>);
+
+impl Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Invalid configuration:")?;
+        for problem in &self.0 {
+            write!(f, "\n  - {problem}")?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for Error {}
 
 #[derive(Clone)]
 pub struct Env(Arc<EnvVars>);
 
 impl Env {
-    pub fn load() -> Self {
-        let raw = std::fs::read_to_string(".env").ok();
-        let map = raw.as_ref().map(|raw| {
-            raw.lines()
-                .filter(|l| !l.starts_with('#'))
-                .filter_map(|l| l.split_once('='))
-                .collect()
-        });
+    /// Reads settings from, in order of precedence, the process environment, `.env`, the TOML file
+    /// at `CONFIG_FILE` (or `splitwiser.toml`), and the defaults of the profile.
+    ///
+    /// Keys in the config file are the lowercase names of the variables. Unknown keys there are
+    /// errors; unknown keys in `.env` are ignored, since it is shared with tools like the diesel
+    /// CLI.
+    pub fn load() -> Result<Self, Error> {
+        let dotenv = std::fs::read_to_string(".env")
+            .map(|raw| parse_dotenv(&raw))
+            .unwrap_or_default();
 
-        Self(Arc::new(EnvVars::load(map)))
+        let mut loader = Loader::new(std::env::vars().collect(), dotenv);
+        loader.add_config_file(|path| std::fs::read_to_string(path));
+
+        EnvVars::load(loader).map(|vars| Self(Arc::new(vars)))
+    }
+
+    /// The `test` profile's defaults, ignoring the process environment and config files.
+    #[cfg(test)]
+    pub fn test() -> Self {
+        let vars = [
+            ("ENV", "test"),
+            ("DATABASE_URL", "postgres://localhost/splitwiser_test"),
+            ("AUTH_KEY", "secret"),
+        ];
+        let loader = Loader::new(
+            vars.into_iter()
+                .map(|(key, value)| (key.to_owned(), value.to_owned()))
+                .collect(),
+            HashMap::new(),
+        );
+
+        Self(Arc::new(
+            EnvVars::load(loader).expect("Invalid test settings"),
+        ))
     }
 }
 
@@ -28,39 +213,444 @@ impl Deref for Env {
 #[allow(clippy::module_name_repetitions)]
 pub struct EnvVars {
     pub database_url: String,
-    pub env: String,
+    pub database_pool: db::PoolConfig,
+    pub profile: Profile,
     pub socket: SocketAddr,
+    /// Where `SplitwiserAdmin` listens; the admin service is disabled when unset. Only loopback
+    /// addresses are allowed without TLS.
+    pub admin_socket: Option<SocketAddr>,
+    /// Bearer token operators present to `SplitwiserAdmin`.
+    pub admin_token: Option<String>,
+    /// Where the JSON gateway listens; it is disabled when unset.
+    pub gateway_socket: Option<SocketAddr>,
+    /// Where Prometheus metrics are served; they are not exposed when unset.
+    pub metrics_socket: Option<SocketAddr>,
+    /// PEM certificate chain and private key; the listener speaks TLS when both are set.
+    pub tls_cert: Option<String>,
+    pub tls_key: Option<String>,
+    /// PEM bundle of CAs whose client certificates the admin listener requires, enabling mutual
+    /// TLS there.
+    pub tls_client_ca: Option<String>,
+    /// Whether to serve gRPC server reflection.
+    pub reflection: bool,
+    pub log_format: LogFormat,
+    pub log_level: tracing::Level,
+    /// Browser origins allowed to call the service over gRPC-Web.
+    pub grpc_web_origins: Vec<String>,
+    pub auth_key: String,
+    /// Requests per minute for each authenticated user.
+    pub rate_limit_per_user: NonZeroU32,
+    /// Requests per minute for each peer IP, applied to anonymous requests.
+    pub rate_limit_per_ip: NonZeroU32,
+    /// How long health checks report NOT_SERVING after SIGTERM or SIGINT before the listeners
+    /// stop accepting connections, so that load balancers can route around the server first.
+    pub shutdown_drain_period: Duration,
+    /// How long in-flight requests may run once the listeners stop accepting connections.
+    pub shutdown_grace_period: Duration,
+    pub lnd_rest_url: Option<String>,
+    pub lnd_macaroon: Option<String>,
+    pub lnd_tls_cert: Option<String>,
 }
 
 impl EnvVars {
-    fn load(mut map: Option<HashMap<&str, &str>>) -> Self {
-        let vars = Self {
-            database_url: read(&mut map, "DATABASE_URL"),
-            env: read(&mut map, "ENV"),
-            socket: read(&mut map, "SOCKET"),
+    fn load(mut loader: Loader) -> Result<Self, Error> {
+        let profile = loader.required("ENV");
+        // Defaults follow the strictest profile until `ENV` is fixed.
+        let defaults = profile.unwrap_or(Profile::Prod);
+
+        let database_url = loader.required("DATABASE_URL");
+        let database_pool = db::PoolConfig {
+            max_size: loader
+                .optional("DATABASE_POOL_SIZE")
+                .map_or(defaults.pool_size(), NonZeroU32::get),
+            connection_timeout: loader
+                .optional("DATABASE_CONNECTION_TIMEOUT")
+                .map(NonZeroU64::get)
+                .map_or(DEFAULT_DATABASE_CONNECTION_TIMEOUT, Duration::from_secs),
+            statement_timeout: loader.seconds(
+                "DATABASE_STATEMENT_TIMEOUT",
+                DEFAULT_DATABASE_STATEMENT_TIMEOUT,
+            ),
         };
+        let socket = loader.or("SOCKET", defaults.socket());
+        let admin_socket: Option<SocketAddr> = loader.optional("ADMIN_SOCKET");
+        let admin_token = loader.optional("ADMIN_TOKEN");
+        let gateway_socket = loader.optional("GATEWAY_SOCKET");
+        let metrics_socket = loader.optional("METRICS_SOCKET");
+        let tls_cert = loader.optional("TLS_CERT");
+        let tls_key = loader.optional("TLS_KEY");
+        let tls_client_ca = loader.optional("TLS_CLIENT_CA");
+        let reflection = loader.or("REFLECTION", defaults != Profile::Prod);
+        let log_format = loader.or("LOG_FORMAT", defaults.log_format());
+        let log_level = loader.or("LOG_LEVEL", tracing::Level::INFO);
+        let grpc_web_origins = loader
+            .optional::<String>("GRPC_WEB_ORIGINS")
+            .map(|origins| read_list(&origins))
+            .unwrap_or_default();
+        let auth_key: Option<String> = loader.required("AUTH_KEY");
+        let rate_limit_per_user = loader.or("RATE_LIMIT_PER_USER", DEFAULT_RATE_LIMIT_PER_USER);
+        let rate_limit_per_ip = loader.or("RATE_LIMIT_PER_IP", DEFAULT_RATE_LIMIT_PER_IP);
+        let shutdown_drain_period =
+            loader.seconds("SHUTDOWN_DRAIN_PERIOD", defaults.drain_period());
+        let shutdown_grace_period =
+            loader.seconds("SHUTDOWN_GRACE_PERIOD", DEFAULT_SHUTDOWN_GRACE_PERIOD);
+        let lnd_rest_url = loader.optional("LND_REST_URL");
+        let lnd_macaroon = loader.optional("LND_MACAROON");
+        let lnd_tls_cert = loader.optional("LND_TLS_CERT");
 
-        if let Some(m) = map.filter(|m| !m.is_empty()) {
-            panic!("Env file has extra keys: {:?}", m.keys());
+        if admin_socket.is_some() && admin_token.is_none() {
+            loader.conflict("ADMIN_TOKEN is required when ADMIN_SOCKET is set");
         }
 
-        vars
+        if admin_socket.is_some_and(|socket| !socket.ip().is_loopback()) && tls_cert.is_none() {
+            loader.conflict("ADMIN_SOCKET must be a loopback address unless TLS is configured");
+        }
+
+        if tls_cert.is_some() != tls_key.is_some() {
+            loader.conflict("TLS_CERT and TLS_KEY must be set together");
+        }
+
+        if tls_client_ca.is_some() && tls_cert.is_none() {
+            loader.conflict("TLS_CLIENT_CA requires TLS_CERT and TLS_KEY");
+        }
+
+        if profile == Some(Profile::Prod)
+            && auth_key
+                .as_ref()
+                .is_some_and(|key| key.len() < MIN_PROD_AUTH_KEY_LEN)
+        {
+            loader.conflict("AUTH_KEY must be at least 32 characters in prod");
+        }
+
+        let problems = loader.finish();
+        match (profile, database_url, auth_key) {
+            (Some(profile), Some(database_url), Some(auth_key)) if problems.is_empty() => {
+                Ok(Self {
+                    database_url,
+                    database_pool,
+                    profile,
+                    socket,
+                    admin_socket,
+                    admin_token,
+                    gateway_socket,
+                    metrics_socket,
+                    tls_cert,
+                    tls_key,
+                    tls_client_ca,
+                    reflection,
+                    log_format,
+                    log_level,
+                    grpc_web_origins,
+                    auth_key,
+                    rate_limit_per_user,
+                    rate_limit_per_ip,
+                    shutdown_drain_period,
+                    shutdown_grace_period,
+                    lnd_rest_url,
+                    lnd_macaroon,
+                    lnd_tls_cert,
+                })
+            }
+            _ => Err(Error(problems)),
+        }
     }
 }
 
-fn read<Parsed, ParseError>(map: &mut Option<HashMap<&str, &str>>, key: &str) -> Parsed
-where
-    ParseError: Debug,
-    Parsed: FromStr<Err = ParseError>,
-{
-    if let Some(map) = map.as_mut() {
-        if let Some(val) = map.remove(key).map(ToOwned::to_owned) {
-            std::env::set_var(key, val.replace("\\n", "\n"));
+/// Raw values in layers from highest to lowest precedence, collecting problems instead of
+/// stopping at the first.
+struct Loader {
+    layers: Vec<(Origin, HashMap<String, String>)>,
+    requested: HashSet<&'static str>,
+    problems: Vec<Problem>,
+}
+
+impl Loader {
+    fn new(environment: HashMap<String, String>, dotenv: HashMap<String, String>) -> Self {
+        Self {
+            layers: vec![(Origin::Environment, environment), (Origin::DotEnv, dotenv)],
+            requested: HashSet::new(),
+            problems: Vec::new(),
+        }
+    }
+
+    /// Adds the config file named by `CONFIG_FILE`, or the default one if it exists.
+    fn add_config_file(&mut self, read: impl FnOnce(&str) -> io::Result<String>) {
+        let explicit = self.optional::<String>("CONFIG_FILE");
+        let path = explicit.as_deref().unwrap_or(DEFAULT_CONFIG_FILE);
+
+        match read(path) {
+            Ok(raw) => self.add_toml(path, &raw),
+            Err(e) if explicit.is_none() && e.kind() == io::ErrorKind::NotFound => {}
+            Err(e) => self.problems.push(Problem::File {
+                path: path.to_owned(),
+                reason: e.to_string(),
+            }),
         }
     }
 
-    std::env::var(key)
-        .unwrap_or_else(|_| panic!("No {key} environment variable found"))
-        .parse()
-        .unwrap_or_else(|_| panic!("Could not parse {key} environment variable"))
+    fn add_toml(&mut self, path: &str, raw: &str) {
+        let table = match raw.parse::<toml::Value>() {
+            Ok(toml::Value::Table(table)) => table,
+            Ok(_) => unreachable!("A TOML document is a table"),
+            Err(e) => {
+                self.problems.push(Problem::File {
+                    path: path.to_owned(),
+                    reason: e.to_string(),
+                });
+                return;
+            }
+        };
+
+        let mut values = HashMap::new();
+        for (key, value) in table {
+            let value = match value {
+                toml::Value::String(s) => s,
+                toml::Value::Array(entries) => entries
+                    .iter()
+                    .map(|entry| entry.as_str().map_or_else(|| entry.to_string(), Into::into))
+                    .collect::<Vec<_>>()
+                    .join(","),
+                toml::Value::Table(_) => {
+                    self.problems.push(Problem::File {
+                        path: path.to_owned(),
+                        reason: format!("{key} is a table, but settings are flat"),
+                    });
+                    continue;
+                }
+                other => other.to_string(),
+            };
+            values.insert(key.to_uppercase(), value);
+        }
+
+        self.layers
+            .push((Origin::ConfigFile(path.to_owned()), values));
+    }
+
+    /// The highest-precedence value of `key`, treating empty values as unset.
+    fn raw(&mut self, key: &'static str) -> Option<(Origin, String)> {
+        self.requested.insert(key);
+
+        self.layers.iter().find_map(|(origin, values)| {
+            values
+                .get(key)
+                .filter(|value| !value.is_empty())
+                .map(|value| (origin.clone(), value.clone()))
+        })
+    }
+
+    fn optional<T>(&mut self, key: &'static str) -> Option<T>
+    where
+        T: FromStr,
+        T::Err: Display,
+    {
+        let (origin, raw) = self.raw(key)?;
+
+        raw.parse()
+            .map_err(|e: T::Err| {
+                self.problems.push(Problem::Invalid {
+                    key,
+                    origin,
+                    reason: e.to_string(),
+                });
+            })
+            .ok()
+    }
+
+    fn required<T>(&mut self, key: &'static str) -> Option<T>
+    where
+        T: FromStr,
+        T::Err: Display,
+    {
+        if self.raw(key).is_none() {
+            self.problems.push(Problem::Missing(key));
+        }
+        self.optional(key)
+    }
+
+    fn or<T>(&mut self, key: &'static str, default: T) -> T
+    where
+        T: FromStr,
+        T::Err: Display,
+    {
+        self.optional(key).unwrap_or(default)
+    }
+
+    fn seconds(&mut self, key: &'static str, default: Duration) -> Duration {
+        self.optional(key).map_or(default, Duration::from_secs)
+    }
+
+    fn conflict(&mut self, problem: &'static str) {
+        self.problems.push(Problem::Conflict(problem));
+    }
+
+    /// Every problem found, including keys in config files that no setting reads.
+    fn finish(mut self) -> Vec<Problem> {
+        for (origin, values) in &self.layers {
+            let Origin::ConfigFile(path) = origin else {
+                continue;
+            };
+
+            let mut unknown: Vec<_> = values
+                .keys()
+                .filter(|key| !self.requested.contains(key.as_str()))
+                .collect();
+            unknown.sort();
+
+            self.problems
+                .extend(unknown.into_iter().map(|key| Problem::UnknownKey {
+                    path: path.clone(),
+                    key: key.to_lowercase(),
+                }));
+        }
+
+        self.problems
+    }
+}
+
+/// Parses `KEY=value` lines, skipping comments. `\n` in values stands for a newline, so PEM
+/// contents fit on one line.
+fn parse_dotenv(raw: &str) -> HashMap<String, String> {
+    raw.lines()
+        .filter(|l| !l.starts_with('#'))
+        .filter_map(|l| l.split_once('='))
+        .map(|(key, value)| (key.to_owned(), value.replace("\\n", "\n")))
+        .collect()
+}
+
+/// Splits a comma-separated variable, ignoring blank entries.
+fn read_list(raw: &str) -> Vec<String> {
+    raw.split(',')
+        .map(str::trim)
+        .filter(|entry| !entry.is_empty())
+        .map(ToOwned::to_owned)
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn vars(pairs: &[(&str, &str)]) -> HashMap<String, String> {
+        pairs
+            .iter()
+            .map(|&(key, value)| (key.to_owned(), value.to_owned()))
+            .collect()
+    }
+
+    fn load(
+        environment: &[(&str, &str)],
+        dotenv: &str,
+        config_file: Option<&str>,
+    ) -> Result<EnvVars, Error> {
+        let mut loader = Loader::new(vars(environment), parse_dotenv(dotenv));
+        loader.add_config_file(|_| {
+            config_file
+                .map(ToOwned::to_owned)
+                .ok_or_else(|| io::ErrorKind::NotFound.into())
+        });
+        EnvVars::load(loader)
+    }
+
+    #[test]
+    fn layers_settings_over_profile_defaults() {
+        let config_file = r#"
+            database_url = "postgres://file"
+            database_pool_size = 4
+            socket = "127.0.0.1:1"
+            grpc_web_origins = ["https://a.example", "https://b.example"]
+        "#;
+        let vars = load(
+            &[("ENV", "prod"), ("SOCKET", "127.0.0.1:3")],
+            "SOCKET=127.0.0.1:2\nAUTH_KEY=0123456789abcdef0123456789abcdef\nDIESEL_CONFIG=x",
+            Some(config_file),
+        )
+        .unwrap();
+
+        assert_eq!(vars.profile, Profile::Prod);
+        assert_eq!(vars.database_url, "postgres://file");
+        assert_eq!(vars.database_pool.max_size, 4);
+        assert_eq!(vars.socket, "127.0.0.1:3".parse().unwrap());
+        assert_eq!(
+            vars.grpc_web_origins,
+            ["https://a.example", "https://b.example"]
+        );
+        assert_eq!(vars.log_format, LogFormat::Json);
+        assert!(!vars.reflection);
+
+        let vars = load(
+            &[("ENV", "dev"), ("DATABASE_URL", "x"), ("AUTH_KEY", "k")],
+            "",
+            None,
+        )
+        .unwrap();
+        assert_eq!(vars.socket, Profile::Dev.socket());
+        assert_eq!(vars.database_pool.max_size, 2);
+        assert_eq!(vars.log_format, LogFormat::Pretty);
+        assert!(vars.reflection);
+    }
+
+    #[test]
+    fn reports_every_problem() {
+        let Err(Error(problems)) = load(
+            &[
+                ("ENV", "prod"),
+                ("AUTH_KEY", "short"),
+                ("DATABASE_POOL_SIZE", "0"),
+                ("TLS_CERT", "cert.pem"),
+                ("RATE_LIMIT_PER_IP", "0"),
+            ],
+            "SOCKET=nowhere",
+            Some("databse_url = \"typo\""),
+        ) else {
+            panic!("Expected problems");
+        };
+
+        assert_eq!(
+            problems,
+            [
+                Problem::Missing("DATABASE_URL"),
+                Problem::Invalid {
+                    key: "DATABASE_POOL_SIZE",
+                    origin: Origin::Environment,
+                    reason: "number would be zero for non-zero type".to_owned(),
+                },
+                Problem::Invalid {
+                    key: "SOCKET",
+                    origin: Origin::DotEnv,
+                    reason: "invalid socket address syntax".to_owned(),
+                },
+                Problem::Invalid {
+                    key: "RATE_LIMIT_PER_IP",
+                    origin: Origin::Environment,
+                    reason: "number would be zero for non-zero type".to_owned(),
+                },
+                Problem::Conflict("TLS_CERT and TLS_KEY must be set together"),
+                Problem::Conflict("AUTH_KEY must be at least 32 characters in prod"),
+                Problem::UnknownKey {
+                    path: DEFAULT_CONFIG_FILE.to_owned(),
+                    key: "databse_url".to_owned(),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn keeps_plaintext_admin_on_loopback() {
+        let base = [("ENV", "dev"), ("DATABASE_URL", "x"), ("AUTH_KEY", "k")];
+        let admin = |socket| {
+            load(
+                &[&base[..], &[("ADMIN_SOCKET", socket), ("ADMIN_TOKEN", "t")]].concat(),
+                "",
+                None,
+            )
+        };
+
+        assert!(admin("127.0.0.1:9").is_ok());
+        assert!(matches!(
+            admin("0.0.0.0:9"),
+            Err(Error(problems)) if problems == [Problem::Conflict(
+                "ADMIN_SOCKET must be a loopback address unless TLS is configured"
+            )]
+        ));
+    }
 }
@@ -1 +1,9 @@
+pub(crate) mod admin;
+pub(crate) mod audit;
+pub(crate) mod profile;
+pub(crate) mod session;
+pub(crate) mod settlement;
 pub(crate) mod user;
+
+/// ISO 4217 code of every amount, which is stored in centavos. Pix only moves reais.
+pub const CURRENCY: &str = "BRL";
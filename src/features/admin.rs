@@ -0,0 +1,174 @@
+use db::{
+    enums::AuditEntityType,
+    queries::{
+        integrity::{self, Issue},
+        sessions, user_expenses, user_payments, user_revenues,
+        users::{self, Account},
+    },
+    types::UserId,
+    PgConnection,
+};
+use time::OffsetDateTime;
+
+use super::audit::{self, Audited};
+
+const DEFAULT_LIST_LIMIT: u32 = 50;
+const MAX_LIST_LIMIT: u32 = 200;
+
+#[derive(Debug, thiserror::Error)]
+pub enum AdminError {
+    #[error("Not found")]
+    NotFound,
+    #[error("Entry is referenced by other records")]
+    Referenced,
+    #[error("Database error: {0:?}")]
+    DbError(db::Error),
+}
+
+pub async fn list_users(
+    db: &db::Db,
+    after_id: Option<i32>,
+    limit: u32,
+) -> Result<Vec<Account>, AdminError> {
+    let limit = match limit {
+        0 => DEFAULT_LIST_LIMIT,
+        limit => limit.min(MAX_LIST_LIMIT),
+    };
+
+    db.write(move |conn| users::list(conn, after_id, limit.into()))
+        .await
+        .map_err(AdminError::DbError)
+}
+
+/// Blocks further logins and revokes every session, so access tokens already issued stop working
+/// as well.
+pub async fn deactivate_user(db: &db::Db, id: i32) -> Result<UserId, AdminError> {
+    let now = OffsetDateTime::now_utc();
+
+    db.write(move |conn| {
+        let Some(id) = users::deactivate(conn, id, now)? else {
+            return Ok(None);
+        };
+
+        sessions::revoke_all(conn, *id, now)?;
+        Ok(Some(id))
+    })
+    .await
+    .map_err(AdminError::DbError)?
+    .ok_or(AdminError::NotFound)
+}
+
+pub async fn set_admin(db: &db::Db, id: i32, admin: bool) -> Result<UserId, AdminError> {
+    db.write(move |conn| users::set_admin(conn, id, admin))
+        .await
+        .map_err(AdminError::DbError)?
+        .ok_or(AdminError::NotFound)
+}
+
+/// Deletes a ledger entry regardless of its creator, recording it in the audit log without an
+/// actor.
+pub async fn force_delete_entry(
+    db: &db::Db,
+    entity_type: AuditEntityType,
+    id: i32,
+) -> Result<i32, AdminError> {
+    db.write(move |conn| match entity_type {
+        AuditEntityType::UserRevenue => {
+            let entry = user_revenues::find_by_id(conn, id)?;
+            delete_audited(conn, entry, user_revenues::force_delete)
+        }
+        AuditEntityType::UserPayment => {
+            let entry = user_payments::find_by_id(conn, id)?;
+            delete_audited(conn, entry, user_payments::force_delete)
+        }
+        AuditEntityType::UserExpense => {
+            let entry = user_expenses::find_by_id(conn, id)?;
+            delete_audited(conn, entry, user_expenses::force_delete)
+        }
+    })
+    .await
+    .map_err(|e| match e {
+        db::Error::DatabaseError(db::DatabaseErrorKind::ForeignKeyViolation, _) => {
+            AdminError::Referenced
+        }
+        e => AdminError::DbError(e),
+    })?
+    .ok_or(AdminError::NotFound)
+}
+
+fn delete_audited<T: Audited, I>(
+    conn: &mut PgConnection,
+    entry: Option<T>,
+    delete: fn(&mut PgConnection, i32) -> Result<Option<I>, db::Error>,
+) -> Result<Option<i32>, db::Error> {
+    let Some(entry) = entry else {
+        return Ok(None);
+    };
+
+    delete(conn, entry.id())?;
+    audit::force_deleted(conn, "ForceDeleteEntry", &entry)?;
+    Ok(Some(entry.id()))
+}
+
+pub async fn check_integrity(db: &db::Db) -> Result<Vec<Issue>, AdminError> {
+    db.write(integrity::check)
+        .await
+        .map_err(AdminError::DbError)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::features::user::{self, CreateRevenueParams};
+
+    #[tokio::test]
+    async fn deactivates_users_and_deletes_their_entries() {
+        let db = db::test::db();
+        let u0 = user::create(&db).await.unwrap();
+
+        let revenue = user::create_revenue(
+            &db,
+            u0,
+            CreateRevenueParams {
+                amount_cents: 100,
+                description: None,
+                incoming_at: 0,
+            },
+        )
+        .await
+        .unwrap()
+        .id;
+
+        assert_eq!(*deactivate_user(&db, *u0).await.unwrap(), *u0);
+        assert!(matches!(
+            deactivate_user(&db, *u0).await,
+            Err(AdminError::NotFound)
+        ));
+
+        let accounts = list_users(&db, Some(*u0 - 1), 1).await.unwrap();
+        assert!(accounts[0].deactivated_at.is_some());
+
+        let deleted = force_delete_entry(&db, AuditEntityType::UserRevenue, *revenue)
+            .await
+            .unwrap();
+        assert_eq!(deleted, *revenue);
+        assert!(matches!(
+            force_delete_entry(&db, AuditEntityType::UserRevenue, *revenue).await,
+            Err(AdminError::NotFound)
+        ));
+
+        let events = audit::list(
+            &db,
+            u0,
+            audit::ListParams {
+                entity: Some((AuditEntityType::UserRevenue, *revenue)),
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+        assert_eq!(events[0].rpc, "ForceDeleteEntry");
+        assert_eq!(events[0].actor_user_id, None);
+        assert!(events[0].after.is_none());
+    }
+}
@@ -0,0 +1,286 @@
+use db::{
+    enums::AuditEntityType,
+    queries::{
+        audit_events::{self, AuditEvent},
+        user_expenses::UserExpense,
+        user_payments::UserPayment,
+        user_revenues::UserRevenue,
+    },
+    types::UserId,
+    PgConnection,
+};
+use serde_json::{json, Value};
+use time::OffsetDateTime;
+
+use crate::policy::Entry;
+
+const DEFAULT_LIST_LIMIT: u32 = 50;
+const MAX_LIST_LIMIT: u32 = 200;
+
+/// A ledger entry whose changes are recorded in the audit log.
+pub(super) trait Audited {
+    const ENTITY_TYPE: AuditEntityType;
+
+    fn id(&self) -> i32;
+
+    /// Users who may read the entry's history.
+    fn participants(&self) -> Vec<i32>;
+
+    fn snapshot(&self) -> Value;
+}
+
+impl Audited for UserRevenue {
+    const ENTITY_TYPE: AuditEntityType = AuditEntityType::UserRevenue;
+
+    fn id(&self) -> i32 {
+        *self.id
+    }
+
+    fn participants(&self) -> Vec<i32> {
+        vec![self.user_id]
+    }
+
+    fn snapshot(&self) -> Value {
+        json!({
+            "id": *self.id,
+            "user_id": self.user_id,
+            "amount_cents": self.amount_cents,
+            "description": self.description,
+            "incoming_at": self.incoming_at.unix_timestamp(),
+            "created_at": self.created_at.unix_timestamp(),
+        })
+    }
+}
+
+impl Audited for UserPayment {
+    const ENTITY_TYPE: AuditEntityType = AuditEntityType::UserPayment;
+
+    fn id(&self) -> i32 {
+        *self.id
+    }
+
+    fn participants(&self) -> Vec<i32> {
+        Entry::from(self).participants().to_vec()
+    }
+
+    fn snapshot(&self) -> Value {
+        json!({
+            "id": *self.id,
+            "created_by": self.created_by,
+            "amount_cents": self.amount_cents,
+            "payee_user_id": self.payee_user_id,
+            "payer_user_id": self.payer_user_id,
+            "payed_at": self.payed_at.unix_timestamp(),
+            "status": format!("{:?}", self.status),
+            "method": format!("{:?}", self.method),
+            "proof": self.proof,
+            "reviewed_at": self.reviewed_at.map(OffsetDateTime::unix_timestamp),
+            "created_at": self.created_at.unix_timestamp(),
+        })
+    }
+}
+
+impl Audited for UserExpense {
+    const ENTITY_TYPE: AuditEntityType = AuditEntityType::UserExpense;
+
+    fn id(&self) -> i32 {
+        *self.id
+    }
+
+    fn participants(&self) -> Vec<i32> {
+        Entry::from(self).participants().to_vec()
+    }
+
+    fn snapshot(&self) -> Value {
+        json!({
+            "id": *self.id,
+            "created_by": self.created_by,
+            "amount_cents": self.amount_cents,
+            "description": self.description,
+            "chargee_user_id": self.chargee_user_id,
+            "charged_user_id": self.charged_user_id,
+            "charge_method": format!("{:?}", self.charge_method),
+            "begin_charging_at": self.begin_charging_at.unix_timestamp(),
+            "created_at": self.created_at.unix_timestamp(),
+        })
+    }
+}
+
+pub(super) fn created<T: Audited>(
+    conn: &mut PgConnection,
+    caller: UserId,
+    rpc: &str,
+    entity: &T,
+) -> Result<(), db::Error> {
+    record(
+        conn,
+        Some(caller),
+        rpc,
+        entity,
+        None,
+        Some(entity.snapshot()),
+    )
+}
+
+pub(super) fn updated<T: Audited>(
+    conn: &mut PgConnection,
+    caller: UserId,
+    rpc: &str,
+    before: &T,
+    after: &T,
+) -> Result<(), db::Error> {
+    record(
+        conn,
+        Some(caller),
+        rpc,
+        after,
+        Some(before.snapshot()),
+        Some(after.snapshot()),
+    )
+}
+
+pub(super) fn deleted<T: Audited>(
+    conn: &mut PgConnection,
+    caller: UserId,
+    rpc: &str,
+    entity: &T,
+) -> Result<(), db::Error> {
+    record(
+        conn,
+        Some(caller),
+        rpc,
+        entity,
+        Some(entity.snapshot()),
+        None,
+    )
+}
+
+/// Records a deletion by an operator, who has no user account to act as.
+pub(super) fn force_deleted<T: Audited>(
+    conn: &mut PgConnection,
+    rpc: &str,
+    entity: &T,
+) -> Result<(), db::Error> {
+    record(conn, None, rpc, entity, Some(entity.snapshot()), None)
+}
+
+fn record<T: Audited>(
+    conn: &mut PgConnection,
+    actor: Option<UserId>,
+    rpc: &str,
+    entity: &T,
+    before: Option<Value>,
+    after: Option<Value>,
+) -> Result<(), db::Error> {
+    audit_events::create(
+        conn,
+        &audit_events::CreateParams {
+            actor_user_id: actor.map(|actor| *actor),
+            rpc,
+            entity_type: T::ENTITY_TYPE,
+            entity_id: entity.id(),
+            participant_user_ids: &entity.participants(),
+            before: before.as_ref(),
+            after: after.as_ref(),
+            created_at: OffsetDateTime::now_utc(),
+        },
+    )?;
+
+    Ok(())
+}
+
+#[derive(Default)]
+pub struct ListParams {
+    pub entity: Option<(AuditEntityType, i32)>,
+    pub actor_user_id: Option<i32>,
+    pub before_id: Option<i32>,
+    pub limit: u32,
+}
+
+/// Lists events of entries the caller participates in, newest first.
+pub async fn list(
+    db: &db::Db,
+    caller: UserId,
+    ListParams {
+        entity,
+        actor_user_id,
+        before_id,
+        limit,
+    }: ListParams,
+) -> Result<Vec<AuditEvent>, db::Error> {
+    let limit = match limit {
+        0 => DEFAULT_LIST_LIMIT,
+        limit => limit.min(MAX_LIST_LIMIT),
+    };
+
+    db.write(move |conn| {
+        audit_events::list(
+            conn,
+            &audit_events::ListParams {
+                participant_user_id: *caller,
+                entity,
+                actor_user_id,
+                before_id,
+                limit: limit.into(),
+            },
+        )
+    })
+    .await
+}
+
+#[cfg(test)]
+mod test {
+    use db::enums::UserExpensesChargeMethod;
+
+    use super::*;
+    use crate::features::user::{self, CreateExpenseOutcome, CreateExpenseParams};
+
+    #[tokio::test]
+    async fn records_creations_and_deletions() {
+        let db = db::test::db();
+        let u0 = user::create(&db).await.unwrap();
+        let u1 = user::create(&db).await.unwrap();
+        let outsider = user::create(&db).await.unwrap();
+
+        let CreateExpenseOutcome::Created(expense) = user::create_expense(
+            &db,
+            u0,
+            CreateExpenseParams {
+                amount_cents: 100,
+                begin_charging_at: 0,
+                charged_user_id: *u1,
+                chargee_user_id: *u0,
+                charge_method: UserExpensesChargeMethod::Even,
+                description: None,
+                installments: 1,
+            },
+        )
+        .await
+        .unwrap();
+        let id = *expense.id;
+        user::delete_expense(&db, u0, id).await.unwrap();
+
+        let events = list(
+            &db,
+            u1,
+            ListParams {
+                entity: Some((AuditEntityType::UserExpense, id)),
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0].rpc, "DeleteExpense");
+        assert_eq!(events[0].actor_user_id, Some(*u0));
+        assert_eq!(events[0].before.as_ref().unwrap()["amount_cents"], 100);
+        assert!(events[0].after.is_none());
+        assert_eq!(events[1].rpc, "CreateExpense");
+        assert!(events[1].before.is_none());
+        assert_eq!(events[0].before, events[1].after);
+
+        let hidden = list(&db, outsider, ListParams::default()).await.unwrap();
+        assert!(hidden.is_empty());
+    }
+}
@@ -0,0 +1,294 @@
+use db::{
+    queries::users::{self, Profile},
+    types::UserId,
+};
+use time_tz::{timezones, TimeZone, Tz};
+
+const MAX_DISPLAY_NAME_LEN: usize = 64;
+const MAX_EMAIL_LEN: usize = 254;
+const MAX_AVATAR_URL_LEN: usize = 2048;
+const MIN_QUERY_LEN: usize = 2;
+const DEFAULT_SEARCH_LIMIT: u32 = 20;
+const MAX_SEARCH_LIMIT: u32 = 50;
+
+#[derive(Debug, thiserror::Error)]
+pub enum ProfileError {
+    #[error("Display name must have between 1 and {MAX_DISPLAY_NAME_LEN} characters")]
+    InvalidDisplayName,
+    #[error("Invalid e-mail address")]
+    InvalidEmail,
+    #[error("Locale must be a language tag such as pt-BR")]
+    InvalidLocale,
+    #[error("Unknown timezone")]
+    InvalidTimezone,
+    #[error("Avatar URL must be an https URL")]
+    InvalidAvatarUrl,
+    #[error("E-mail address already in use")]
+    EmailTaken,
+    #[error("Search query must have at least {MIN_QUERY_LEN} characters")]
+    QueryTooShort,
+    #[error("User not found")]
+    UserNotFound,
+    #[error("Database error: {0:?}")]
+    DbError(db::Error),
+}
+
+/// Looks up an IANA timezone, falling back to UTC for names that are no longer known.
+pub fn timezone(name: &str) -> &'static Tz {
+    timezones::get_by_name(name).unwrap_or(timezones::db::UTC)
+}
+
+pub async fn get(db: &db::Db, caller: UserId, user_id: i32) -> Result<Profile, ProfileError> {
+    let profile = db
+        .write(move |conn| users::find_profile(conn, user_id))
+        .await
+        .map_err(ProfileError::DbError)?
+        .ok_or(ProfileError::UserNotFound)?;
+
+    Ok(redact(caller, profile))
+}
+
+#[derive(Default)]
+pub struct UpdateProfileParams {
+    pub display_name: Option<String>,
+    pub email: Option<String>,
+    pub locale: Option<String>,
+    pub timezone: Option<String>,
+    pub avatar_url: Option<String>,
+}
+
+pub async fn update(
+    db: &db::Db,
+    caller: UserId,
+    UpdateProfileParams {
+        display_name,
+        email,
+        locale,
+        timezone,
+        avatar_url,
+    }: UpdateProfileParams,
+) -> Result<Profile, ProfileError> {
+    let display_name = display_name.map(validate_display_name).transpose()?;
+    let email = email.map(validate_email).transpose()?;
+    let locale = locale.map(validate_locale).transpose()?;
+    let timezone = timezone.map(validate_timezone).transpose()?;
+    let avatar_url = avatar_url.map(validate_avatar_url).transpose()?;
+
+    db.write(move |conn| {
+        users::update_profile(
+            conn,
+            *caller,
+            &users::UpdateProfileParams {
+                display_name: display_name.as_deref(),
+                email: email.as_deref(),
+                locale: locale.as_deref(),
+                timezone: timezone.as_deref(),
+                avatar_url: avatar_url.as_deref(),
+            },
+        )
+    })
+    .await
+    .map_err(|e| match e {
+        db::Error::DatabaseError(db::DatabaseErrorKind::UniqueViolation, _) => {
+            ProfileError::EmailTaken
+        }
+        e => ProfileError::DbError(e),
+    })?
+    .ok_or(ProfileError::UserNotFound)
+}
+
+/// Searches display names by prefix. Queries containing `@` only match a whole e-mail address,
+/// so addresses cannot be enumerated.
+pub async fn search(
+    db: &db::Db,
+    caller: UserId,
+    query: &str,
+    limit: u32,
+) -> Result<Vec<Profile>, ProfileError> {
+    let query = query.trim();
+    if query.chars().count() < MIN_QUERY_LEN {
+        return Err(ProfileError::QueryTooShort);
+    }
+
+    let query = if query.contains('@') {
+        query.to_lowercase()
+    } else {
+        query.to_owned()
+    };
+
+    let limit = match limit {
+        0 => DEFAULT_SEARCH_LIMIT,
+        limit => limit.min(MAX_SEARCH_LIMIT),
+    };
+
+    let profiles = db
+        .write(move |conn| users::search(conn, &query, limit.into()))
+        .await
+        .map_err(ProfileError::DbError)?;
+
+    Ok(profiles
+        .into_iter()
+        .map(|profile| redact(caller, profile))
+        .collect())
+}
+
+/// E-mail addresses are only shown to their owners.
+fn redact(caller: UserId, mut profile: Profile) -> Profile {
+    if *profile.id != *caller {
+        profile.email = None;
+    }
+    profile
+}
+
+fn validate_display_name(name: String) -> Result<String, ProfileError> {
+    let name = name.trim();
+
+    if (1..=MAX_DISPLAY_NAME_LEN).contains(&name.chars().count())
+        && !name.chars().any(char::is_control)
+    {
+        Ok(name.to_owned())
+    } else {
+        Err(ProfileError::InvalidDisplayName)
+    }
+}
+
+pub(super) fn validate_email(email: String) -> Result<String, ProfileError> {
+    let email = email.trim().to_lowercase();
+
+    let valid = email.len() <= MAX_EMAIL_LEN
+        && !email.contains(char::is_whitespace)
+        && email.split_once('@').is_some_and(|(user, domain)| {
+            !user.is_empty()
+                && !domain.contains('@')
+                && domain.split('.').count() > 1
+                && domain.split('.').all(|label| !label.is_empty())
+        });
+
+    if valid {
+        Ok(email)
+    } else {
+        Err(ProfileError::InvalidEmail)
+    }
+}
+
+/// Accepts a language subtag optionally followed by a region, e.g. `pt`, `pt-BR` or `es-419`.
+fn validate_locale(locale: String) -> Result<String, ProfileError> {
+    let (language, region) = match locale.split_once('-') {
+        Some((language, region)) => (language, Some(region)),
+        None => (locale.as_str(), None),
+    };
+
+    let language_ok =
+        (2..=3).contains(&language.len()) && language.bytes().all(|b| b.is_ascii_lowercase());
+    let region_ok = region.is_none_or(|region| {
+        (region.len() == 2 && region.bytes().all(|b| b.is_ascii_uppercase()))
+            || (region.len() == 3 && region.bytes().all(|b| b.is_ascii_digit()))
+    });
+
+    if language_ok && region_ok {
+        Ok(locale)
+    } else {
+        Err(ProfileError::InvalidLocale)
+    }
+}
+
+/// Stores the canonical IANA name, also when given a Windows zone name.
+fn validate_timezone(name: String) -> Result<String, ProfileError> {
+    timezones::get_by_name(name.trim())
+        .map(|tz| tz.name().to_owned())
+        .ok_or(ProfileError::InvalidTimezone)
+}
+
+fn validate_avatar_url(url: String) -> Result<String, ProfileError> {
+    let url = url.trim();
+
+    if url.len() <= MAX_AVATAR_URL_LEN
+        && url.len() > "https://".len()
+        && url.starts_with("https://")
+        && !url.contains(char::is_whitespace)
+    {
+        Ok(url.to_owned())
+    } else {
+        Err(ProfileError::InvalidAvatarUrl)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::features::user;
+
+    #[test]
+    fn validates_fields() {
+        assert_eq!(
+            validate_email(" Fulano@Example.COM ".to_owned()).unwrap(),
+            "fulano@example.com"
+        );
+        assert!(validate_email("fulano@example".to_owned()).is_err());
+        assert!(validate_email("fulano@@example.com".to_owned()).is_err());
+        assert!(validate_email("@example.com".to_owned()).is_err());
+
+        assert!(validate_locale("pt-BR".to_owned()).is_ok());
+        assert!(validate_locale("es-419".to_owned()).is_ok());
+        assert!(validate_locale("en".to_owned()).is_ok());
+        assert!(validate_locale("pt_BR".to_owned()).is_err());
+        assert!(validate_locale("PT-br".to_owned()).is_err());
+
+        assert_eq!(
+            validate_timezone("America/Sao_Paulo".to_owned()).unwrap(),
+            "America/Sao_Paulo"
+        );
+        assert!(validate_timezone("Mars/Olympus_Mons".to_owned()).is_err());
+
+        assert!(validate_display_name("  ".to_owned()).is_err());
+        assert!(validate_display_name("a\nb".to_owned()).is_err());
+        assert_eq!(
+            validate_display_name(" Fulano ".to_owned()).unwrap(),
+            "Fulano"
+        );
+
+        assert!(validate_avatar_url("http://example.com/a.png".to_owned()).is_err());
+        assert!(validate_avatar_url("https://example.com/a.png".to_owned()).is_ok());
+    }
+
+    #[tokio::test]
+    async fn emails_are_only_visible_to_their_owner() {
+        let db = db::test::db();
+        let u0 = user::create(&db).await.unwrap();
+        let u1 = user::create(&db).await.unwrap();
+
+        update(
+            &db,
+            u0,
+            UpdateProfileParams {
+                display_name: Some("Fulano".to_owned()),
+                email: Some("Fulano@Example.com".to_owned()),
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+
+        let own = get(&db, u0, *u0).await.unwrap();
+        assert_eq!(own.email.as_deref(), Some("fulano@example.com"));
+
+        let other = get(&db, u1, *u0).await.unwrap();
+        assert_eq!(other.display_name.as_deref(), Some("Fulano"));
+        assert!(other.email.is_none());
+
+        let found = search(&db, u1, "FULANO@example.com", 0).await.unwrap();
+        assert_eq!(found.len(), 1);
+        assert!(found[0].email.is_none());
+
+        let res = update(
+            &db,
+            u1,
+            UpdateProfileParams {
+                email: Some("fulano@example.com".to_owned()),
+                ..Default::default()
+            },
+        )
+        .await;
+        assert!(matches!(res, Err(ProfileError::EmailTaken)));
+    }
+}
@@ -0,0 +1,318 @@
+use db::{
+    queries::{
+        sessions::{self, Session},
+        users,
+    },
+    types::{SessionId, UserId},
+};
+use time::OffsetDateTime;
+
+use crate::{
+    auth::{self, Caller, Tokens, ACCESS_TOKEN_TTL, REFRESH_TOKEN_TTL},
+    features::profile,
+};
+
+const MIN_PASSWORD_LEN: usize = 8;
+const MAX_PASSWORD_LEN: usize = 128;
+
+#[derive(Debug, thiserror::Error)]
+pub enum SessionError {
+    #[error("Invalid e-mail address")]
+    InvalidEmail,
+    #[error("Password must have between {MIN_PASSWORD_LEN} and {MAX_PASSWORD_LEN} characters")]
+    InvalidPassword,
+    #[error("E-mail address already in use")]
+    EmailTaken,
+    #[error("Invalid e-mail or password")]
+    InvalidCredentials,
+    #[error("Invalid or expired refresh token")]
+    InvalidRefreshToken,
+    #[error("Session not found")]
+    NotFound,
+    #[error("Database error: {0:?}")]
+    DbError(db::Error),
+}
+
+pub struct IssuedSession {
+    pub user_id: UserId,
+    pub session_id: SessionId,
+    pub access_token: String,
+    pub access_token_expires_at: OffsetDateTime,
+    pub refresh_token: String,
+    pub refresh_token_expires_at: OffsetDateTime,
+}
+
+pub struct CredentialsParams {
+    pub email: String,
+    pub password: String,
+    pub device: Option<String>,
+}
+
+pub async fn register(
+    db: &db::Db,
+    tokens: &Tokens,
+    CredentialsParams {
+        email,
+        password,
+        device,
+    }: CredentialsParams,
+) -> Result<(UserId, IssuedSession), SessionError> {
+    let (email, password_hash) = new_credentials(email, password).await?;
+    let (refresh_token, refresh_token_hash) = auth::new_refresh_token();
+    let now = OffsetDateTime::now_utc();
+
+    let (user_id, session) = db
+        .write(move |conn| {
+            let user_id = users::create(conn, now)?;
+
+            users::update_profile(
+                conn,
+                *user_id,
+                &users::UpdateProfileParams {
+                    email: Some(&email),
+                    ..Default::default()
+                },
+            )?;
+            users::set_password_hash(conn, *user_id, &password_hash)?;
+
+            let params = session_params(user_id, &refresh_token_hash, device.as_deref(), now);
+            Ok((user_id, sessions::create(conn, &params)?))
+        })
+        .await
+        .map_err(email_taken)?;
+
+    Ok((user_id, issue(tokens, &session, refresh_token)))
+}
+
+pub async fn login(
+    db: &db::Db,
+    tokens: &Tokens,
+    CredentialsParams {
+        email,
+        password,
+        device,
+    }: CredentialsParams,
+) -> Result<IssuedSession, SessionError> {
+    let email = email.trim().to_lowercase();
+
+    let credentials = db
+        .write(move |conn| users::find_credentials(conn, &email))
+        .await
+        .map_err(SessionError::DbError)?;
+
+    let (user_id, password_hash) = match credentials {
+        Some((user_id, password_hash)) => (Some(user_id), password_hash),
+        None => (None, None),
+    };
+
+    let verified = tokio::task::spawn_blocking(move || {
+        auth::verify_password(password_hash.as_deref(), &password)
+    })
+    .await
+    .expect("Password verification panicked");
+
+    let user_id = user_id
+        .filter(|_| verified)
+        .ok_or(SessionError::InvalidCredentials)?;
+
+    let (refresh_token, refresh_token_hash) = auth::new_refresh_token();
+    let now = OffsetDateTime::now_utc();
+
+    let session = db
+        .write(move |conn| {
+            let params = session_params(user_id, &refresh_token_hash, device.as_deref(), now);
+            sessions::create(conn, &params)
+        })
+        .await
+        .map_err(SessionError::DbError)?;
+
+    Ok(issue(tokens, &session, refresh_token))
+}
+
+/// Exchanges a refresh token for a new access token and a new refresh token. Presenting a
+/// refresh token that was already rotated revokes the session, since either the client or an
+/// attacker holds a stolen copy.
+pub async fn refresh(
+    db: &db::Db,
+    tokens: &Tokens,
+    refresh_token: &str,
+) -> Result<IssuedSession, SessionError> {
+    let old_hash = auth::hash_refresh_token(refresh_token);
+    let (refresh_token, new_hash) = auth::new_refresh_token();
+    let now = OffsetDateTime::now_utc();
+
+    let session = db
+        .write(move |conn| {
+            let Some(session) = sessions::find_by_refresh_token_hash(conn, &old_hash)? else {
+                return Ok(None);
+            };
+
+            if session.revoked_at.is_some() || session.expires_at <= now {
+                return Ok(None);
+            }
+
+            if session.refresh_token_hash != old_hash {
+                sessions::revoke(conn, *session.id, session.user_id, now)?;
+                return Ok(None);
+            }
+
+            sessions::rotate(
+                conn,
+                &sessions::RotateParams {
+                    id: *session.id,
+                    old_hash: &old_hash,
+                    new_hash: &new_hash,
+                    refreshed_at: now,
+                    expires_at: now + REFRESH_TOKEN_TTL,
+                },
+            )
+        })
+        .await
+        .map_err(SessionError::DbError)?
+        .ok_or(SessionError::InvalidRefreshToken)?;
+
+    Ok(issue(tokens, &session, refresh_token))
+}
+
+pub async fn revoke(
+    db: &db::Db,
+    caller: UserId,
+    session_id: i32,
+) -> Result<SessionId, SessionError> {
+    db.write(move |conn| sessions::revoke(conn, session_id, *caller, OffsetDateTime::now_utc()))
+        .await
+        .map_err(SessionError::DbError)?
+        .ok_or(SessionError::NotFound)
+}
+
+pub async fn list(db: &db::Db, caller: UserId) -> Result<Vec<Session>, SessionError> {
+    db.write(move |conn| sessions::list_active(conn, *caller, OffsetDateTime::now_utc()))
+        .await
+        .map_err(SessionError::DbError)
+}
+
+/// Validates new credentials, returning the normalised e-mail and the password hash.
+async fn new_credentials(
+    email: String,
+    password: String,
+) -> Result<(String, String), SessionError> {
+    let email = profile::validate_email(email).map_err(|_| SessionError::InvalidEmail)?;
+
+    if !(MIN_PASSWORD_LEN..=MAX_PASSWORD_LEN).contains(&password.chars().count()) {
+        return Err(SessionError::InvalidPassword);
+    }
+
+    let password_hash = tokio::task::spawn_blocking(move || auth::hash_password(&password))
+        .await
+        .expect("Password hashing panicked");
+
+    Ok((email, password_hash))
+}
+
+fn email_taken(e: db::Error) -> SessionError {
+    match e {
+        db::Error::DatabaseError(db::DatabaseErrorKind::UniqueViolation, _) => {
+            SessionError::EmailTaken
+        }
+        e => SessionError::DbError(e),
+    }
+}
+
+fn session_params<'a>(
+    user_id: UserId,
+    refresh_token_hash: &'a str,
+    device: Option<&'a str>,
+    now: OffsetDateTime,
+) -> sessions::CreateParams<'a> {
+    sessions::CreateParams {
+        user_id: *user_id,
+        refresh_token_hash,
+        device,
+        created_at: now,
+        expires_at: now + REFRESH_TOKEN_TTL,
+    }
+}
+
+fn issue(tokens: &Tokens, session: &Session, refresh_token: String) -> IssuedSession {
+    let caller = Caller {
+        user_id: UserId::from(session.user_id),
+        session_id: session.id,
+    };
+
+    IssuedSession {
+        user_id: caller.user_id,
+        session_id: session.id,
+        access_token: tokens.issue(caller, session.refreshed_at),
+        access_token_expires_at: session.refreshed_at + ACCESS_TOKEN_TTL,
+        refresh_token,
+        refresh_token_expires_at: session.expires_at,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn credentials(email: &str, password: &str) -> CredentialsParams {
+        CredentialsParams {
+            email: email.to_owned(),
+            password: password.to_owned(),
+            device: Some("test".to_owned()),
+        }
+    }
+
+    #[tokio::test]
+    async fn login_and_rotate_refresh_tokens() {
+        let db = db::test::db();
+        let tokens = Tokens::new("secret");
+
+        let (user_id, first) = register(&db, &tokens, credentials("a@example.com", "password"))
+            .await
+            .unwrap();
+        assert_eq!(
+            *tokens.verify(&first.access_token).unwrap().user_id,
+            *user_id
+        );
+
+        let res = register(&db, &tokens, credentials("A@example.com", "password")).await;
+        assert!(matches!(res, Err(SessionError::EmailTaken)));
+
+        let res = login(&db, &tokens, credentials("a@example.com", "wrong")).await;
+        assert!(matches!(res, Err(SessionError::InvalidCredentials)));
+        let res = login(&db, &tokens, credentials("b@example.com", "password")).await;
+        assert!(matches!(res, Err(SessionError::InvalidCredentials)));
+
+        let second = login(&db, &tokens, credentials("A@Example.com", "password"))
+            .await
+            .unwrap();
+        assert_eq!(*second.user_id, *user_id);
+        assert_eq!(list(&db, user_id).await.unwrap().len(), 2);
+
+        let rotated = refresh(&db, &tokens, &second.refresh_token).await.unwrap();
+        assert_eq!(*rotated.session_id, *second.session_id);
+        assert_ne!(rotated.refresh_token, second.refresh_token);
+
+        // Replaying the rotated token revokes the whole session.
+        let res = refresh(&db, &tokens, &second.refresh_token).await;
+        assert!(matches!(res, Err(SessionError::InvalidRefreshToken)));
+        let res = refresh(&db, &tokens, &rotated.refresh_token).await;
+        assert!(matches!(res, Err(SessionError::InvalidRefreshToken)));
+
+        revoke(&db, user_id, *first.session_id).await.unwrap();
+        assert!(list(&db, user_id).await.unwrap().is_empty());
+        let res = refresh(&db, &tokens, &first.refresh_token).await;
+        assert!(matches!(res, Err(SessionError::InvalidRefreshToken)));
+    }
+
+    #[tokio::test]
+    async fn validates_credentials() {
+        let db = db::test::db();
+        let tokens = Tokens::new("secret");
+
+        let res = register(&db, &tokens, credentials("a@example", "password")).await;
+        assert!(matches!(res, Err(SessionError::InvalidEmail)));
+
+        let res = register(&db, &tokens, credentials("a@example.com", "short")).await;
+        assert!(matches!(res, Err(SessionError::InvalidPassword)));
+    }
+}
@@ -0,0 +1,809 @@
+use std::sync::Arc;
+
+use db::{
+    enums::{SettlementInvoiceStatus, UserPaymentMethod, UserPaymentStatus},
+    queries::{
+        balances,
+        settlement_invoices::{self, SettlementInvoice},
+        user_payments, users,
+    },
+    types::UserId,
+};
+use time::{Duration, OffsetDateTime};
+
+use super::audit;
+use crate::{
+    bitcoin::{self, BitcoinError},
+    lightning::{CreateInvoiceParams, InvoiceStatus, LightningBackend, LightningError},
+    pix::{self, PixError},
+    policy::{self, Action, Denied, Entry},
+};
+
+const SATS_PER_BTC: u128 = 100_000_000;
+const MSATS_PER_BTC: u128 = 100_000_000_000;
+const INVOICE_EXPIRY: Duration = Duration::hours(1);
+
+#[derive(Debug, thiserror::Error)]
+pub enum SettlementError {
+    #[error("Nothing to settle")]
+    NothingToSettle,
+    #[error("Invalid exchange rate")]
+    InvalidRate,
+    #[error("Settlement invoice not found")]
+    NotFound,
+    #[error("A pending invoice is for a different amount")]
+    PendingInvoiceMismatch,
+    #[error("{0}")]
+    Forbidden(Denied),
+    #[error("User not found")]
+    UserNotFound,
+    #[error("User has no PIX account")]
+    NoPixAccount,
+    #[error("PIX error: {0}")]
+    PixError(PixError),
+    #[error("User has no bitcoin address")]
+    NoBitcoinAddress,
+    #[error("Bitcoin error: {0}")]
+    BitcoinError(BitcoinError),
+    #[error("Lightning error: {0}")]
+    LightningError(LightningError),
+    #[error("Database error: {0:?}")]
+    DbError(db::Error),
+}
+
+pub fn to_msats(amount_cents: i64, cents_per_btc: u64) -> Option<i64> {
+    convert(amount_cents, cents_per_btc, MSATS_PER_BTC)
+}
+
+pub fn to_sats(amount_cents: i64, cents_per_btc: u64) -> Option<i64> {
+    convert(amount_cents, cents_per_btc, SATS_PER_BTC)
+}
+
+fn convert(amount_cents: i64, cents_per_btc: u64, units_per_btc: u128) -> Option<i64> {
+    if amount_cents <= 0 || cents_per_btc == 0 {
+        return None;
+    }
+
+    let units = amount_cents as u128 * units_per_btc / u128::from(cents_per_btc);
+    i64::try_from(units).ok().filter(|units| *units > 0)
+}
+
+pub struct RequestInvoiceParams {
+    pub payee_user_id: i32,
+    pub cents_per_btc: u64,
+}
+
+/// Returns the pending invoice from the caller to the payee, if there is one for the amount
+/// owed, rather than letting them pay twice. A pending invoice for another amount has to expire
+/// first.
+pub async fn request_invoice(
+    db: &db::Db,
+    lightning: &dyn LightningBackend,
+    caller: UserId,
+    RequestInvoiceParams {
+        payee_user_id,
+        cents_per_btc,
+    }: RequestInvoiceParams,
+) -> Result<SettlementInvoice, SettlementError> {
+    authorize_request(caller, payee_user_id)?;
+
+    let payer_user_id = *caller;
+
+    let pending = db
+        .write(move |conn| settlement_invoices::find_pending(conn, payer_user_id, payee_user_id))
+        .await
+        .map_err(SettlementError::DbError)?;
+    // Paid ones lower the balance, and neither paid nor expired ones block a new invoice.
+    let pending = match pending {
+        Some(invoice) => Some(sync(db, lightning, invoice).await?)
+            .filter(|invoice| invoice.status == SettlementInvoiceStatus::Pending),
+        None => None,
+    };
+
+    let now = OffsetDateTime::now_utc();
+    let amount_cents = db
+        .write(move |conn| balances::owed(conn, payer_user_id, payee_user_id, now, true))
+        .await
+        .map_err(SettlementError::DbError)?;
+
+    if let Some(invoice) = pending {
+        return reuse(invoice, amount_cents);
+    }
+    if amount_cents <= 0 {
+        return Err(SettlementError::NothingToSettle);
+    }
+
+    let amount_msats = to_msats(amount_cents, cents_per_btc).ok_or(SettlementError::InvalidRate)?;
+
+    let invoice = lightning
+        .create_invoice(CreateInvoiceParams {
+            amount_msats: amount_msats as u64,
+            description: format!(
+                "Splitwiser settlement from user {payer_user_id} to user {payee_user_id}"
+            ),
+            expiry: INVOICE_EXPIRY,
+        })
+        .await
+        .map_err(SettlementError::LightningError)?;
+
+    let created = db
+        .write(move |conn| {
+            settlement_invoices::create(
+                conn,
+                &settlement_invoices::CreateParams {
+                    payer_user_id,
+                    payee_user_id,
+                    amount_cents,
+                    amount_msats,
+                    payment_hash: &invoice.payment_hash,
+                    payment_request: &invoice.payment_request,
+                    expires_at: invoice.expires_at,
+                    created_at: now,
+                },
+            )
+        })
+        .await;
+
+    match created {
+        // A concurrent request stored its invoice first. This one is never handed out, so it
+        // expires unpaid.
+        Err(db::Error::DatabaseError(db::DatabaseErrorKind::UniqueViolation, info))
+            if info.constraint_name() == Some(settlement_invoices::ONE_PENDING) =>
+        {
+            let pending = db
+                .write(move |conn| {
+                    settlement_invoices::find_pending(conn, payer_user_id, payee_user_id)
+                })
+                .await
+                .map_err(SettlementError::DbError)?
+                .ok_or(SettlementError::NotFound)?;
+            reuse(pending, amount_cents)
+        }
+        created => created.map_err(SettlementError::DbError),
+    }
+}
+
+fn reuse(
+    pending: SettlementInvoice,
+    amount_cents: i64,
+) -> Result<SettlementInvoice, SettlementError> {
+    if pending.amount_cents == amount_cents {
+        Ok(pending)
+    } else {
+        Err(SettlementError::PendingInvoiceMismatch)
+    }
+}
+
+pub async fn get_invoice(
+    db: &db::Db,
+    lightning: &dyn LightningBackend,
+    caller: UserId,
+    id: i32,
+) -> Result<SettlementInvoice, SettlementError> {
+    let invoice = db
+        .write(move |conn| settlement_invoices::find_by_id(conn, id))
+        .await
+        .map_err(SettlementError::DbError)?
+        .ok_or(SettlementError::NotFound)?;
+
+    policy::authorize(caller, Action::View, &Entry::from(&invoice))
+        .map_err(SettlementError::Forbidden)?;
+
+    sync(db, lightning, invoice).await
+}
+
+/// An invoice that fails to sync is logged and retried on the next pass, so it cannot hold up the
+/// ones after it.
+pub async fn sync_pending(
+    db: &db::Db,
+    lightning: &dyn LightningBackend,
+) -> Result<(), SettlementError> {
+    let pending = db
+        .write(settlement_invoices::list_pending)
+        .await
+        .map_err(SettlementError::DbError)?;
+
+    for invoice in pending {
+        let id = *invoice.id;
+
+        if let Err(error) = sync(db, lightning, invoice).await {
+            tracing::warn!(invoice_id = id, %error, "Failed to sync settlement invoice");
+        }
+    }
+
+    Ok(())
+}
+
+pub async fn watch(db: db::Db, lightning: Arc<dyn LightningBackend>, period: std::time::Duration) {
+    let mut interval = tokio::time::interval(period);
+
+    loop {
+        interval.tick().await;
+
+        if let Err(error) = sync_pending(&db, &*lightning).await {
+            tracing::warn!(%error, "Failed to list pending settlement invoices");
+        }
+    }
+}
+
+pub struct SetPixAccountParams {
+    pub key: String,
+    pub merchant_name: String,
+    pub merchant_city: String,
+}
+
+pub async fn set_pix_account(
+    db: &db::Db,
+    caller: UserId,
+    SetPixAccountParams {
+        key,
+        merchant_name,
+        merchant_city,
+    }: SetPixAccountParams,
+) -> Result<UserId, SettlementError> {
+    pix::validate_key(&key).map_err(SettlementError::PixError)?;
+    pix::validate_merchant(&merchant_name, &merchant_city).map_err(SettlementError::PixError)?;
+
+    let account = users::PixAccount {
+        key,
+        merchant_name,
+        merchant_city,
+    };
+
+    db.write(move |conn| users::set_pix_account(conn, *caller, &account))
+        .await
+        .map_err(SettlementError::DbError)?
+        .ok_or(SettlementError::UserNotFound)
+}
+
+pub struct PixPayloadParams {
+    pub payee_user_id: i32,
+    pub txid: Option<String>,
+    pub location: Option<String>,
+}
+
+pub struct PixPayload {
+    pub payload: String,
+    pub amount_cents: i64,
+}
+
+pub async fn pix_payload(
+    db: &db::Db,
+    caller: UserId,
+    PixPayloadParams {
+        payee_user_id,
+        txid,
+        location,
+    }: PixPayloadParams,
+) -> Result<PixPayload, SettlementError> {
+    authorize_request(caller, payee_user_id)?;
+
+    let (amount_cents, account) = db
+        .write(move |conn| {
+            let owed = balances::owed(
+                conn,
+                *caller,
+                payee_user_id,
+                OffsetDateTime::now_utc(),
+                true,
+            )?;
+
+            Ok((owed, users::find_pix_account(conn, payee_user_id)?))
+        })
+        .await
+        .map_err(SettlementError::DbError)?;
+
+    if amount_cents <= 0 {
+        return Err(SettlementError::NothingToSettle);
+    }
+
+    let account = account.ok_or(SettlementError::NoPixAccount)?;
+
+    let payload = pix::encode(&pix::Payload {
+        account: match &location {
+            Some(location) => pix::Account::Dynamic { location },
+            None => pix::Account::Static { key: &account.key },
+        },
+        merchant_name: &account.merchant_name,
+        merchant_city: &account.merchant_city,
+        amount_cents: Some(amount_cents),
+        txid: txid.as_deref(),
+    })
+    .map_err(SettlementError::PixError)?;
+
+    Ok(PixPayload {
+        payload,
+        amount_cents,
+    })
+}
+
+/// Returns the address as stored, normalised to lowercase for bech32.
+pub async fn set_bitcoin_address(
+    db: &db::Db,
+    caller: UserId,
+    address: &str,
+) -> Result<String, SettlementError> {
+    let address = bitcoin::validate_address(address).map_err(SettlementError::BitcoinError)?;
+
+    db.write(move |conn| {
+        let stored = users::set_bitcoin_address(conn, *caller, &address)?;
+        Ok(stored.map(|_| address.clone()))
+    })
+    .await
+    .map_err(SettlementError::DbError)?
+    .ok_or(SettlementError::UserNotFound)
+}
+
+pub struct BitcoinPaymentUriParams {
+    pub payee_user_id: i32,
+    pub cents_per_btc: u64,
+}
+
+pub struct BitcoinPaymentUri {
+    pub uri: String,
+    pub amount_cents: i64,
+    pub amount_sats: i64,
+}
+
+pub async fn bitcoin_payment_uri(
+    db: &db::Db,
+    caller: UserId,
+    BitcoinPaymentUriParams {
+        payee_user_id,
+        cents_per_btc,
+    }: BitcoinPaymentUriParams,
+) -> Result<BitcoinPaymentUri, SettlementError> {
+    authorize_request(caller, payee_user_id)?;
+
+    let (amount_cents, address) = db
+        .write(move |conn| {
+            let owed = balances::owed(
+                conn,
+                *caller,
+                payee_user_id,
+                OffsetDateTime::now_utc(),
+                true,
+            )?;
+
+            Ok((owed, users::find_bitcoin_address(conn, payee_user_id)?))
+        })
+        .await
+        .map_err(SettlementError::DbError)?;
+
+    if amount_cents <= 0 {
+        return Err(SettlementError::NothingToSettle);
+    }
+
+    let address = address.ok_or(SettlementError::NoBitcoinAddress)?;
+    let amount_sats = to_sats(amount_cents, cents_per_btc).ok_or(SettlementError::InvalidRate)?;
+
+    Ok(BitcoinPaymentUri {
+        uri: bitcoin::payment_uri(&address, amount_sats, Some("Splitwiser settlement")),
+        amount_cents,
+        amount_sats,
+    })
+}
+
+/// Every way of settling up is a settlement the caller pays.
+fn authorize_request(caller: UserId, payee_user_id: i32) -> Result<(), SettlementError> {
+    policy::authorize(
+        caller,
+        Action::Create,
+        &Entry::SettlementInvoice {
+            payer_user_id: *caller,
+            payee_user_id,
+        },
+    )
+    .map_err(SettlementError::Forbidden)
+}
+
+async fn sync(
+    db: &db::Db,
+    lightning: &dyn LightningBackend,
+    invoice: SettlementInvoice,
+) -> Result<SettlementInvoice, SettlementError> {
+    if invoice.status != SettlementInvoiceStatus::Pending {
+        return Ok(invoice);
+    }
+
+    let id = *invoice.id;
+
+    let synced = match lightning
+        .invoice_status(&invoice.payment_hash)
+        .await
+        .map_err(SettlementError::LightningError)?
+    {
+        InvoiceStatus::Pending => return Ok(invoice),
+        InvoiceStatus::Paid {
+            settled_at,
+            preimage,
+        } => {
+            db.write::<_, db::Error, _>(move |conn| {
+                match settlement_invoices::find_by_id(conn, id)? {
+                    Some(invoice) if invoice.status == SettlementInvoiceStatus::Pending => {}
+                    invoice => return Ok(invoice),
+                }
+
+                let user_payment_id = user_payments::create(
+                    conn,
+                    &user_payments::CreateParams {
+                        created_by: invoice.payer_user_id,
+                        amount_cents: invoice.amount_cents,
+                        payee_user_id: invoice.payee_user_id,
+                        payer_user_id: invoice.payer_user_id,
+                        payed_at: settled_at,
+                        status: UserPaymentStatus::Confirmed,
+                        method: UserPaymentMethod::Lightning,
+                        proof: Some(&preimage),
+                        created_at: OffsetDateTime::now_utc(),
+                    },
+                )?;
+
+                // Recorded as the payer's, who requested the invoice.
+                let payment = user_payments::find_by_id(conn, *user_payment_id)?
+                    .ok_or(db::Error::NotFound)?;
+                audit::created(
+                    conn,
+                    UserId::from(invoice.payer_user_id),
+                    "RequestSettlementInvoice",
+                    &payment,
+                )?;
+
+                settlement_invoices::mark_paid(conn, id, user_payment_id, settled_at)
+            })
+            .await
+        }
+        InvoiceStatus::Expired => {
+            db.write::<_, db::Error, _>(move |conn| {
+                match settlement_invoices::mark_expired(conn, id)? {
+                    Some(invoice) => Ok(Some(invoice)),
+                    None => settlement_invoices::find_by_id(conn, id),
+                }
+            })
+            .await
+        }
+    };
+
+    synced
+        .map_err(SettlementError::DbError)?
+        .ok_or(SettlementError::NotFound)
+}
+
+#[cfg(test)]
+mod test {
+    use db::{
+        enums::{AuditEntityType, UserExpensesChargeMethod},
+        queries::balances,
+    };
+    use time::OffsetDateTime;
+
+    use super::*;
+    use crate::{
+        features::user::{self, CreateExpenseParams},
+        lightning::fake::FakeBackend,
+    };
+
+    async fn setup(db: &db::Db) -> (UserId, UserId) {
+        let u0 = user::create(db).await.unwrap();
+        let u1 = user::create(db).await.unwrap();
+
+        user::create_expense(
+            db,
+            u0,
+            CreateExpenseParams {
+                amount_cents: 1000,
+                begin_charging_at: OffsetDateTime::now_utc().unix_timestamp() - 60,
+                charged_user_id: *u1,
+                chargee_user_id: *u0,
+                charge_method: UserExpensesChargeMethod::Full,
+                description: None,
+                installments: 1,
+            },
+        )
+        .await
+        .unwrap();
+
+        (u0, u1)
+    }
+
+    #[test]
+    fn to_msats_converts_with_rate() {
+        // 1 BTC = R$ 100.000,00
+        assert_eq!(to_msats(1000, 10_000_000), Some(10_000_000));
+        assert_eq!(to_msats(1000, 0), None);
+        assert_eq!(to_msats(0, 10_000_000), None);
+        assert_eq!(to_msats(1, u64::MAX), None);
+        assert_eq!(to_sats(1000, 10_000_000), Some(10_000));
+    }
+
+    #[tokio::test]
+    async fn paid_invoice_records_payment() {
+        let db = db::test::db();
+        let lightning = FakeBackend::default();
+        let (u0, u1) = setup(&db).await;
+
+        let invoice = request_invoice(
+            &db,
+            &lightning,
+            u1,
+            RequestInvoiceParams {
+                payee_user_id: *u0,
+                cents_per_btc: 10_000_000,
+            },
+        )
+        .await
+        .unwrap();
+        assert_eq!(invoice.amount_cents, 1000);
+        assert_eq!(invoice.amount_msats, 10_000_000);
+
+        let invoice = get_invoice(&db, &lightning, u1, *invoice.id).await.unwrap();
+        assert_eq!(invoice.status, SettlementInvoiceStatus::Pending);
+
+        lightning
+            .settle(&invoice.payment_hash, OffsetDateTime::now_utc())
+            .unwrap();
+        sync_pending(&db, &lightning).await.unwrap();
+
+        let invoice = get_invoice(&db, &lightning, u1, *invoice.id).await.unwrap();
+        assert_eq!(invoice.status, SettlementInvoiceStatus::Paid);
+
+        let events = audit::list(
+            &db,
+            u0,
+            audit::ListParams {
+                entity: Some((
+                    AuditEntityType::UserPayment,
+                    *invoice.user_payment_id.unwrap(),
+                )),
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].actor_user_id, Some(*u1));
+
+        let owed = db
+            .write(move |conn| balances::owed(conn, *u1, *u0, OffsetDateTime::now_utc(), false))
+            .await
+            .unwrap();
+        assert_eq!(owed, 0);
+    }
+
+    #[tokio::test]
+    async fn repeated_requests_return_the_pending_invoice() {
+        let db = db::test::db();
+        let lightning = FakeBackend::default();
+        let (u0, u1) = setup(&db).await;
+
+        let params = || RequestInvoiceParams {
+            payee_user_id: *u0,
+            cents_per_btc: 10_000_000,
+        };
+
+        let first = request_invoice(&db, &lightning, u1, params())
+            .await
+            .unwrap();
+        let again = request_invoice(&db, &lightning, u1, params())
+            .await
+            .unwrap();
+        assert_eq!(*again.id, *first.id);
+        assert_eq!(again.payment_hash, first.payment_hash);
+
+        user::create_expense(
+            &db,
+            u0,
+            CreateExpenseParams {
+                amount_cents: 500,
+                begin_charging_at: OffsetDateTime::now_utc().unix_timestamp() - 60,
+                charged_user_id: *u1,
+                chargee_user_id: *u0,
+                charge_method: UserExpensesChargeMethod::Full,
+                description: None,
+                installments: 1,
+            },
+        )
+        .await
+        .unwrap();
+        let res = request_invoice(&db, &lightning, u1, params()).await;
+        assert!(matches!(res, Err(SettlementError::PendingInvoiceMismatch)));
+
+        lightning.expire(&first.payment_hash).unwrap();
+        let next = request_invoice(&db, &lightning, u1, params())
+            .await
+            .unwrap();
+        assert_ne!(*next.id, *first.id);
+        assert_eq!(next.amount_cents, 1500);
+    }
+
+    #[tokio::test]
+    async fn invoices_are_hidden_from_other_users() {
+        let db = db::test::db();
+        let lightning = FakeBackend::default();
+        let (u0, u1) = setup(&db).await;
+        let outsider = user::create(&db).await.unwrap();
+
+        let invoice = request_invoice(
+            &db,
+            &lightning,
+            u1,
+            RequestInvoiceParams {
+                payee_user_id: *u0,
+                cents_per_btc: 10_000_000,
+            },
+        )
+        .await
+        .unwrap();
+
+        assert!(get_invoice(&db, &lightning, u0, *invoice.id).await.is_ok());
+
+        let res = get_invoice(&db, &lightning, outsider, *invoice.id).await;
+        assert!(matches!(res, Err(SettlementError::Forbidden(_))));
+    }
+
+    #[tokio::test]
+    async fn sync_pending_skips_invoices_that_fail() {
+        let db = db::test::db();
+        let lightning = FakeBackend::default();
+        let (u0, u1) = setup(&db).await;
+
+        let params = || RequestInvoiceParams {
+            payee_user_id: *u0,
+            cents_per_btc: 10_000_000,
+        };
+
+        // Created on another node, so `lightning` cannot sync it. Skip the first hash, which
+        // `lightning` is about to hand out.
+        let other = FakeBackend::default();
+        other
+            .create_invoice(CreateInvoiceParams {
+                amount_msats: 1,
+                description: String::new(),
+                expiry: INVOICE_EXPIRY,
+            })
+            .await
+            .unwrap();
+        let orphan = request_invoice(&db, &other, u1, params()).await.unwrap();
+        // Another debt, since `lightning` cannot sync the orphan when u1 asks again.
+        let (u2, u3) = setup(&db).await;
+        let invoice = request_invoice(
+            &db,
+            &lightning,
+            u3,
+            RequestInvoiceParams {
+                payee_user_id: *u2,
+                cents_per_btc: 10_000_000,
+            },
+        )
+        .await
+        .unwrap();
+
+        lightning
+            .settle(&invoice.payment_hash, OffsetDateTime::now_utc())
+            .unwrap();
+        sync_pending(&db, &lightning).await.unwrap();
+
+        let id = *invoice.id;
+        let invoice = db
+            .write(move |conn| settlement_invoices::find_by_id(conn, id))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(invoice.status, SettlementInvoiceStatus::Paid);
+
+        let id = *orphan.id;
+        let orphan = db
+            .write(move |conn| settlement_invoices::find_by_id(conn, id))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(orphan.status, SettlementInvoiceStatus::Pending);
+    }
+
+    #[tokio::test]
+    async fn nothing_to_settle() {
+        let db = db::test::db();
+        let lightning = FakeBackend::default();
+        let (u0, u1) = setup(&db).await;
+
+        let res = request_invoice(
+            &db,
+            &lightning,
+            u0,
+            RequestInvoiceParams {
+                payee_user_id: *u1,
+                cents_per_btc: 10_000_000,
+            },
+        )
+        .await;
+
+        assert!(matches!(res, Err(SettlementError::NothingToSettle)));
+    }
+
+    #[tokio::test]
+    async fn pix_payload_charges_outstanding_balance() {
+        let db = db::test::db();
+        let (u0, u1) = setup(&db).await;
+
+        let params = || PixPayloadParams {
+            payee_user_id: *u0,
+            txid: Some("SPLITWISER1".to_owned()),
+            location: None,
+        };
+
+        let res = pix_payload(&db, u1, params()).await;
+        assert!(matches!(res, Err(SettlementError::NoPixAccount)));
+
+        set_pix_account(
+            &db,
+            u0,
+            SetPixAccountParams {
+                key: "fulano@example.com".to_owned(),
+                merchant_name: "Fulano de Tal".to_owned(),
+                merchant_city: "BRASILIA".to_owned(),
+            },
+        )
+        .await
+        .unwrap();
+
+        let payload = pix_payload(&db, u1, params()).await.unwrap();
+        assert_eq!(payload.amount_cents, 1000);
+        assert!(payload.payload.contains("0118fulano@example.com"));
+        assert!(payload.payload.contains("540510.00"));
+    }
+
+    #[tokio::test]
+    async fn bitcoin_payment_uri_charges_outstanding_balance() {
+        let db = db::test::db();
+        let (u0, u1) = setup(&db).await;
+
+        let res = set_bitcoin_address(&db, u0, "bc1qinvalid").await;
+        assert!(matches!(res, Err(SettlementError::BitcoinError(_))));
+
+        set_bitcoin_address(&db, u0, "BC1QW508D6QEJXTDG4Y5R3ZARVARY0C5XW7KV8F3T4")
+            .await
+            .unwrap();
+
+        let uri = bitcoin_payment_uri(
+            &db,
+            u1,
+            BitcoinPaymentUriParams {
+                payee_user_id: *u0,
+                cents_per_btc: 10_000_000,
+            },
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(uri.amount_sats, 10_000);
+        assert_eq!(
+            uri.uri,
+            "bitcoin:bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4?amount=0.0001&label=Splitwiser%20settlement"
+        );
+    }
+
+    #[tokio::test]
+    async fn expired_invoice_records_nothing() {
+        let db = db::test::db();
+        let lightning = FakeBackend::default();
+        let (u0, u1) = setup(&db).await;
+
+        let invoice = request_invoice(
+            &db,
+            &lightning,
+            u1,
+            RequestInvoiceParams {
+                payee_user_id: *u0,
+                cents_per_btc: 10_000_000,
+            },
+        )
+        .await
+        .unwrap();
+
+        lightning.expire(&invoice.payment_hash).unwrap();
+
+        let invoice = get_invoice(&db, &lightning, u1, *invoice.id).await.unwrap();
+        assert_eq!(invoice.status, SettlementInvoiceStatus::Expired);
+        assert!(invoice.user_payment_id.is_none());
+    }
+}
@@ -1,28 +1,49 @@
 use db::{
-    enums::UserExpensesChargeMethod,
-    queries::{user_expense_installments, user_expenses, user_payments, user_revenues},
-    types::{UserExpenseId, UserId, UserPaymentId, UserRevenueId},
+    enums::{UserExpensesChargeMethod, UserPaymentMethod, UserPaymentStatus},
+    queries::{
+        balances, user_expense_installments,
+        user_expenses::{self, UserExpense},
+        user_payments::{self, UserPayment},
+        user_revenues::{self, UserRevenue},
+        users,
+    },
+    types::{UserExpenseId, UserId},
+    TransactionError,
+};
+use time::{Duration, OffsetDateTime, PrimitiveDateTime};
+use time_tz::{OffsetDateTimeExt, OffsetResult, PrimitiveDateTimeExt, Tz};
+
+use crate::{
+    features::{audit, profile},
+    policy::{self, Action, Actor, Denied, Entry},
 };
-use time::{Duration, OffsetDateTime};
 
 #[derive(Debug, thiserror::Error)]
 pub enum UserError {
     #[error("Time conversion error: {0:?}")]
     TimeError(time::error::ComponentRange),
+    #[error("{0}")]
+    Forbidden(Denied),
     #[error("Database error: {0:?}")]
-    DbError(db::Error),
+    DbError(#[from] db::Error),
+}
+
+impl TransactionError for UserError {
+    fn is_serialization_failure(&self) -> bool {
+        matches!(self, Self::DbError(e) if e.is_serialization_failure())
+    }
 }
 
+/// Users sign up through [`super::session::register`]; tests only need bare accounts.
+#[cfg(test)]
 pub async fn create(db: &db::Db) -> Result<UserId, db::Error> {
-    Ok(db
-        .write::<_, db::Error, _>(move |conn| {
-            db::queries::users::create(conn, OffsetDateTime::now_utc())
-        })
-        .await?)
+    db.write::<_, db::Error, _>(move |conn| {
+        db::queries::users::create(conn, OffsetDateTime::now_utc())
+    })
+    .await
 }
 
 pub struct CreateRevenueParams {
-    pub user_id: i32,
     pub amount_cents: i64,
     pub description: Option<String>,
     pub incoming_at: i64,
@@ -30,79 +51,181 @@ pub struct CreateRevenueParams {
 
 pub async fn create_revenue(
     db: &db::Db,
+    caller: UserId,
     CreateRevenueParams {
-        user_id,
         amount_cents,
         description,
         incoming_at,
     }: CreateRevenueParams,
-) -> Result<UserRevenueId, UserError> {
+) -> Result<UserRevenue, UserError> {
     let incoming_at =
         OffsetDateTime::from_unix_timestamp(incoming_at).map_err(UserError::TimeError)?;
 
-    let id = db
-        .write(move |conn| {
-            user_revenues::create(
-                conn,
-                &user_revenues::CreateParams {
-                    user_id,
-                    amount_cents,
-                    description: description.as_deref(),
-                    incoming_at,
-                    created_at: OffsetDateTime::now_utc(),
-                },
-            )
-        })
-        .await
-        .map_err(UserError::DbError)?;
+    db.write::<_, db::Error, _>(move |conn| {
+        let id = user_revenues::create(
+            conn,
+            &user_revenues::CreateParams {
+                user_id: *caller,
+                amount_cents,
+                description: description.as_deref(),
+                incoming_at,
+                created_at: OffsetDateTime::now_utc(),
+            },
+        )?;
+
+        let revenue = user_revenues::find_by_id(conn, *id)?.ok_or(db::Error::NotFound)?;
+        audit::created(conn, caller, "CreateRevenue", &revenue)?;
 
-    Ok(id)
+        Ok(revenue)
+    })
+    .await
+    .map_err(UserError::DbError)
 }
 
 pub struct CreatePaymentParams {
-    pub created_by: i32,
     pub amount_cents: i64,
     pub payee_user_id: i32,
     pub payer_user_id: i32,
     pub payed_at: i64,
+    pub method: UserPaymentMethod,
+    pub proof: Option<String>,
 }
 
 pub async fn create_payment(
     db: &db::Db,
+    caller: UserId,
     CreatePaymentParams {
-        created_by,
         amount_cents,
         payee_user_id,
         payer_user_id,
         payed_at,
+        method,
+        proof,
     }: CreatePaymentParams,
-) -> Result<UserPaymentId, UserError> {
+) -> Result<UserPayment, UserError> {
+    policy::authorize(
+        caller,
+        Action::Create,
+        &Entry::Payment {
+            created_by: *caller,
+            payer_user_id,
+            payee_user_id,
+        },
+    )
+    .map_err(UserError::Forbidden)?;
+
     let payed_at = OffsetDateTime::from_unix_timestamp(payed_at).map_err(UserError::TimeError)?;
 
-    let id = db
-        .write(move |conn| {
-            user_payments::create(
+    // A payee recording a payment they received needs no further confirmation.
+    let status = if *caller == payee_user_id {
+        UserPaymentStatus::Confirmed
+    } else {
+        UserPaymentStatus::Pending
+    };
+
+    db.write::<_, db::Error, _>(move |conn| {
+        let id = user_payments::create(
+            conn,
+            &user_payments::CreateParams {
+                created_by: *caller,
+                amount_cents,
+                payee_user_id,
+                payer_user_id,
+                payed_at,
+                status,
+                method,
+                proof: proof.as_deref(),
+                created_at: OffsetDateTime::now_utc(),
+            },
+        )?;
+
+        let payment = user_payments::find_by_id(conn, *id)?.ok_or(db::Error::NotFound)?;
+        audit::created(conn, caller, "CreatePayment", &payment)?;
+
+        Ok(payment)
+    })
+    .await
+    .map_err(UserError::DbError)
+}
+
+pub struct ReviewPaymentParams {
+    pub payment_id: i32,
+    pub status: UserPaymentStatus,
+}
+
+pub enum ReviewPaymentOutcome {
+    Reviewed(UserPayment),
+    NotFound,
+}
+
+pub async fn review_payment(
+    db: &db::Db,
+    caller: UserId,
+    ReviewPaymentParams { payment_id, status }: ReviewPaymentParams,
+) -> Result<ReviewPaymentOutcome, UserError> {
+    let reviewed = db
+        .write::<_, UserError, _>(move |conn| {
+            let Some(payment) = user_payments::find_by_id(conn, payment_id)? else {
+                return Ok(None);
+            };
+
+            policy::authorize(caller, Action::Confirm, &Entry::from(&payment))
+                .map_err(UserError::Forbidden)?;
+
+            let id = user_payments::review(
                 conn,
-                &user_payments::CreateParams {
-                    created_by,
-                    amount_cents,
-                    payee_user_id,
-                    payer_user_id,
-                    payed_at,
-                    created_at: OffsetDateTime::now_utc(),
-                },
-            )
+                payment_id,
+                *caller,
+                status,
+                OffsetDateTime::now_utc(),
+            )?;
+
+            if id.is_none() {
+                return Ok(None);
+            }
+
+            let reviewed =
+                user_payments::find_by_id(conn, payment_id)?.ok_or(db::Error::NotFound)?;
+            audit::updated(conn, caller, "ConfirmPayment", &payment, &reviewed)?;
+
+            Ok(Some(reviewed))
         })
-        .await
-        .map_err(UserError::DbError)?;
+        .await?;
+
+    Ok(reviewed.map_or(
+        ReviewPaymentOutcome::NotFound,
+        ReviewPaymentOutcome::Reviewed,
+    ))
+}
+
+pub struct BalanceParams {
+    pub other_user_id: i32,
+    pub include_unconfirmed: bool,
+}
 
-    Ok(id)
+pub async fn balance(
+    db: &db::Db,
+    caller: UserId,
+    BalanceParams {
+        other_user_id,
+        include_unconfirmed,
+    }: BalanceParams,
+) -> Result<i64, db::Error> {
+    db.write(move |conn| {
+        balances::owed(
+            conn,
+            *caller,
+            other_user_id,
+            OffsetDateTime::now_utc(),
+            include_unconfirmed,
+        )
+    })
+    .await
 }
 
 pub struct CreateExpenseParams {
     pub amount_cents: i64,
     pub begin_charging_at: i64,
-    pub created_by: i32,
     pub charged_user_id: i32,
     pub chargee_user_id: i32,
     pub charge_method: UserExpensesChargeMethod,
@@ -111,15 +234,15 @@ pub struct CreateExpenseParams {
 }
 
 pub enum CreateExpenseOutcome {
-    Created(UserExpenseId),
+    Created(UserExpense),
 }
 
 pub async fn create_expense(
     db: &db::Db,
+    caller: UserId,
     CreateExpenseParams {
         amount_cents,
         begin_charging_at,
-        created_by,
         charged_user_id,
         chargee_user_id,
         charge_method,
@@ -127,16 +250,27 @@ pub async fn create_expense(
         installments,
     }: CreateExpenseParams,
 ) -> Result<CreateExpenseOutcome, UserError> {
+    policy::authorize(
+        caller,
+        Action::Create,
+        &Entry::Expense {
+            created_by: *caller,
+            charged_user_id,
+            chargee_user_id,
+        },
+    )
+    .map_err(UserError::Forbidden)?;
+
     let created_at = OffsetDateTime::now_utc();
     let begin_charging_at =
         OffsetDateTime::from_unix_timestamp(begin_charging_at).map_err(UserError::TimeError)?;
 
-    let id = db
+    let expense = db
         .write::<_, db::Error, _>(move |conn| {
             let user_expense_id = user_expenses::create(
                 conn,
                 &user_expenses::CreateParams {
-                    created_by,
+                    created_by: *caller,
                     amount_cents,
                     description: description.as_deref(),
                     chargee_user_id,
@@ -147,20 +281,225 @@ pub async fn create_expense(
                 },
             )?;
 
-            let installments: Vec<_> = (0..installments)
-                .map(|i| user_expense_installments::CreateParams {
+            let timezone = users::find_timezone(conn, *caller)?;
+            let timezone = profile::timezone(timezone.as_deref().unwrap_or("UTC"));
+
+            let installments: Vec<_> = installment_dates(begin_charging_at, installments, timezone)
+                .into_iter()
+                .map(|charged_at| user_expense_installments::CreateParams {
                     user_expense_id,
-                    charged_at: begin_charging_at + Duration::weeks(4 * (i as i64)),
+                    charged_at,
                     amount_cents: (amount_cents / installments as i64),
                 })
                 .collect();
 
             user_expense_installments::create(conn, &installments)?;
 
-            Ok(user_expense_id)
+            let expense =
+                user_expenses::find_by_id(conn, *user_expense_id)?.ok_or(db::Error::NotFound)?;
+            audit::created(conn, caller, "CreateExpense", &expense)?;
+
+            Ok(expense)
         })
         .await
         .map_err(UserError::DbError)?;
 
-    Ok(CreateExpenseOutcome::Created(id))
+    crate::metrics::expense_created(amount_cents);
+
+    Ok(CreateExpenseOutcome::Created(expense))
+}
+
+/// Installments fall every four weeks at the same wall-clock time in `timezone`, so daylight
+/// saving transitions do not move them by an hour.
+fn installment_dates(
+    begin: OffsetDateTime,
+    installments: u32,
+    timezone: &Tz,
+) -> Vec<OffsetDateTime> {
+    let local = begin.to_timezone(timezone);
+    let local = PrimitiveDateTime::new(local.date(), local.time());
+
+    (0..installments)
+        .map(|i| {
+            let period = Duration::weeks(4 * i64::from(i));
+
+            match (local + period).assume_timezone(timezone) {
+                OffsetResult::Some(due) | OffsetResult::Ambiguous(due, _) => due,
+                // The wall-clock time was skipped by a transition.
+                OffsetResult::None => begin + period,
+            }
+        })
+        .collect()
+}
+
+pub async fn get_expense(
+    db: &db::Db,
+    caller: UserId,
+    id: i32,
+) -> Result<Option<UserExpense>, UserError> {
+    db.write(move |conn| {
+        let Some(expense) = user_expenses::find_by_id(conn, id)? else {
+            return Ok(None);
+        };
+
+        policy::authorize(actor(conn, caller)?, Action::View, &Entry::from(&expense))
+            .map_err(UserError::Forbidden)?;
+
+        Ok(Some(expense))
+    })
+    .await
+}
+
+pub struct UpdateExpenseParams {
+    pub id: i32,
+    pub description: Option<String>,
+}
+
+pub enum UpdateExpenseOutcome {
+    Updated(UserExpense),
+    NotFound,
+}
+
+/// Only the description can change; the amount and who owes whom are fixed once installments
+/// exist.
+pub async fn update_expense(
+    db: &db::Db,
+    caller: UserId,
+    UpdateExpenseParams { id, description }: UpdateExpenseParams,
+) -> Result<UpdateExpenseOutcome, UserError> {
+    db.write(move |conn| {
+        let Some(expense) = user_expenses::find_by_id(conn, id)? else {
+            return Ok(UpdateExpenseOutcome::NotFound);
+        };
+
+        policy::authorize(actor(conn, caller)?, Action::Edit, &Entry::from(&expense))
+            .map_err(UserError::Forbidden)?;
+
+        let updated = user_expenses::update_description(conn, id, description.as_deref())?
+            .ok_or(db::Error::NotFound)?;
+        audit::updated(conn, caller, "UpdateExpense", &expense, &updated)?;
+
+        Ok(UpdateExpenseOutcome::Updated(updated))
+    })
+    .await
+}
+
+pub enum DeleteExpenseOutcome {
+    Deleted(UserExpenseId),
+    NotFound,
+}
+
+pub async fn delete_expense(
+    db: &db::Db,
+    caller: UserId,
+    id: i32,
+) -> Result<DeleteExpenseOutcome, UserError> {
+    db.write(move |conn| {
+        let Some(expense) = user_expenses::find_by_id(conn, id)? else {
+            return Ok(DeleteExpenseOutcome::NotFound);
+        };
+
+        policy::authorize(actor(conn, caller)?, Action::Delete, &Entry::from(&expense))
+            .map_err(UserError::Forbidden)?;
+
+        let id = user_expenses::force_delete(conn, id)?.ok_or(db::Error::NotFound)?;
+        audit::deleted(conn, caller, "DeleteExpense", &expense)?;
+
+        Ok(DeleteExpenseOutcome::Deleted(id))
+    })
+    .await
+}
+
+/// Expenses are the only entries admins act on, so only their paths look the flag up.
+fn actor(conn: &mut db::PgConnection, caller: UserId) -> Result<Actor, db::Error> {
+    Ok(Actor {
+        user_id: caller,
+        admin: users::is_admin(conn, *caller)?,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use time::macros::datetime;
+
+    use super::*;
+
+    #[test]
+    fn installments_keep_local_time_across_dst() {
+        let new_york = profile::timezone("America/New_York");
+        let dates = installment_dates(datetime!(2026-02-15 17:00 UTC), 3, new_york);
+
+        assert_eq!(
+            dates,
+            [
+                datetime!(2026-02-15 17:00 UTC),
+                datetime!(2026-03-15 16:00 UTC),
+                datetime!(2026-04-12 16:00 UTC),
+            ]
+        );
+
+        let utc = profile::timezone("UTC");
+        let dates = installment_dates(datetime!(2026-02-15 17:00 UTC), 2, utc);
+        assert_eq!(dates[1], datetime!(2026-03-15 17:00 UTC));
+    }
+
+    #[tokio::test]
+    async fn only_creators_and_admins_edit_or_delete_expenses() {
+        let db = db::test::db();
+        let u0 = create(&db).await.unwrap();
+        let u1 = create(&db).await.unwrap();
+        let admin = create(&db).await.unwrap();
+        db.write(move |conn| users::set_admin(conn, *admin, true))
+            .await
+            .unwrap();
+
+        let CreateExpenseOutcome::Created(expense) = create_expense(
+            &db,
+            u0,
+            CreateExpenseParams {
+                amount_cents: 100,
+                begin_charging_at: 0,
+                charged_user_id: *u1,
+                chargee_user_id: *u0,
+                charge_method: UserExpensesChargeMethod::Even,
+                description: None,
+                installments: 1,
+            },
+        )
+        .await
+        .unwrap();
+        let id = *expense.id;
+        let edit = |description: &str| UpdateExpenseParams {
+            id,
+            description: Some(description.to_owned()),
+        };
+
+        assert!(matches!(
+            update_expense(&db, u1, edit("Groceries")).await,
+            Err(UserError::Forbidden(_))
+        ));
+        let Ok(UpdateExpenseOutcome::Updated(updated)) =
+            update_expense(&db, u0, edit("Groceries")).await
+        else {
+            panic!("the creator may edit");
+        };
+        assert_eq!(updated.description.as_deref(), Some("Groceries"));
+        assert!(matches!(
+            update_expense(&db, admin, edit("Rent")).await,
+            Ok(UpdateExpenseOutcome::Updated(_))
+        ));
+
+        assert!(matches!(
+            delete_expense(&db, u1, id).await,
+            Err(UserError::Forbidden(_))
+        ));
+        assert!(matches!(
+            delete_expense(&db, admin, id).await,
+            Ok(DeleteExpenseOutcome::Deleted(_))
+        ));
+        assert!(matches!(
+            update_expense(&db, u0, edit("Rent")).await,
+            Ok(UpdateExpenseOutcome::NotFound)
+        ));
+    }
 }
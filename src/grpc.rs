@@ -1,32 +1,155 @@
-use futures::{Future, TryFutureExt};
+use std::{convert::Infallible, io, num::NonZeroU32, sync::Arc, time::Duration};
 
-use tonic::{Request, Response, Status};
+use futures::{
+    future::{self, Either},
+    Future, Stream, TryFutureExt,
+};
 
-use self::proto::{CreateExpenseRequest, CreatePaymentRequest, CreateRevenueRequest, Id};
+use tokio::{
+    io::{AsyncRead, AsyncWrite},
+    net::{TcpListener, TcpStream},
+    sync::watch,
+};
+use tonic::{
+    codegen::{http, Body},
+    transport::server::Connected,
+    Request, Response, Status,
+};
+use tower::{
+    layer::util::{Identity, Stack},
+    Layer, Service, ServiceBuilder, ServiceExt,
+};
 
+use self::proto::{
+    AuditEvents, AuthTokens, Balance, BitcoinPaymentUri, ConfirmPaymentRequest,
+    CreateExpenseRequest, CreatePaymentRequest, CreateRevenueRequest, CreateUserRequest,
+    CreatedUser, Expense, GetBalanceRequest, GetBitcoinPaymentUriRequest, GetPixPayloadRequest, Id,
+    ListAuditEventsRequest, LoginRequest, PixPayload, QrCode, RefreshSessionRequest,
+    RenderQrCodeRequest, RequestSettlementInvoiceRequest, SearchUsersRequest, Sessions,
+    SetBitcoinAddressRequest, SetPixAccountRequest, SettlementInvoice, UpdateExpenseRequest,
+    UpdateProfileRequest, User, Users,
+};
+use crate::{auth::Tokens, lightning::LightningBackend, tls::Listener};
+
+mod admin;
+mod audit;
+mod auth;
+mod deadline;
+mod gateway;
+mod health;
+mod metrics;
+mod profile;
+mod rate_limit;
+mod reflection;
+mod session;
+mod settlement;
+mod trace;
 mod user;
+mod v1;
+mod web;
 
 pub mod proto {
     tonic::include_proto!("splitwiser");
 }
 
 #[allow(unused)]
+#[derive(Clone)]
 pub struct Server {
     db: db::Db,
     env: crate::env::Env,
+    lightning: Option<Arc<dyn LightningBackend>>,
+    tokens: Tokens,
 }
 
 #[tonic::async_trait]
 impl proto::splitwiser_server::Splitwiser for Server {
-    async fn create_user(&self, _request: Request<()>) -> Result<Response<proto::Id>, Status> {
-        user::create(&self.db).map_ok(Response::new).await
+    async fn create_user(
+        &self,
+        request: Request<CreateUserRequest>,
+    ) -> Result<Response<CreatedUser>, Status> {
+        session::create_user(&self.db, &self.tokens, request.into_inner())
+            .map_ok(Response::new)
+            .await
+    }
+
+    async fn login(&self, request: Request<LoginRequest>) -> Result<Response<AuthTokens>, Status> {
+        session::login(&self.db, &self.tokens, request.into_inner())
+            .map_ok(Response::new)
+            .await
+    }
+
+    async fn refresh_session(
+        &self,
+        request: Request<RefreshSessionRequest>,
+    ) -> Result<Response<AuthTokens>, Status> {
+        session::refresh(&self.db, &self.tokens, request.into_inner())
+            .map_ok(Response::new)
+            .await
+    }
+
+    async fn logout(&self, request: Request<()>) -> Result<Response<()>, Status> {
+        let caller = auth::caller(&request)?;
+        let current = auth::session(&request)?;
+
+        session::logout(&self.db, caller, current)
+            .map_ok(Response::new)
+            .await
+    }
+
+    async fn list_sessions(&self, request: Request<()>) -> Result<Response<Sessions>, Status> {
+        let caller = auth::caller(&request)?;
+        let current = auth::session(&request)?;
+
+        session::list(&self.db, caller, current)
+            .map_ok(Response::new)
+            .await
+    }
+
+    async fn revoke_session(&self, request: Request<Id>) -> Result<Response<Id>, Status> {
+        let caller = auth::caller(&request)?;
+
+        session::revoke(&self.db, caller, request.into_inner().id)
+            .map_ok(Response::new)
+            .await
+    }
+
+    async fn update_profile(
+        &self,
+        request: Request<UpdateProfileRequest>,
+    ) -> Result<Response<User>, Status> {
+        let caller = auth::caller(&request)?;
+
+        profile::update(&self.db, caller, request.into_inner())
+            .map_ok(Response::new)
+            .await
+    }
+
+    async fn get_user(&self, request: Request<Id>) -> Result<Response<User>, Status> {
+        let caller = auth::caller(&request)?;
+
+        profile::get(&self.db, caller, request.into_inner().id)
+            .map_ok(Response::new)
+            .await
+    }
+
+    async fn search_users(
+        &self,
+        request: Request<SearchUsersRequest>,
+    ) -> Result<Response<Users>, Status> {
+        let caller = auth::caller(&request)?;
+
+        profile::search(&self.db, caller, request.into_inner())
+            .map_ok(Response::new)
+            .await
     }
 
     async fn create_revenue(
         &self,
         request: Request<CreateRevenueRequest>,
     ) -> Result<Response<Id>, Status> {
-        user::create_revenue(&self.db, request.into_inner())
+        let caller = auth::caller(&request)?;
+
+        user::create_revenue(&self.db, caller, request.into_inner())
             .map_ok(Response::new)
             .await
     }
@@ -35,7 +158,9 @@ impl proto::splitwiser_server::Splitwiser for Server {
         &self,
         request: Request<CreatePaymentRequest>,
     ) -> Result<Response<Id>, Status> {
-        user::create_payment(&self.db, request.into_inner())
+        let caller = auth::caller(&request)?;
+
+        user::create_payment(&self.db, caller, request.into_inner())
             .map_ok(Response::new)
             .await
     }
@@ -44,23 +169,384 @@ impl proto::splitwiser_server::Splitwiser for Server {
         &self,
         request: Request<CreateExpenseRequest>,
     ) -> Result<Response<Id>, Status> {
-        user::create_expense(&self.db, request.into_inner())
+        let caller = auth::caller(&request)?;
+
+        user::create_expense(&self.db, caller, request.into_inner())
+            .map_ok(Response::new)
+            .await
+    }
+
+    async fn get_expense(&self, request: Request<Id>) -> Result<Response<Expense>, Status> {
+        let caller = auth::caller(&request)?;
+
+        user::get_expense(&self.db, caller, request.into_inner().id)
+            .map_ok(Response::new)
+            .await
+    }
+
+    async fn update_expense(
+        &self,
+        request: Request<UpdateExpenseRequest>,
+    ) -> Result<Response<Expense>, Status> {
+        let caller = auth::caller(&request)?;
+
+        user::update_expense(&self.db, caller, request.into_inner())
+            .map_ok(Response::new)
+            .await
+    }
+
+    async fn delete_expense(&self, request: Request<Id>) -> Result<Response<Id>, Status> {
+        let caller = auth::caller(&request)?;
+
+        user::delete_expense(&self.db, caller, request.into_inner().id)
+            .map_ok(Response::new)
+            .await
+    }
+
+    async fn confirm_payment(
+        &self,
+        request: Request<ConfirmPaymentRequest>,
+    ) -> Result<Response<Id>, Status> {
+        let caller = auth::caller(&request)?;
+
+        user::confirm_payment(&self.db, caller, request.into_inner())
+            .map_ok(Response::new)
+            .await
+    }
+
+    async fn get_balance(
+        &self,
+        request: Request<GetBalanceRequest>,
+    ) -> Result<Response<Balance>, Status> {
+        let caller = auth::caller(&request)?;
+
+        user::balance(&self.db, caller, request.into_inner())
+            .map_ok(Response::new)
+            .await
+    }
+
+    async fn request_settlement_invoice(
+        &self,
+        request: Request<RequestSettlementInvoiceRequest>,
+    ) -> Result<Response<SettlementInvoice>, Status> {
+        let caller = auth::caller(&request)?;
+
+        settlement::request_invoice(
+            &self.db,
+            self.lightning.as_deref(),
+            caller,
+            request.into_inner(),
+        )
+        .map_ok(Response::new)
+        .await
+    }
+
+    async fn get_settlement_invoice(
+        &self,
+        request: Request<Id>,
+    ) -> Result<Response<SettlementInvoice>, Status> {
+        let caller = auth::caller(&request)?;
+
+        settlement::get_invoice(
+            &self.db,
+            self.lightning.as_deref(),
+            caller,
+            request.into_inner().id,
+        )
+        .map_ok(Response::new)
+        .await
+    }
+
+    async fn set_pix_account(
+        &self,
+        request: Request<SetPixAccountRequest>,
+    ) -> Result<Response<Id>, Status> {
+        let caller = auth::caller(&request)?;
+
+        settlement::set_pix_account(&self.db, caller, request.into_inner())
+            .map_ok(Response::new)
+            .await
+    }
+
+    async fn get_pix_payload(
+        &self,
+        request: Request<GetPixPayloadRequest>,
+    ) -> Result<Response<PixPayload>, Status> {
+        let caller = auth::caller(&request)?;
+
+        settlement::pix_payload(&self.db, caller, request.into_inner())
+            .map_ok(Response::new)
+            .await
+    }
+
+    async fn set_bitcoin_address(
+        &self,
+        request: Request<SetBitcoinAddressRequest>,
+    ) -> Result<Response<Id>, Status> {
+        let caller = auth::caller(&request)?;
+
+        settlement::set_bitcoin_address(&self.db, caller, request.into_inner())
             .map_ok(Response::new)
             .await
     }
+
+    async fn get_bitcoin_payment_uri(
+        &self,
+        request: Request<GetBitcoinPaymentUriRequest>,
+    ) -> Result<Response<BitcoinPaymentUri>, Status> {
+        let caller = auth::caller(&request)?;
+
+        settlement::bitcoin_payment_uri(&self.db, caller, request.into_inner())
+            .map_ok(Response::new)
+            .await
+    }
+
+    async fn render_qr_code(
+        &self,
+        request: Request<RenderQrCodeRequest>,
+    ) -> Result<Response<QrCode>, Status> {
+        auth::caller(&request)?;
+
+        settlement::render_qr_code(request.into_inner())
+            .map_ok(Response::new)
+            .await
+    }
+
+    async fn list_audit_events(
+        &self,
+        request: Request<ListAuditEventsRequest>,
+    ) -> Result<Response<AuditEvents>, Status> {
+        let caller = auth::caller(&request)?;
+
+        audit::list(&self.db, caller, request.into_inner())
+            .map_ok(Response::new)
+            .await
+    }
+}
+
+/// Logs `error` against the current request, since `status` hides it from the caller.
+fn logged(status: Status, error: impl std::fmt::Display) -> Status {
+    tracing::error!(code = ?status.code(), %error, "{}", status.message());
+    status
 }
 
+/// Binds the listener, speaking TLS when it is configured.
 pub(super) fn serve(
     deps: &super::Deps,
-) -> impl Future<Output = Result<(), tonic::transport::Error>> {
+) -> std::io::Result<impl Future<Output = Result<(), tonic::transport::Error>>> {
     let socket = deps.env.socket;
 
     let server = Server {
         db: deps.db.clone(),
         env: deps.env.clone(),
+        lightning: deps.lightning.clone(),
+        tokens: Tokens::new(&deps.env.auth_key),
     };
 
-    tonic::transport::Server::builder()
-        .add_service(proto::splitwiser_server::SplitwiserServer::new(server))
-        .serve(socket)
+    let auth = auth::AuthLayer {
+        db: deps.db.clone(),
+        tokens: server.tokens.clone(),
+    };
+
+    let health = health::HealthService {
+        db: deps.db.clone(),
+        shutdown: deps.draining.clone(),
+    };
+
+    let router = tonic::transport::Server::builder()
+        .accept_http1(true)
+        .layer(trace::TraceLayer)
+        .layer(web::layer(&deps.env.grpc_web_origins))
+        .layer(deps.layers.0.clone())
+        .add_service(health::proto::health_server::HealthServer::new(health))
+        .add_optional_service(deps.env.reflection.then(|| {
+            reflection::proto::server_reflection_server::ServerReflectionServer::new(
+                reflection::ReflectionService::new(),
+            )
+        }))
+        .add_service(auth.layer(proto::splitwiser_server::SplitwiserServer::new(
+            server.clone(),
+        )))
+        .add_service(auth.layer(v1::proto::splitwiser_server::SplitwiserServer::new(server)));
+
+    let shutdown = shutdown_signal(deps.shutdown.clone());
+
+    Ok(match deps.tls.clone() {
+        Some(tls) => Either::Left(router.serve_with_incoming_shutdown(
+            crate::tls::incoming(tls, Listener::Public, crate::tls::bind(socket)?),
+            shutdown,
+        )),
+        None => Either::Right(router.serve_with_shutdown(socket, shutdown)),
+    })
+}
+
+/// Serves `SplitwiserAdmin` when `ADMIN_SOCKET` is set, with the main listener's certificate.
+/// When `TLS_CLIENT_CA` is set, this is the one listener that requires client certificates.
+pub(super) fn serve_admin(
+    deps: &super::Deps,
+) -> std::io::Result<Option<impl Future<Output = Result<(), tonic::transport::Error>>>> {
+    let (Some(socket), Some(token)) = (deps.env.admin_socket, deps.env.admin_token.as_deref())
+    else {
+        return Ok(None);
+    };
+
+    let server = admin::AdminServer {
+        db: deps.db.clone(),
+    };
+    let authenticator = admin::AdminAuthenticator::new(token);
+
+    let router = tonic::transport::Server::builder()
+        .layer(trace::TraceLayer)
+        .layer(deadline::DeadlineLayer)
+        .add_service(
+            proto::splitwiser_admin_server::SplitwiserAdminServer::with_interceptor(
+                server,
+                authenticator,
+            ),
+        );
+
+    let shutdown = shutdown_signal(deps.shutdown.clone());
+
+    Ok(Some(match deps.tls.clone() {
+        Some(tls) => Either::Left(router.serve_with_incoming_shutdown(
+            crate::tls::incoming(tls, Listener::Admin, crate::tls::bind(socket)?),
+            shutdown,
+        )),
+        None => Either::Right(router.serve_with_shutdown(socket, shutdown)),
+    }))
+}
+
+/// Layers the gRPC listener and the JSON gateway both apply within tracing, outermost first.
+type SharedLayers = Stack<
+    rate_limit::RateLimitLayer,
+    Stack<deadline::DeadlineLayer, Stack<metrics::MetricsLayer, Identity>>,
+>;
+
+/// The [`SharedLayers`], built once and cloned into both listeners so that a client has a single
+/// rate limit budget across them.
+#[derive(Clone)]
+pub(super) struct Layers(SharedLayers);
+
+impl Layers {
+    pub(super) fn new(
+        tokens: Tokens,
+        rate_limit_per_user: NonZeroU32,
+        rate_limit_per_ip: NonZeroU32,
+    ) -> Self {
+        Self(shared_layers(
+            tokens,
+            rate_limit_per_user,
+            rate_limit_per_ip,
+        ))
+    }
+}
+
+fn shared_layers(
+    tokens: Tokens,
+    rate_limit_per_user: NonZeroU32,
+    rate_limit_per_ip: NonZeroU32,
+) -> SharedLayers {
+    ServiceBuilder::new()
+        .layer(metrics::MetricsLayer::new())
+        .layer(deadline::DeadlineLayer)
+        .layer(rate_limit::RateLimitLayer::new(
+            tokens,
+            rate_limit_per_user,
+            rate_limit_per_ip,
+        ))
+        .into_inner()
+}
+
+/// Serves the JSON gateway when `GATEWAY_SOCKET` is set, behind the same layers and TLS
+/// configuration as the gRPC listener.
+pub(super) fn serve_gateway(
+    deps: &super::Deps,
+) -> io::Result<Option<impl Future<Output = Result<(), hyper::Error>>>> {
+    let Some(socket) = deps.env.gateway_socket else {
+        return Ok(None);
+    };
+
+    let service = trace::TraceLayer.layer(gateway::service(
+        gateway::Gateway {
+            db: deps.db.clone(),
+            lightning: deps.lightning.clone(),
+            tokens: Tokens::new(&deps.env.auth_key),
+        },
+        deps.layers.0.clone(),
+    ));
+
+    let listener = crate::tls::bind(socket)?;
+    let shutdown = shutdown_signal(deps.shutdown.clone());
+
+    Ok(Some(match deps.tls.clone() {
+        Some(tls) => Either::Left(serve_http(
+            crate::tls::incoming(tls, Listener::Public, listener),
+            service,
+            shutdown,
+        )),
+        None => Either::Right(serve_http(accept(listener), service, shutdown)),
+    }))
+}
+
+/// Serves `service` on every connection `incoming` yields, storing the peer's address in the
+/// request extensions as tonic does.
+async fn serve_http<IO, S, B>(
+    incoming: impl Stream<Item = io::Result<IO>>,
+    service: S,
+    shutdown: impl Future<Output = ()>,
+) -> Result<(), hyper::Error>
+where
+    IO: Connected + AsyncRead + AsyncWrite + Unpin + Send + 'static,
+    S: Service<http::Request<hyper::Body>, Response = http::Response<B>, Error = Infallible>
+        + Clone
+        + Send
+        + 'static,
+    S::Future: Send + 'static,
+    B: Body + Send + 'static,
+    B::Data: Send,
+    B::Error: Into<Box<dyn std::error::Error + Send + Sync>>,
+{
+    let make_service = hyper::service::make_service_fn(move |io: &IO| {
+        let info = io.connect_info();
+        let service =
+            service
+                .clone()
+                .map_request(move |mut request: http::Request<hyper::Body>| {
+                    request.extensions_mut().insert(info.clone());
+                    request
+                });
+
+        future::ok::<_, Infallible>(service)
+    });
+
+    hyper::Server::builder(hyper::server::accept::from_stream(incoming))
+        .serve(make_service)
+        .with_graceful_shutdown(shutdown)
+        .await
+}
+
+/// Accepts plain TCP connections on `listener`.
+fn accept(listener: TcpListener) -> impl Stream<Item = io::Result<TcpStream>> {
+    futures::stream::unfold(listener, |listener| async move {
+        loop {
+            match listener.accept().await {
+                Ok((stream, _)) => {
+                    let _ = stream.set_nodelay(true);
+                    return Some((Ok(stream), listener));
+                }
+                // Usually running out of file descriptors; retrying right away would spin, and
+                // passing the error on would stop the server.
+                Err(_) => tokio::time::sleep(Duration::from_millis(100)).await,
+            }
+        }
+    })
+}
+
+/// Resolves once `shutdown` turns true.
+pub(super) async fn shutdown_signal(mut shutdown: watch::Receiver<bool>) {
+    while !*shutdown.borrow() {
+        if shutdown.changed().await.is_err() {
+            return futures::future::pending().await;
+        }
+    }
 }
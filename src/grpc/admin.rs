@@ -0,0 +1,207 @@
+// `Interceptor` fixes the error type to `Status`, which clippy deems too large.
+#![allow(clippy::result_large_err)]
+
+use db::{enums::AuditEntityType, queries::integrity::Issue};
+use futures::TryFutureExt;
+use ring::{constant_time, digest};
+use tonic::{service::Interceptor, Request, Response, Status};
+
+use crate::features::admin::{self, AdminError};
+
+use super::{
+    logged,
+    proto::{
+        audit_event, integrity_issue, splitwiser_admin_server::SplitwiserAdmin, Account, Accounts,
+        EntryRef, Id, IntegrityIssue, IntegrityReport, ListUsersRequest, PoolStats,
+        SetAdminRequest,
+    },
+};
+
+/// Accepts only `authorization: Bearer <ADMIN_TOKEN>`, so user access tokens never grant
+/// operator access.
+#[derive(Clone)]
+pub(super) struct AdminAuthenticator {
+    token_digest: digest::Digest,
+}
+
+impl AdminAuthenticator {
+    pub(super) fn new(token: &str) -> Self {
+        Self {
+            token_digest: digest::digest(&digest::SHA256, token.as_bytes()),
+        }
+    }
+}
+
+impl Interceptor for AdminAuthenticator {
+    fn call(&mut self, request: Request<()>) -> Result<Request<()>, Status> {
+        let token = request
+            .metadata()
+            .get("authorization")
+            .and_then(|header| header.to_str().ok())
+            .and_then(|header| header.strip_prefix("Bearer "))
+            .ok_or_else(|| Status::unauthenticated("Missing admin token"))?;
+
+        // Comparing digests keeps the comparison constant-time regardless of token length.
+        let digest = digest::digest(&digest::SHA256, token.as_bytes());
+        constant_time::verify_slices_are_equal(digest.as_ref(), self.token_digest.as_ref())
+            .map_err(|_| Status::unauthenticated("Invalid admin token"))?;
+
+        Ok(request)
+    }
+}
+
+pub(super) struct AdminServer {
+    pub(super) db: db::Db,
+}
+
+#[tonic::async_trait]
+impl SplitwiserAdmin for AdminServer {
+    async fn list_users(
+        &self,
+        request: Request<ListUsersRequest>,
+    ) -> Result<Response<Accounts>, Status> {
+        let request = request.into_inner();
+
+        admin::list_users(&self.db, request.after_id, request.limit)
+            .map_ok(|accounts| {
+                Response::new(Accounts {
+                    accounts: accounts
+                        .into_iter()
+                        .map(|account| Account {
+                            id: *account.id,
+                            display_name: account.display_name,
+                            email: account.email,
+                            created_at: account.created_at.unix_timestamp(),
+                            deactivated_at: account
+                                .deactivated_at
+                                .map(time::OffsetDateTime::unix_timestamp),
+                            admin: account.admin,
+                        })
+                        .collect(),
+                })
+            })
+            .map_err(into_status)
+            .await
+    }
+
+    async fn deactivate_user(&self, request: Request<Id>) -> Result<Response<Id>, Status> {
+        admin::deactivate_user(&self.db, request.into_inner().id)
+            .map_ok(|id| Response::new(Id { id: *id }))
+            .map_err(into_status)
+            .await
+    }
+
+    async fn set_admin(&self, request: Request<SetAdminRequest>) -> Result<Response<Id>, Status> {
+        let request = request.into_inner();
+
+        admin::set_admin(&self.db, request.user_id, request.admin)
+            .map_ok(|id| Response::new(Id { id: *id }))
+            .map_err(into_status)
+            .await
+    }
+
+    async fn force_delete_entry(&self, request: Request<EntryRef>) -> Result<Response<Id>, Status> {
+        let request = request.into_inner();
+        let entity_type = match request.entity_type() {
+            audit_event::EntityType::Revenue => AuditEntityType::UserRevenue,
+            audit_event::EntityType::Payment => AuditEntityType::UserPayment,
+            audit_event::EntityType::Expense => AuditEntityType::UserExpense,
+        };
+
+        admin::force_delete_entry(&self.db, entity_type, request.id)
+            .map_ok(|id| Response::new(Id { id }))
+            .map_err(into_status)
+            .await
+    }
+
+    async fn check_integrity(
+        &self,
+        _request: Request<()>,
+    ) -> Result<Response<IntegrityReport>, Status> {
+        admin::check_integrity(&self.db)
+            .map_ok(|issues| {
+                Response::new(IntegrityReport {
+                    issues: issues.into_iter().map(into_proto).collect(),
+                })
+            })
+            .map_err(into_status)
+            .await
+    }
+
+    async fn get_pool_stats(&self, _request: Request<()>) -> Result<Response<PoolStats>, Status> {
+        let state = self.db.state();
+
+        Ok(Response::new(PoolStats {
+            connections: state.connections,
+            idle_connections: state.idle_connections,
+            max_size: state.max_size,
+        }))
+    }
+}
+
+fn into_status(e: AdminError) -> Status {
+    match e {
+        AdminError::NotFound => Status::not_found(e.to_string()),
+        AdminError::Referenced => Status::failed_precondition(e.to_string()),
+        AdminError::DbError(_) => logged(Status::internal("Database error"), e),
+    }
+}
+
+fn into_proto(issue: Issue) -> IntegrityIssue {
+    let (kind, entity_id, detail) = match issue {
+        Issue::UnbalancedInstallments {
+            expense_id,
+            amount_cents,
+            installments_cents,
+            installments,
+        } => (
+            integrity_issue::Kind::UnbalancedInstallments,
+            expense_id,
+            format!("{installments} installments add up to {installments_cents} of {amount_cents} cents"),
+        ),
+        Issue::UnrecordedSettlement { invoice_id } => (
+            integrity_issue::Kind::UnrecordedSettlement,
+            invoice_id,
+            "Paid invoice has no payment".to_owned(),
+        ),
+        Issue::ReviewedPendingPayment { payment_id } => (
+            integrity_issue::Kind::ReviewedPendingPayment,
+            payment_id,
+            "Pending payment has a review date".to_owned(),
+        ),
+    };
+
+    IntegrityIssue {
+        kind: kind.into(),
+        entity_id,
+        detail,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn intercept(header: Option<&str>) -> Result<Request<()>, Status> {
+        let mut request = Request::new(());
+        if let Some(header) = header {
+            request
+                .metadata_mut()
+                .insert("authorization", header.parse().unwrap());
+        }
+
+        AdminAuthenticator::new("operator").call(request)
+    }
+
+    #[test]
+    fn requires_the_admin_token() {
+        assert!(intercept(Some("Bearer operator")).is_ok());
+
+        for header in [None, Some("Bearer operato"), Some("operator")] {
+            assert_eq!(
+                intercept(header).unwrap_err().code(),
+                tonic::Code::Unauthenticated
+            );
+        }
+    }
+}
@@ -0,0 +1,96 @@
+use db::{enums::AuditEntityType, queries::audit_events::AuditEvent, types::UserId};
+use tonic::Status;
+
+use crate::features::audit::{self, ListParams};
+
+use super::{
+    logged,
+    proto::{self, audit_event, AuditEvents, ListAuditEventsRequest},
+};
+
+pub(super) async fn list(
+    db: &db::Db,
+    caller: UserId,
+    request: ListAuditEventsRequest,
+) -> Result<AuditEvents, Status> {
+    let entity = match (request.entity_type, request.entity_id) {
+        (Some(entity_type), Some(id)) => {
+            let entity_type = audit_event::EntityType::from_i32(entity_type)
+                .ok_or_else(|| Status::invalid_argument("Unknown entity_type"))?;
+            Some((into_entity_type(entity_type), id))
+        }
+        (None, None) => None,
+        _ => {
+            return Err(Status::invalid_argument(
+                "entity_type and entity_id must be set together",
+            ))
+        }
+    };
+
+    audit::list(
+        db,
+        caller,
+        ListParams {
+            entity,
+            actor_user_id: request.actor_user_id,
+            before_id: request.before_id,
+            limit: request.limit,
+        },
+    )
+    .await
+    .map(|events| AuditEvents {
+        events: events.into_iter().map(into_proto).collect(),
+    })
+    .map_err(|e| logged(Status::internal("Database error"), e))
+}
+
+fn into_entity_type(entity_type: audit_event::EntityType) -> AuditEntityType {
+    match entity_type {
+        audit_event::EntityType::Revenue => AuditEntityType::UserRevenue,
+        audit_event::EntityType::Payment => AuditEntityType::UserPayment,
+        audit_event::EntityType::Expense => AuditEntityType::UserExpense,
+    }
+}
+
+fn into_proto(event: AuditEvent) -> proto::AuditEvent {
+    let entity_type = match event.entity_type {
+        AuditEntityType::UserRevenue => audit_event::EntityType::Revenue,
+        AuditEntityType::UserPayment => audit_event::EntityType::Payment,
+        AuditEntityType::UserExpense => audit_event::EntityType::Expense,
+    };
+
+    proto::AuditEvent {
+        id: *event.id,
+        actor_user_id: event.actor_user_id,
+        rpc: event.rpc,
+        entity_type: entity_type.into(),
+        entity_id: event.entity_id,
+        before: event.before.map(|before| before.to_string()),
+        after: event.after.map(|after| after.to_string()),
+        created_at: event.created_at.unix_timestamp(),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[tokio::test]
+    async fn rejects_unknown_entity_types() {
+        let db = db::test::db();
+
+        let status = list(
+            &db,
+            UserId::from(1),
+            ListAuditEventsRequest {
+                entity_type: Some(99),
+                entity_id: Some(1),
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap_err();
+
+        assert_eq!(status.code(), tonic::Code::InvalidArgument);
+    }
+}
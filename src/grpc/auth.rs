@@ -0,0 +1,290 @@
+// Handlers return `Status` as their error, which clippy deems too large.
+#![allow(clippy::result_large_err)]
+
+use std::task::{Context, Poll};
+
+use db::{
+    queries::sessions,
+    types::{SessionId, UserId},
+};
+use futures::future::BoxFuture;
+use time::OffsetDateTime;
+use tonic::{body::BoxBody, codegen::http, server::NamedService, transport::Body, Request, Status};
+use tower::{Layer, Service};
+
+use crate::auth::{Caller, Tokens};
+
+use super::logged;
+
+/// Validates `authorization: Bearer <token>` headers against the session the token was issued
+/// for, and stores the caller's [`UserId`] and [`SessionId`] in the request extensions. Requests
+/// without the header pass through so that endpoints such as `CreateUser` stay reachable;
+/// [`caller`] rejects them everywhere else.
+#[derive(Clone)]
+pub(super) struct AuthLayer {
+    pub(super) db: db::Db,
+    pub(super) tokens: Tokens,
+}
+
+impl<S> Layer<S> for AuthLayer {
+    type Service = Authenticate<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        Authenticate {
+            inner,
+            layer: self.clone(),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub(super) struct Authenticate<S> {
+    inner: S,
+    layer: AuthLayer,
+}
+
+impl<S: NamedService> NamedService for Authenticate<S> {
+    const NAME: &'static str = S::NAME;
+}
+
+impl<S> Service<http::Request<Body>> for Authenticate<S>
+where
+    S: Service<http::Request<Body>, Response = http::Response<BoxBody>> + Clone + Send + 'static,
+    S::Future: Send,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = BoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, mut request: http::Request<Body>) -> Self::Future {
+        // The clone may not be ready, so call the instance that was polled.
+        let clone = self.inner.clone();
+        let mut inner = std::mem::replace(&mut self.inner, clone);
+        let layer = self.layer.clone();
+
+        Box::pin(async move {
+            let verified = request
+                .headers()
+                .get("authorization")
+                .map(|header| verify(&layer.tokens, header.as_bytes()));
+
+            if let Some(verified) = verified {
+                let caller = match verified {
+                    Ok(caller) => caller,
+                    Err(status) => return Ok(status.to_http()),
+                };
+
+                if let Err(status) = check_session(&layer.db, caller).await {
+                    return Ok(status.to_http());
+                }
+
+                request.extensions_mut().insert(caller.user_id);
+                request.extensions_mut().insert(caller.session_id);
+            }
+
+            inner.call(request).await
+        })
+    }
+}
+
+fn verify(tokens: &Tokens, header: &[u8]) -> Result<Caller, Status> {
+    let token = std::str::from_utf8(header)
+        .ok()
+        .and_then(|header| header.strip_prefix("Bearer "))
+        .ok_or_else(|| Status::unauthenticated("Malformed authorization header"))?;
+
+    tokens
+        .verify(token)
+        .map_err(|_| Status::unauthenticated("Invalid token"))
+}
+
+/// Rejects tokens of sessions that were revoked or have expired since the token was issued.
+async fn check_session(db: &db::Db, caller: Caller) -> Result<(), Status> {
+    let live = db
+        .write(move |conn| {
+            sessions::is_live(
+                conn,
+                *caller.session_id,
+                *caller.user_id,
+                OffsetDateTime::now_utc(),
+            )
+        })
+        .await
+        .map_err(|e| logged(Status::internal("Database error"), e))?;
+
+    if live {
+        Ok(())
+    } else {
+        Err(Status::unauthenticated("Session revoked"))
+    }
+}
+
+/// Authenticates an `authorization` value of the form `Bearer <token>`, for callers outside the
+/// gRPC stack.
+pub(super) async fn authenticate(
+    db: &db::Db,
+    tokens: &Tokens,
+    header: &[u8],
+) -> Result<Caller, Status> {
+    let caller = verify(tokens, header)?;
+    check_session(db, caller).await?;
+    Ok(caller)
+}
+
+pub(super) fn caller<T>(request: &Request<T>) -> Result<UserId, Status> {
+    request
+        .extensions()
+        .get::<UserId>()
+        .copied()
+        .ok_or_else(missing)
+}
+
+pub(super) fn session<T>(request: &Request<T>) -> Result<SessionId, Status> {
+    request
+        .extensions()
+        .get::<SessionId>()
+        .copied()
+        .ok_or_else(missing)
+}
+
+fn missing() -> Status {
+    Status::unauthenticated("Missing bearer token")
+}
+
+#[cfg(test)]
+mod test {
+    use std::convert::Infallible;
+
+    use tower::ServiceExt;
+
+    use super::*;
+    use crate::features::session::{self, CredentialsParams};
+
+    /// Answers with the caller it was handed, or an empty body for anonymous requests.
+    #[derive(Clone)]
+    struct Echo;
+
+    impl Service<http::Request<Body>> for Echo {
+        type Response = http::Response<BoxBody>;
+        type Error = Infallible;
+        type Future = futures::future::Ready<Result<Self::Response, Infallible>>;
+
+        fn poll_ready(&mut self, _: &mut Context<'_>) -> Poll<Result<(), Infallible>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn call(&mut self, request: http::Request<Body>) -> Self::Future {
+            let request = Request::from_http(request);
+            let mut response = http::Response::new(tonic::body::empty_body());
+
+            if let Ok(caller) = caller(&request) {
+                response.headers_mut().insert("x-caller", (*caller).into());
+            }
+
+            futures::future::ready(Ok(response))
+        }
+    }
+
+    async fn call(db: &db::Db, tokens: &Tokens, header: Option<&str>) -> http::Response<BoxBody> {
+        let mut request = http::Request::new(Body::empty());
+        if let Some(header) = header {
+            request
+                .headers_mut()
+                .insert("authorization", header.parse().unwrap());
+        }
+
+        AuthLayer {
+            db: db.clone(),
+            tokens: tokens.clone(),
+        }
+        .layer(Echo)
+        .oneshot(request)
+        .await
+        .unwrap()
+    }
+
+    fn code(response: &http::Response<BoxBody>) -> Option<&str> {
+        response
+            .headers()
+            .get("grpc-status")
+            .map(|code| code.to_str().unwrap())
+    }
+
+    #[tokio::test]
+    async fn injects_caller_of_live_sessions_only() {
+        let db = db::test::db();
+        let tokens = Tokens::new("secret");
+
+        let (user_id, issued) = session::register(
+            &db,
+            &tokens,
+            CredentialsParams {
+                email: "a@example.com".to_owned(),
+                password: "password".to_owned(),
+                device: None,
+            },
+        )
+        .await
+        .unwrap();
+        let header = format!("Bearer {}", issued.access_token);
+
+        let response = call(&db, &tokens, Some(&header)).await;
+        assert_eq!(code(&response), None);
+        assert_eq!(
+            response.headers().get("x-caller").unwrap(),
+            &user_id.to_string()
+        );
+
+        session::revoke(&db, user_id, *issued.session_id)
+            .await
+            .unwrap();
+
+        let response = call(&db, &tokens, Some(&header)).await;
+        assert_eq!(code(&response), Some("16"));
+        assert!(response.headers().get("x-caller").is_none());
+    }
+
+    #[tokio::test]
+    async fn anonymous_requests_have_no_caller() {
+        let db = db::test::db();
+        let response = call(&db, &Tokens::new("secret"), None).await;
+
+        assert_eq!(code(&response), None);
+        assert!(response.headers().get("x-caller").is_none());
+    }
+
+    #[tokio::test]
+    async fn rejects_invalid_tokens() {
+        let db = db::test::db();
+        let caller = Caller {
+            user_id: UserId::from(3),
+            session_id: SessionId::from(5),
+        };
+        let now = OffsetDateTime::now_utc();
+        let token = Tokens::new("other").issue(caller, now);
+        let sessionless = jsonwebtoken::encode(
+            &jsonwebtoken::Header::default(),
+            &serde_json::json!({
+                "sub": 3,
+                "iat": now.unix_timestamp(),
+                "exp": now.unix_timestamp() + 60,
+            }),
+            &jsonwebtoken::EncodingKey::from_secret(b"secret"),
+        )
+        .unwrap();
+
+        for header in [
+            format!("Bearer {token}"),
+            format!("Bearer {sessionless}"),
+            token,
+            "Bearer".to_owned(),
+        ] {
+            let response = call(&db, &Tokens::new("secret"), Some(&header)).await;
+            assert_eq!(code(&response), Some("16"));
+        }
+    }
+}
@@ -0,0 +1,155 @@
+use std::{
+    task::{Context, Poll},
+    time::{Duration, Instant},
+};
+
+use futures::future::{self, BoxFuture, Either};
+use tonic::{
+    body::BoxBody,
+    codegen::http::{self, HeaderMap},
+    Status,
+};
+use tower::{Layer, Service};
+
+const GRPC_TIMEOUT: &str = "grpc-timeout";
+
+/// How long before the client's deadline the server gives up. Tonic enforces `grpc-timeout` too,
+/// but answers `CANCELLED` and may do so a moment before this layer would.
+const MARGIN: Duration = Duration::from_millis(5);
+
+/// Enforces the `grpc-timeout` a client sends. Past the deadline the RPC is answered with
+/// `DEADLINE_EXCEEDED` and its handler dropped, and its database writes, which run within
+/// [`db::deadline::scope`], are cut short and rolled back.
+#[derive(Clone, Copy)]
+pub(super) struct DeadlineLayer;
+
+impl<S> Layer<S> for DeadlineLayer {
+    type Service = Deadline<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        Deadline { inner }
+    }
+}
+
+#[derive(Clone)]
+pub(super) struct Deadline<S> {
+    inner: S,
+}
+
+impl<S, B> Service<http::Request<B>> for Deadline<S>
+where
+    S: Service<http::Request<B>, Response = http::Response<BoxBody>>,
+    S::Future: Send + 'static,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = Either<S::Future, BoxFuture<'static, Result<Self::Response, Self::Error>>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: http::Request<B>) -> Self::Future {
+        let Some(timeout) = grpc_timeout(request.headers()) else {
+            return Either::Left(self.inner.call(request));
+        };
+
+        let deadline = Instant::now() + timeout.saturating_sub(MARGIN);
+        let response = db::deadline::scope(deadline, self.inner.call(request));
+        let expired = tokio::time::sleep_until(deadline.into());
+
+        Either::Right(Box::pin(async move {
+            match future::select(Box::pin(response), Box::pin(expired)).await {
+                // Failures once the deadline has passed are most likely caused by it, such as a
+                // statement cut short.
+                Either::Left((Ok(response), _))
+                    if response.headers().contains_key("grpc-status")
+                        && Instant::now() >= deadline =>
+                {
+                    Ok(exceeded())
+                }
+                Either::Left((response, _)) => response,
+                Either::Right(((), _)) => Ok(exceeded()),
+            }
+        }))
+    }
+}
+
+fn exceeded() -> http::Response<BoxBody> {
+    Status::deadline_exceeded("Deadline exceeded").to_http()
+}
+
+/// Parses `grpc-timeout`: at most eight digits followed by a unit, `H`, `M`, `S`, `m`, `u` or
+/// `n`. Malformed values are ignored, as tonic does.
+fn grpc_timeout(headers: &HeaderMap) -> Option<Duration> {
+    let value = headers.get(GRPC_TIMEOUT)?.to_str().ok()?;
+    if value.len() < 2 {
+        return None;
+    }
+
+    let (amount, unit) = value.split_at(value.len() - 1);
+    if amount.len() > 8 || !amount.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    let amount: u64 = amount.parse().ok()?;
+
+    Some(match unit {
+        "H" => Duration::from_secs(amount * 60 * 60),
+        "M" => Duration::from_secs(amount * 60),
+        "S" => Duration::from_secs(amount),
+        "m" => Duration::from_millis(amount),
+        "u" => Duration::from_micros(amount),
+        "n" => Duration::from_nanos(amount),
+        _ => return None,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use std::convert::Infallible;
+
+    use tonic::Code;
+
+    use super::*;
+
+    fn timeout(value: &str) -> Option<Duration> {
+        let mut headers = HeaderMap::new();
+        headers.insert(GRPC_TIMEOUT, value.parse().unwrap());
+        grpc_timeout(&headers)
+    }
+
+    #[test]
+    fn parses_grpc_timeouts() {
+        assert_eq!(timeout("1S"), Some(Duration::from_secs(1)));
+        assert_eq!(timeout("250m"), Some(Duration::from_millis(250)));
+        assert_eq!(timeout("2H"), Some(Duration::from_secs(7200)));
+        assert_eq!(timeout("99999999n"), Some(Duration::from_nanos(99_999_999)));
+        assert_eq!(timeout("123456789S"), None);
+        assert_eq!(timeout("+1S"), None);
+        assert_eq!(timeout("1s"), None);
+        assert_eq!(timeout("S"), None);
+    }
+
+    #[tokio::test]
+    async fn answers_deadline_exceeded_once_the_deadline_passes() {
+        let mut service = DeadlineLayer.layer(tower::service_fn(|_| async {
+            tokio::time::sleep(Duration::from_secs(5)).await;
+            Ok::<_, Infallible>(http::Response::new(tonic::body::empty_body()))
+        }));
+
+        let started = Instant::now();
+        let response = service
+            .call(
+                http::Request::builder()
+                    .header(GRPC_TIMEOUT, "50m")
+                    .body(())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert!(started.elapsed() < Duration::from_secs(1));
+        let status = Status::from_header_map(response.headers()).unwrap();
+        assert_eq!(status.code(), Code::DeadlineExceeded);
+    }
+}
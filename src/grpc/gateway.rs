@@ -0,0 +1,646 @@
+use std::{convert::Infallible, sync::Arc};
+
+use axum::{
+    async_trait,
+    body::HttpBody,
+    extract::{
+        rejection::{JsonRejection, PathRejection, QueryRejection},
+        FromRequest, FromRequestParts, MatchedPath, Path, Query, State,
+    },
+    http::{header, request::Parts, Request, StatusCode},
+    middleware::{self, Next},
+    response::{IntoResponse, Response},
+    routing::{delete, get, patch, post, put},
+    Json, Router,
+};
+use tonic::{body::BoxBody, codegen::http, Code, Status};
+use tower::{util::BoxCloneService, ServiceBuilder};
+
+use super::{
+    audit, auth, metrics, profile,
+    proto::{
+        AuditEvents, AuthTokens, Balance, BitcoinPaymentUri, ConfirmPaymentRequest,
+        CreateExpenseRequest, CreatePaymentRequest, CreateRevenueRequest, CreateUserRequest,
+        CreatedUser, Expense, GetBalanceRequest, GetBitcoinPaymentUriRequest, GetPixPayloadRequest,
+        Id, ListAuditEventsRequest, LoginRequest, PixPayload, RefreshSessionRequest,
+        RenderQrCodeRequest, RequestSettlementInvoiceRequest, SearchUsersRequest, Sessions,
+        SetBitcoinAddressRequest, SetPixAccountRequest, SettlementInvoice, UpdateExpenseRequest,
+        UpdateProfileRequest, User, Users,
+    },
+    session, settlement, user, SharedLayers,
+};
+use crate::{
+    auth::{Caller, Tokens},
+    lightning::LightningBackend,
+};
+
+pub(super) struct Gateway {
+    pub(super) db: db::Db,
+    pub(super) lightning: Option<Arc<dyn LightningBackend>>,
+    pub(super) tokens: Tokens,
+}
+
+/// JSON over HTTP for clients that cannot speak gRPC. Every route calls the same adapter as its
+/// RPC, so bodies are the proto messages with their field names, enums are their numeric values
+/// and timestamps are unix seconds. Errors are `{"code": "NOT_FOUND", "message": "..."}` with the
+/// usual HTTP status for the gRPC code.
+pub(super) fn router(gateway: Gateway) -> Router {
+    Router::new()
+        .route("/users", post(create_user).get(search_users))
+        .route("/users/me", patch(update_profile))
+        .route("/users/:id", get(get_user))
+        .route("/sessions", post(login).get(list_sessions))
+        .route("/sessions/refresh", post(refresh_session))
+        .route("/sessions/current", delete(logout))
+        .route("/sessions/:id", delete(revoke_session))
+        .route("/revenues", post(create_revenue))
+        .route("/payments", post(create_payment))
+        .route("/payments/:id/review", post(confirm_payment))
+        .route("/expenses", post(create_expense))
+        .route(
+            "/expenses/:id",
+            get(get_expense)
+                .patch(update_expense)
+                .delete(delete_expense),
+        )
+        .route("/balances", get(get_balance))
+        .route("/settlement-invoices", post(request_settlement_invoice))
+        .route("/settlement-invoices/:id", get(get_settlement_invoice))
+        .route("/pix-account", put(set_pix_account))
+        .route("/pix-payload", get(get_pix_payload))
+        .route("/bitcoin-address", put(set_bitcoin_address))
+        .route("/bitcoin-payment-uri", get(get_bitcoin_payment_uri))
+        .route("/qr-codes", post(render_qr_code))
+        .route("/audit-events", get(list_audit_events))
+        .route_layer(middleware::from_fn(label_metrics))
+        .with_state(Arc::new(gateway))
+}
+
+/// The gateway behind `layers`, the ones the gRPC listener applies as well. Their own responses,
+/// such as RESOURCE_EXHAUSTED from rate limiting, are gRPC statuses and are rendered as JSON.
+pub(super) fn service(
+    gateway: Gateway,
+    layers: SharedLayers,
+) -> BoxCloneService<Request<hyper::Body>, Response, Infallible> {
+    BoxCloneService::new(
+        ServiceBuilder::new()
+            .map_response(json_errors)
+            .layer(layers)
+            .map_response(|response: Response| {
+                response.map(|body| {
+                    body.map_err(|e| Status::internal(e.to_string()))
+                        .boxed_unsync()
+                })
+            })
+            .service(router(gateway)),
+    )
+}
+
+fn json_errors(response: http::Response<BoxBody>) -> Response {
+    let is_grpc = response
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .is_some_and(|content_type| content_type == "application/grpc");
+
+    match Status::from_header_map(response.headers()) {
+        Some(status) if is_grpc => {
+            let mut json = JsonStatus(status).into_response();
+            if let Some(retry_after) = response.headers().get(header::RETRY_AFTER) {
+                json.headers_mut()
+                    .insert(header::RETRY_AFTER, retry_after.clone());
+            }
+            json
+        }
+        _ => response.map(axum::body::boxed),
+    }
+}
+
+/// Labels the metrics of each call by its route, such as `GET /users/:id`, and the code of its
+/// [`JsonStatus`].
+async fn label_metrics<B>(path: MatchedPath, request: Request<B>, next: Next<B>) -> Response {
+    let method = format!("{} {}", request.method(), path.as_str());
+    let mut response = next.run(request).await;
+
+    let code = match response.extensions().get::<Code>() {
+        Some(&code) => code,
+        None if response.status().is_success() => Code::Ok,
+        None => Code::Unknown,
+    };
+    response
+        .extensions_mut()
+        .insert(metrics::Labels { method, code });
+
+    response
+}
+
+type GatewayState = State<Arc<Gateway>>;
+type JsonResult<T> = Result<Json<T>, JsonStatus>;
+
+async fn create_user(
+    State(gw): GatewayState,
+    Body(request): Body<CreateUserRequest>,
+) -> JsonResult<CreatedUser> {
+    Ok(Json(
+        session::create_user(&gw.db, &gw.tokens, request).await?,
+    ))
+}
+
+async fn login(
+    State(gw): GatewayState,
+    Body(request): Body<LoginRequest>,
+) -> JsonResult<AuthTokens> {
+    Ok(Json(session::login(&gw.db, &gw.tokens, request).await?))
+}
+
+async fn refresh_session(
+    State(gw): GatewayState,
+    Body(request): Body<RefreshSessionRequest>,
+) -> JsonResult<AuthTokens> {
+    Ok(Json(session::refresh(&gw.db, &gw.tokens, request).await?))
+}
+
+async fn logout(
+    State(gw): GatewayState,
+    Authenticated(caller): Authenticated,
+) -> Result<StatusCode, JsonStatus> {
+    session::logout(&gw.db, caller.user_id, caller.session_id).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+async fn list_sessions(
+    State(gw): GatewayState,
+    Authenticated(caller): Authenticated,
+) -> JsonResult<Sessions> {
+    Ok(Json(
+        session::list(&gw.db, caller.user_id, caller.session_id).await?,
+    ))
+}
+
+async fn revoke_session(
+    State(gw): GatewayState,
+    Authenticated(caller): Authenticated,
+    Params(id): Params<i32>,
+) -> JsonResult<Id> {
+    Ok(Json(session::revoke(&gw.db, caller.user_id, id).await?))
+}
+
+async fn update_profile(
+    State(gw): GatewayState,
+    Authenticated(caller): Authenticated,
+    Body(request): Body<UpdateProfileRequest>,
+) -> JsonResult<User> {
+    Ok(Json(
+        profile::update(&gw.db, caller.user_id, request).await?,
+    ))
+}
+
+async fn get_user(
+    State(gw): GatewayState,
+    Authenticated(caller): Authenticated,
+    Params(id): Params<i32>,
+) -> JsonResult<User> {
+    Ok(Json(profile::get(&gw.db, caller.user_id, id).await?))
+}
+
+async fn search_users(
+    State(gw): GatewayState,
+    Authenticated(caller): Authenticated,
+    Filter(request): Filter<SearchUsersRequest>,
+) -> JsonResult<Users> {
+    Ok(Json(
+        profile::search(&gw.db, caller.user_id, request).await?,
+    ))
+}
+
+async fn create_revenue(
+    State(gw): GatewayState,
+    Authenticated(caller): Authenticated,
+    Body(request): Body<CreateRevenueRequest>,
+) -> JsonResult<Id> {
+    Ok(Json(
+        user::create_revenue(&gw.db, caller.user_id, request).await?,
+    ))
+}
+
+async fn create_payment(
+    State(gw): GatewayState,
+    Authenticated(caller): Authenticated,
+    Body(request): Body<CreatePaymentRequest>,
+) -> JsonResult<Id> {
+    Ok(Json(
+        user::create_payment(&gw.db, caller.user_id, request).await?,
+    ))
+}
+
+async fn confirm_payment(
+    State(gw): GatewayState,
+    Authenticated(caller): Authenticated,
+    Params(payment_id): Params<i32>,
+    Body(request): Body<ConfirmPaymentRequest>,
+) -> JsonResult<Id> {
+    let request = ConfirmPaymentRequest {
+        payment_id,
+        ..request
+    };
+    Ok(Json(
+        user::confirm_payment(&gw.db, caller.user_id, request).await?,
+    ))
+}
+
+async fn create_expense(
+    State(gw): GatewayState,
+    Authenticated(caller): Authenticated,
+    Body(request): Body<CreateExpenseRequest>,
+) -> JsonResult<Id> {
+    Ok(Json(
+        user::create_expense(&gw.db, caller.user_id, request).await?,
+    ))
+}
+
+async fn get_expense(
+    State(gw): GatewayState,
+    Authenticated(caller): Authenticated,
+    Params(id): Params<i32>,
+) -> JsonResult<Expense> {
+    Ok(Json(user::get_expense(&gw.db, caller.user_id, id).await?))
+}
+
+async fn update_expense(
+    State(gw): GatewayState,
+    Authenticated(caller): Authenticated,
+    Params(expense_id): Params<i32>,
+    Body(request): Body<UpdateExpenseRequest>,
+) -> JsonResult<Expense> {
+    let request = UpdateExpenseRequest {
+        expense_id,
+        ..request
+    };
+    Ok(Json(
+        user::update_expense(&gw.db, caller.user_id, request).await?,
+    ))
+}
+
+async fn delete_expense(
+    State(gw): GatewayState,
+    Authenticated(caller): Authenticated,
+    Params(id): Params<i32>,
+) -> JsonResult<Id> {
+    Ok(Json(
+        user::delete_expense(&gw.db, caller.user_id, id).await?,
+    ))
+}
+
+async fn get_balance(
+    State(gw): GatewayState,
+    Authenticated(caller): Authenticated,
+    Filter(request): Filter<GetBalanceRequest>,
+) -> JsonResult<Balance> {
+    Ok(Json(user::balance(&gw.db, caller.user_id, request).await?))
+}
+
+async fn request_settlement_invoice(
+    State(gw): GatewayState,
+    Authenticated(caller): Authenticated,
+    Body(request): Body<RequestSettlementInvoiceRequest>,
+) -> JsonResult<SettlementInvoice> {
+    let lightning = gw.lightning.as_deref();
+    Ok(Json(
+        settlement::request_invoice(&gw.db, lightning, caller.user_id, request).await?,
+    ))
+}
+
+async fn get_settlement_invoice(
+    State(gw): GatewayState,
+    Authenticated(caller): Authenticated,
+    Params(id): Params<i32>,
+) -> JsonResult<SettlementInvoice> {
+    let lightning = gw.lightning.as_deref();
+    Ok(Json(
+        settlement::get_invoice(&gw.db, lightning, caller.user_id, id).await?,
+    ))
+}
+
+async fn set_pix_account(
+    State(gw): GatewayState,
+    Authenticated(caller): Authenticated,
+    Body(request): Body<SetPixAccountRequest>,
+) -> JsonResult<Id> {
+    Ok(Json(
+        settlement::set_pix_account(&gw.db, caller.user_id, request).await?,
+    ))
+}
+
+async fn get_pix_payload(
+    State(gw): GatewayState,
+    Authenticated(caller): Authenticated,
+    Filter(request): Filter<GetPixPayloadRequest>,
+) -> JsonResult<PixPayload> {
+    Ok(Json(
+        settlement::pix_payload(&gw.db, caller.user_id, request).await?,
+    ))
+}
+
+async fn set_bitcoin_address(
+    State(gw): GatewayState,
+    Authenticated(caller): Authenticated,
+    Body(request): Body<SetBitcoinAddressRequest>,
+) -> JsonResult<Id> {
+    Ok(Json(
+        settlement::set_bitcoin_address(&gw.db, caller.user_id, request).await?,
+    ))
+}
+
+async fn get_bitcoin_payment_uri(
+    State(gw): GatewayState,
+    Authenticated(caller): Authenticated,
+    Filter(request): Filter<GetBitcoinPaymentUriRequest>,
+) -> JsonResult<BitcoinPaymentUri> {
+    Ok(Json(
+        settlement::bitcoin_payment_uri(&gw.db, caller.user_id, request).await?,
+    ))
+}
+
+/// Responds with the image itself rather than JSON.
+async fn render_qr_code(
+    Authenticated(_): Authenticated,
+    Body(request): Body<RenderQrCodeRequest>,
+) -> Result<Response, JsonStatus> {
+    let qr = settlement::render_qr_code(request).await?;
+    Ok(([(header::CONTENT_TYPE, qr.content_type)], qr.content).into_response())
+}
+
+async fn list_audit_events(
+    State(gw): GatewayState,
+    Authenticated(caller): Authenticated,
+    Filter(request): Filter<ListAuditEventsRequest>,
+) -> JsonResult<AuditEvents> {
+    Ok(Json(audit::list(&gw.db, caller.user_id, request).await?))
+}
+
+/// The caller of a request with a valid `authorization: Bearer <token>` header.
+struct Authenticated(Caller);
+
+#[async_trait]
+impl FromRequestParts<Arc<Gateway>> for Authenticated {
+    type Rejection = JsonStatus;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        gateway: &Arc<Gateway>,
+    ) -> Result<Self, Self::Rejection> {
+        let header = parts
+            .headers
+            .get(header::AUTHORIZATION)
+            .ok_or_else(|| Status::unauthenticated("Missing bearer token"))?;
+
+        Ok(Self(
+            auth::authenticate(&gateway.db, &gateway.tokens, header.as_bytes()).await?,
+        ))
+    }
+}
+
+/// A JSON body, rejected with INVALID_ARGUMENT like a malformed protobuf.
+struct Body<T>(T);
+
+#[async_trait]
+impl<T, S, B> FromRequest<S, B> for Body<T>
+where
+    Json<T>: FromRequest<S, B, Rejection = JsonRejection>,
+    S: Send + Sync,
+    B: Send + 'static,
+{
+    type Rejection = JsonStatus;
+
+    async fn from_request(request: Request<B>, state: &S) -> Result<Self, Self::Rejection> {
+        let Json(value) = Json::from_request(request, state)
+            .await
+            .map_err(|e| Status::invalid_argument(e.body_text()))?;
+        Ok(Self(value))
+    }
+}
+
+/// Query parameters, rejected with INVALID_ARGUMENT.
+struct Filter<T>(T);
+
+#[async_trait]
+impl<T, S> FromRequestParts<S> for Filter<T>
+where
+    Query<T>: FromRequestParts<S, Rejection = QueryRejection>,
+    S: Send + Sync,
+{
+    type Rejection = JsonStatus;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let Query(value) = Query::from_request_parts(parts, state)
+            .await
+            .map_err(|e| Status::invalid_argument(e.body_text()))?;
+        Ok(Self(value))
+    }
+}
+
+/// Path parameters, rejected with INVALID_ARGUMENT.
+struct Params<T>(T);
+
+#[async_trait]
+impl<T, S> FromRequestParts<S> for Params<T>
+where
+    Path<T>: FromRequestParts<S, Rejection = PathRejection>,
+    S: Send + Sync,
+{
+    type Rejection = JsonStatus;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let Path(value) = Path::from_request_parts(parts, state)
+            .await
+            .map_err(|e| Status::invalid_argument(e.body_text()))?;
+        Ok(Self(value))
+    }
+}
+
+/// Renders a [`Status`] as a JSON error body.
+pub(super) struct JsonStatus(Status);
+
+impl From<Status> for JsonStatus {
+    fn from(status: Status) -> Self {
+        Self(status)
+    }
+}
+
+impl IntoResponse for JsonStatus {
+    fn into_response(self) -> Response {
+        let (http_status, code) = match self.0.code() {
+            Code::Ok => (StatusCode::OK, "OK"),
+            Code::Cancelled => (StatusCode::from_u16(499).unwrap(), "CANCELLED"),
+            Code::Unknown => (StatusCode::INTERNAL_SERVER_ERROR, "UNKNOWN"),
+            Code::InvalidArgument => (StatusCode::BAD_REQUEST, "INVALID_ARGUMENT"),
+            Code::DeadlineExceeded => (StatusCode::GATEWAY_TIMEOUT, "DEADLINE_EXCEEDED"),
+            Code::NotFound => (StatusCode::NOT_FOUND, "NOT_FOUND"),
+            Code::AlreadyExists => (StatusCode::CONFLICT, "ALREADY_EXISTS"),
+            Code::PermissionDenied => (StatusCode::FORBIDDEN, "PERMISSION_DENIED"),
+            Code::ResourceExhausted => (StatusCode::TOO_MANY_REQUESTS, "RESOURCE_EXHAUSTED"),
+            Code::FailedPrecondition => (StatusCode::BAD_REQUEST, "FAILED_PRECONDITION"),
+            Code::Aborted => (StatusCode::CONFLICT, "ABORTED"),
+            Code::OutOfRange => (StatusCode::BAD_REQUEST, "OUT_OF_RANGE"),
+            Code::Unimplemented => (StatusCode::NOT_IMPLEMENTED, "UNIMPLEMENTED"),
+            Code::Internal => (StatusCode::INTERNAL_SERVER_ERROR, "INTERNAL"),
+            Code::Unavailable => (StatusCode::SERVICE_UNAVAILABLE, "UNAVAILABLE"),
+            Code::DataLoss => (StatusCode::INTERNAL_SERVER_ERROR, "DATA_LOSS"),
+            Code::Unauthenticated => (StatusCode::UNAUTHORIZED, "UNAUTHENTICATED"),
+        };
+
+        let body = serde_json::json!({ "code": code, "message": self.0.message() });
+        let mut response = (http_status, Json(body)).into_response();
+        response.extensions_mut().insert(self.0.code());
+        response
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::num::NonZeroU32;
+
+    use axum::body::{Body as HttpBody, HttpBody as _};
+    use tower::{Service, ServiceExt};
+
+    use super::*;
+
+    fn gateway() -> Router {
+        router(Gateway {
+            db: db::test::db(),
+            lightning: None,
+            tokens: Tokens::new("secret"),
+        })
+    }
+
+    async fn call<S>(
+        router: &S,
+        method: &str,
+        uri: &str,
+        token: Option<&str>,
+        body: Option<serde_json::Value>,
+    ) -> (StatusCode, serde_json::Value)
+    where
+        S: Service<Request<HttpBody>, Response = Response, Error = Infallible> + Clone,
+    {
+        let mut request = Request::builder().method(method).uri(uri);
+        if let Some(token) = token {
+            request = request.header(header::AUTHORIZATION, format!("Bearer {token}"));
+        }
+        let request = match body {
+            Some(body) => request
+                .header(header::CONTENT_TYPE, "application/json")
+                .body(HttpBody::from(body.to_string())),
+            None => request.body(HttpBody::empty()),
+        };
+
+        let response = router.clone().oneshot(request.unwrap()).await.unwrap();
+        let status = response.status();
+        let mut body = response.into_body();
+        let mut bytes = Vec::new();
+        while let Some(chunk) = body.data().await {
+            bytes.extend_from_slice(&chunk.unwrap());
+        }
+
+        let json = if bytes.is_empty() {
+            serde_json::Value::Null
+        } else {
+            serde_json::from_slice(&bytes).unwrap()
+        };
+        (status, json)
+    }
+
+    #[tokio::test]
+    async fn mirrors_the_grpc_service() {
+        let router = gateway();
+
+        let credentials = serde_json::json!({ "email": "a@example.com", "password": "password" });
+        let (status, created) = call(&router, "POST", "/users", None, Some(credentials)).await;
+        assert_eq!(status, StatusCode::OK);
+        let id = created["id"].as_i64().unwrap();
+        let token = created["tokens"]["access_token"]
+            .as_str()
+            .unwrap()
+            .to_owned();
+
+        let (status, user) =
+            call(&router, "GET", &format!("/users/{id}"), Some(&token), None).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(user["email"], "a@example.com");
+
+        let (status, balance) = call(
+            &router,
+            "GET",
+            &format!("/balances?other_user_id={id}"),
+            Some(&token),
+            None,
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(balance["owed_cents"], 0);
+
+        let (status, error) = call(&router, "GET", "/expenses/1", None, None).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        assert_eq!(error["code"], "UNAUTHENTICATED");
+
+        let (status, error) = call(&router, "GET", "/expenses/0", Some(&token), None).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        assert_eq!(error["code"], "NOT_FOUND");
+
+        let (status, error) = call(&router, "GET", "/expenses/abc", Some(&token), None).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(error["code"], "INVALID_ARGUMENT");
+
+        // Leaving out the decision must not confirm the payment.
+        let (status, error) = call(
+            &router,
+            "POST",
+            "/payments/1/review",
+            Some(&token),
+            Some(serde_json::json!({})),
+        )
+        .await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(error["message"], "decision must be specified");
+
+        let taken = serde_json::json!({ "email": "a@example.com", "password": "password" });
+        let (status, error) = call(&router, "POST", "/users", None, Some(taken)).await;
+        assert_eq!(status, StatusCode::CONFLICT);
+        assert_eq!(error["code"], "ALREADY_EXISTS");
+
+        let (status, _) = call(&router, "DELETE", "/sessions/current", Some(&token), None).await;
+        assert_eq!(status, StatusCode::NO_CONTENT);
+    }
+
+    #[tokio::test]
+    async fn shares_the_grpc_layers() {
+        let db = db::test::db();
+        let tokens = Tokens::new("secret");
+        let one = NonZeroU32::new(1).unwrap();
+        let layers = super::super::Layers::new(tokens.clone(), one, one);
+        let gateway = || Gateway {
+            db: db.clone(),
+            lightning: None,
+            tokens: tokens.clone(),
+        };
+        // Stands in for the gRPC listener, which gets its own clone of the layers.
+        let other = service(gateway(), layers.0.clone());
+        let service = service(gateway(), layers.0);
+
+        let credentials = serde_json::json!({ "email": "a@example.com", "password": "password" });
+        let (_, created) = call(&service, "POST", "/users", None, Some(credentials)).await;
+        let id = created["id"].as_i64().unwrap();
+        let token = created["tokens"]["access_token"].as_str().unwrap();
+
+        let ok = metrics::count("GET /users/:id", "Ok");
+
+        let uri = format!("/users/{id}");
+        let (status, _) = call(&service, "GET", &uri, Some(token), None).await;
+        assert_eq!(status, StatusCode::OK);
+
+        let (status, error) = call(&other, "GET", &uri, Some(token), None).await;
+        assert_eq!(status, StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(error["code"], "RESOURCE_EXHAUSTED");
+
+        assert_eq!(metrics::count("GET /users/:id", "Ok"), ok + 1);
+    }
+}
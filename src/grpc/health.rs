@@ -0,0 +1,161 @@
+use std::{pin::Pin, time::Duration};
+
+use futures::Stream;
+use tokio::sync::watch;
+use tonic::{Request, Response, Status};
+
+use self::proto::{
+    health_check_response::ServingStatus, health_server::Health, HealthCheckRequest,
+    HealthCheckResponse,
+};
+
+pub mod proto {
+    tonic::include_proto!("grpc.health.v1");
+}
+
+/// How long the database may take to hand out a connection and answer `SELECT 1`.
+const READINESS_TIMEOUT: Duration = Duration::from_secs(2);
+/// How often `Watch` re-checks the database.
+const WATCH_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Services whose health is reported; the empty name stands for the whole server.
+const SERVICES: &[&str] = &["", "splitwiser.Splitwiser", "splitwiser.v1.Splitwiser"];
+
+/// Implements `grpc.health.v1.Health`. Every service is SERVING while the database is reachable
+/// and NOT_SERVING once shutdown begins.
+#[derive(Clone)]
+pub(super) struct HealthService {
+    pub(super) db: db::Db,
+    pub(super) shutdown: watch::Receiver<bool>,
+}
+
+impl HealthService {
+    async fn status(&self, service: &str) -> ServingStatus {
+        if !SERVICES.contains(&service) {
+            ServingStatus::ServiceUnknown
+        } else if *self.shutdown.borrow() || !self.db.ping(READINESS_TIMEOUT).await {
+            ServingStatus::NotServing
+        } else {
+            ServingStatus::Serving
+        }
+    }
+}
+
+fn response(status: ServingStatus) -> HealthCheckResponse {
+    HealthCheckResponse {
+        status: status.into(),
+    }
+}
+
+#[tonic::async_trait]
+impl Health for HealthService {
+    async fn check(
+        &self,
+        request: Request<HealthCheckRequest>,
+    ) -> Result<Response<HealthCheckResponse>, Status> {
+        match self.status(&request.into_inner().service).await {
+            ServingStatus::ServiceUnknown => Err(Status::not_found("Unknown service")),
+            status => Ok(Response::new(response(status))),
+        }
+    }
+
+    type WatchStream =
+        Pin<Box<dyn Stream<Item = Result<HealthCheckResponse, Status>> + Send + 'static>>;
+
+    /// Sends the current status, then every change to it. Ends with NOT_SERVING once the
+    /// shutdown flag can no longer change.
+    async fn watch(
+        &self,
+        request: Request<HealthCheckRequest>,
+    ) -> Result<Response<Self::WatchStream>, Status> {
+        let service = request.into_inner().service;
+        let state = Some((self.clone(), service, None));
+
+        let stream = futures::stream::unfold(state, |state| async move {
+            let (mut health, service, last) = state?;
+
+            loop {
+                let status = health.status(&service).await;
+                if last != Some(status) {
+                    return Some((Ok(response(status)), Some((health, service, Some(status)))));
+                }
+
+                let changed = tokio::time::timeout(WATCH_INTERVAL, health.shutdown.changed());
+                if let Ok(Err(_)) = changed.await {
+                    // The sender is gone, so `changed` would return at once from now on.
+                    return (last != Some(ServingStatus::NotServing))
+                        .then(|| (Ok(response(ServingStatus::NotServing)), None));
+                }
+            }
+        });
+
+        Ok(Response::new(Box::pin(stream)))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use futures::StreamExt;
+
+    use super::*;
+
+    fn request(service: &str) -> Request<HealthCheckRequest> {
+        Request::new(HealthCheckRequest {
+            service: service.to_owned(),
+        })
+    }
+
+    #[tokio::test]
+    async fn reports_not_serving_on_shutdown() {
+        let (tx, shutdown) = watch::channel(false);
+        let health = HealthService {
+            db: db::test::db(),
+            shutdown,
+        };
+
+        for service in SERVICES {
+            let status = health.check(request(service)).await.unwrap().into_inner();
+            assert_eq!(status.status(), ServingStatus::Serving);
+        }
+
+        let unknown = health.check(request("other.Service")).await.unwrap_err();
+        assert_eq!(unknown.code(), tonic::Code::NotFound);
+
+        let mut updates = health.watch(request("")).await.unwrap().into_inner();
+        let first = updates.next().await.unwrap().unwrap();
+        assert_eq!(first.status(), ServingStatus::Serving);
+
+        tx.send(true).unwrap();
+
+        let second = updates.next().await.unwrap().unwrap();
+        assert_eq!(second.status(), ServingStatus::NotServing);
+        let status = health.check(request("")).await.unwrap().into_inner();
+        assert_eq!(status.status(), ServingStatus::NotServing);
+    }
+
+    #[tokio::test]
+    async fn ends_watch_when_the_shutdown_sender_is_dropped() {
+        let (tx, shutdown) = watch::channel(false);
+        let health = HealthService {
+            db: db::test::db(),
+            shutdown,
+        };
+
+        let mut updates = health.watch(request("")).await.unwrap().into_inner();
+        let first = updates.next().await.unwrap().unwrap();
+        assert_eq!(first.status(), ServingStatus::Serving);
+
+        drop(tx);
+
+        let last = tokio::time::timeout(WATCH_INTERVAL, updates.next())
+            .await
+            .unwrap()
+            .unwrap()
+            .unwrap();
+        assert_eq!(last.status(), ServingStatus::NotServing);
+        assert!(tokio::time::timeout(WATCH_INTERVAL, updates.next())
+            .await
+            .unwrap()
+            .is_none());
+    }
+}
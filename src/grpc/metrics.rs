@@ -0,0 +1,267 @@
+use std::{
+    collections::HashSet,
+    pin::Pin,
+    sync::Arc,
+    task::{Context, Poll},
+    time::Instant,
+};
+
+use futures::{future::BoxFuture, ready};
+use once_cell::sync::Lazy;
+use prometheus::{register_histogram_vec, register_int_counter_vec, HistogramVec, IntCounterVec};
+use tonic::{
+    body::BoxBody,
+    codegen::{
+        http::{self, HeaderMap},
+        Body, Bytes,
+    },
+    Code, Status,
+};
+use tower::{Layer, Service};
+
+/// Label for paths that are not RPCs, so arbitrary requests cannot add series.
+const UNKNOWN_METHOD: &str = "unknown";
+
+static REQUESTS: Lazy<IntCounterVec> = Lazy::new(|| {
+    register_int_counter_vec!(
+        "splitwiser_rpc_requests_total",
+        "Finished RPCs by method and gRPC status code.",
+        &["method", "code"]
+    )
+    .unwrap()
+});
+
+static DURATION_SECONDS: Lazy<HistogramVec> = Lazy::new(|| {
+    register_histogram_vec!(
+        "splitwiser_rpc_duration_seconds",
+        "Time from receiving an RPC until its status is sent.",
+        &["method"]
+    )
+    .unwrap()
+});
+
+/// Labels of a response that is not an RPC, such as a call to the JSON gateway, whose status is in
+/// neither its headers nor its trailers.
+#[derive(Debug, Clone)]
+pub(super) struct Labels {
+    pub(super) method: String,
+    pub(super) code: Code,
+}
+
+/// Counts RPCs by method and status code and observes their latency. The status is read from the
+/// response headers for trailers-only responses and from the trailers otherwise, so latency covers
+/// the whole response body. Responses dropped before their trailers count as `Cancelled`.
+#[derive(Clone)]
+pub(super) struct MetricsLayer {
+    methods: Arc<HashSet<String>>,
+}
+
+impl MetricsLayer {
+    pub(super) fn new() -> Self {
+        Self {
+            methods: Arc::new(super::reflection::method_paths()),
+        }
+    }
+}
+
+impl<S> Layer<S> for MetricsLayer {
+    type Service = Metrics<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        Metrics {
+            inner,
+            methods: self.methods.clone(),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub(super) struct Metrics<S> {
+    inner: S,
+    methods: Arc<HashSet<String>>,
+}
+
+impl<S, B> Service<http::Request<B>> for Metrics<S>
+where
+    S: Service<http::Request<B>, Response = http::Response<BoxBody>>,
+    S::Future: Send + 'static,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = BoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: http::Request<B>) -> Self::Future {
+        let path = request.uri().path();
+        let method = if self.methods.contains(path) {
+            path.to_owned()
+        } else {
+            UNKNOWN_METHOD.to_owned()
+        };
+        let mut observation = Observation {
+            method,
+            started: Instant::now(),
+        };
+        let response = self.inner.call(request);
+
+        Box::pin(async move {
+            let response = response.await;
+
+            let response = match response {
+                Ok(response) => response,
+                Err(e) => {
+                    observation.finish(Code::Internal);
+                    return Err(e);
+                }
+            };
+
+            if let Some(labels) = response.extensions().get::<Labels>() {
+                observation.method = labels.method.clone();
+                observation.finish(labels.code);
+                return Ok(response);
+            }
+
+            if let Some(code) = status_code(response.headers()) {
+                observation.finish(code);
+                return Ok(response);
+            }
+
+            let (parts, body) = response.into_parts();
+            let body = MetricsBody {
+                inner: body,
+                observation: Some(observation),
+            };
+
+            Ok(http::Response::from_parts(parts, body.boxed_unsync()))
+        })
+    }
+}
+
+struct Observation {
+    method: String,
+    started: Instant,
+}
+
+impl Observation {
+    fn finish(&mut self, code: Code) {
+        REQUESTS
+            .with_label_values(&[&self.method, &format!("{code:?}")])
+            .inc();
+        DURATION_SECONDS
+            .with_label_values(&[&self.method])
+            .observe(self.started.elapsed().as_secs_f64());
+    }
+}
+
+fn status_code(headers: &HeaderMap) -> Option<Code> {
+    headers
+        .get("grpc-status")
+        .map(|code| Code::from_bytes(code.as_bytes()))
+}
+
+/// Passes `inner` through, finishing the observation once its trailers arrive.
+struct MetricsBody {
+    inner: BoxBody,
+    observation: Option<Observation>,
+}
+
+impl MetricsBody {
+    fn finish(&mut self, code: Code) {
+        if let Some(mut observation) = self.observation.take() {
+            observation.finish(code);
+        }
+    }
+}
+
+impl Body for MetricsBody {
+    type Data = Bytes;
+    type Error = Status;
+
+    fn poll_data(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Self::Data, Self::Error>>> {
+        Pin::new(&mut self.inner).poll_data(cx)
+    }
+
+    fn poll_trailers(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<Option<HeaderMap>, Self::Error>> {
+        let trailers = ready!(Pin::new(&mut self.inner).poll_trailers(cx));
+
+        let code = match &trailers {
+            Ok(trailers) => trailers.as_ref().and_then(status_code).unwrap_or(Code::Ok),
+            Err(status) => status.code(),
+        };
+        self.finish(code);
+
+        Poll::Ready(trailers)
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.inner.is_end_stream()
+    }
+}
+
+impl Drop for MetricsBody {
+    fn drop(&mut self) {
+        self.finish(Code::Cancelled);
+    }
+}
+
+/// How many RPCs of `method` finished with `code` so far.
+#[cfg(test)]
+pub(super) fn count(method: &str, code: &str) -> u64 {
+    REQUESTS.with_label_values(&[method, code]).get()
+}
+
+#[cfg(test)]
+mod test {
+    use futures::future;
+    use tonic::codegen::empty_body;
+
+    use super::*;
+
+    async fn call(path: &str, trailers_only: bool) {
+        let mut service =
+            MetricsLayer::new().layer(tower::service_fn(move |_: http::Request<()>| {
+                let status = Status::not_found("Missing");
+                let response = if trailers_only {
+                    status.to_http()
+                } else {
+                    let mut response = http::Response::new(empty_body());
+                    response
+                        .headers_mut()
+                        .insert("content-type", "application/grpc".parse().unwrap());
+                    response
+                };
+                future::ok::<_, std::convert::Infallible>(response)
+            }));
+
+        let request = http::Request::builder().uri(path).body(()).unwrap();
+        let mut body = service.call(request).await.unwrap().into_body();
+        while body.data().await.is_some() {}
+        body.trailers().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn counts_rpcs_by_method_and_code() {
+        const GET_USER: &str = "/splitwiser.Splitwiser/GetUser";
+
+        let not_found = count(GET_USER, "NotFound");
+        let ok = count(GET_USER, "Ok");
+        let unknown = count(UNKNOWN_METHOD, "NotFound");
+
+        call(GET_USER, true).await;
+        call(GET_USER, false).await;
+        call("/splitwiser.Splitwiser/Made-Up", true).await;
+
+        assert_eq!(count(GET_USER, "NotFound"), not_found + 1);
+        assert_eq!(count(GET_USER, "Ok"), ok + 1);
+        assert_eq!(count(UNKNOWN_METHOD, "NotFound"), unknown + 1);
+    }
+}
@@ -0,0 +1,76 @@
+use db::{queries::users::Profile, types::UserId};
+use tonic::Status;
+
+use crate::features::profile::{self, ProfileError};
+
+use super::{
+    logged,
+    proto::{SearchUsersRequest, UpdateProfileRequest, User, Users},
+};
+
+pub(super) async fn update(
+    db: &db::Db,
+    caller: UserId,
+    request: UpdateProfileRequest,
+) -> Result<User, Status> {
+    profile::update(
+        db,
+        caller,
+        profile::UpdateProfileParams {
+            display_name: request.display_name,
+            email: request.email,
+            locale: request.locale,
+            timezone: request.timezone,
+            avatar_url: request.avatar_url,
+        },
+    )
+    .await
+    .map(into_proto)
+    .map_err(into_status)
+}
+
+pub(super) async fn get(db: &db::Db, caller: UserId, id: i32) -> Result<User, Status> {
+    profile::get(db, caller, id)
+        .await
+        .map(into_proto)
+        .map_err(into_status)
+}
+
+pub(super) async fn search(
+    db: &db::Db,
+    caller: UserId,
+    request: SearchUsersRequest,
+) -> Result<Users, Status> {
+    profile::search(db, caller, &request.query, request.limit)
+        .await
+        .map(|profiles| Users {
+            users: profiles.into_iter().map(into_proto).collect(),
+        })
+        .map_err(into_status)
+}
+
+pub(super) fn into_status(e: ProfileError) -> Status {
+    match e {
+        ProfileError::InvalidDisplayName
+        | ProfileError::InvalidEmail
+        | ProfileError::InvalidLocale
+        | ProfileError::InvalidTimezone
+        | ProfileError::InvalidAvatarUrl
+        | ProfileError::QueryTooShort => Status::invalid_argument(e.to_string()),
+        ProfileError::EmailTaken => Status::already_exists(e.to_string()),
+        ProfileError::UserNotFound => Status::not_found(e.to_string()),
+        ProfileError::DbError(_) => logged(Status::internal("Database error"), e),
+    }
+}
+
+fn into_proto(profile: Profile) -> User {
+    User {
+        id: *profile.id,
+        display_name: profile.display_name,
+        email: profile.email,
+        locale: profile.locale,
+        timezone: profile.timezone,
+        avatar_url: profile.avatar_url,
+        created_at: profile.created_at.unix_timestamp(),
+    }
+}
@@ -0,0 +1,327 @@
+use std::{
+    collections::HashMap,
+    net::IpAddr,
+    num::NonZeroU32,
+    sync::{Arc, Mutex},
+    task::{Context, Poll},
+    time::{Duration, Instant},
+};
+
+use futures::future::{self, Either, Ready};
+use tonic::{
+    body::BoxBody,
+    codegen::http,
+    metadata::MetadataValue,
+    transport::server::{TcpConnectInfo, TlsConnectInfo},
+    Status,
+};
+use tower::{Layer, Service};
+
+use crate::auth::Tokens;
+
+/// Above this many tracked keys, buckets that have refilled completely are dropped, at most once
+/// per [`SWEEP_INTERVAL`] so that the sweep costs little per request.
+const MAX_IDLE_BUCKETS: usize = 10_000;
+const SWEEP_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Probed often from a few addresses by load balancers and orchestrators, so exempt from the
+/// per-IP limit.
+const HEALTH_PATH: &str = "/grpc.health.v1.Health/";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum Key {
+    User(i32),
+    Ip(IpAddr),
+}
+
+struct Bucket {
+    tokens: f64,
+    updated_at: Instant,
+}
+
+struct Buckets {
+    by_key: HashMap<Key, Bucket>,
+    swept_at: Instant,
+}
+
+/// Token buckets that refill `per_minute` requests every minute, allowing bursts of the same
+/// size.
+struct Limiter {
+    per_minute: NonZeroU32,
+    buckets: Mutex<Buckets>,
+}
+
+impl Limiter {
+    fn new(per_minute: NonZeroU32) -> Self {
+        Self {
+            per_minute,
+            buckets: Mutex::new(Buckets {
+                by_key: HashMap::new(),
+                swept_at: Instant::now(),
+            }),
+        }
+    }
+
+    /// Takes a token from the bucket of `key`, or returns how long until one is available.
+    fn check(&self, key: Key, now: Instant) -> Result<(), Duration> {
+        let capacity = f64::from(self.per_minute.get());
+        let per_second = capacity / 60.0;
+
+        let mut buckets = self.buckets.lock().expect("Rate limiter lock poisoned");
+
+        if buckets.by_key.len() > MAX_IDLE_BUCKETS
+            && now.duration_since(buckets.swept_at) >= SWEEP_INTERVAL
+        {
+            buckets.by_key.retain(|_, bucket| {
+                bucket.tokens + now.duration_since(bucket.updated_at).as_secs_f64() * per_second
+                    < capacity
+            });
+            buckets.swept_at = now;
+        }
+
+        let bucket = buckets.by_key.entry(key).or_insert(Bucket {
+            tokens: capacity,
+            updated_at: now,
+        });
+
+        let elapsed = now.duration_since(bucket.updated_at).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * per_second).min(capacity);
+        bucket.updated_at = now;
+
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            Ok(())
+        } else {
+            Err(Duration::from_secs_f64((1.0 - bucket.tokens) / per_second))
+        }
+    }
+}
+
+/// Limits requests per authenticated user, falling back to the peer IP for anonymous requests.
+/// Runs before [`super::auth::AuthLayer`], so it verifies bearer tokens itself; requests with
+/// invalid tokens count against their IP and are then rejected by that layer.
+#[derive(Clone)]
+pub(super) struct RateLimitLayer {
+    tokens: Tokens,
+    per_user: Arc<Limiter>,
+    per_ip: Arc<Limiter>,
+}
+
+impl RateLimitLayer {
+    pub(super) fn new(
+        tokens: Tokens,
+        per_user_per_minute: NonZeroU32,
+        per_ip_per_minute: NonZeroU32,
+    ) -> Self {
+        Self {
+            tokens,
+            per_user: Arc::new(Limiter::new(per_user_per_minute)),
+            per_ip: Arc::new(Limiter::new(per_ip_per_minute)),
+        }
+    }
+
+    fn key<B>(&self, request: &http::Request<B>) -> Option<Key> {
+        let authorization = request
+            .headers()
+            .get("authorization")
+            .and_then(|header| header.to_str().ok());
+        let extensions = request.extensions();
+        let peer = extensions
+            .get::<TcpConnectInfo>()
+            .or_else(|| {
+                extensions
+                    .get::<TlsConnectInfo<TcpConnectInfo>>()
+                    .map(TlsConnectInfo::get_ref)
+            })
+            .and_then(TcpConnectInfo::remote_addr)
+            .map(|addr| addr.ip());
+
+        self.key_for(authorization, peer)
+    }
+
+    fn key_for(&self, authorization: Option<&str>, peer: Option<IpAddr>) -> Option<Key> {
+        authorization
+            .and_then(|header| header.strip_prefix("Bearer "))
+            .and_then(|token| self.tokens.verify(token).ok())
+            .map(|caller| Key::User(*caller.user_id))
+            .or(peer.map(Key::Ip))
+    }
+
+    fn check<B>(&self, request: &http::Request<B>) -> Result<(), Duration> {
+        self.check_key(self.key(request), request.uri().path(), Instant::now())
+    }
+
+    fn check_key(&self, key: Option<Key>, path: &str, now: Instant) -> Result<(), Duration> {
+        match key {
+            Some(key @ Key::User(_)) => self.per_user.check(key, now),
+            Some(Key::Ip(_)) if path.starts_with(HEALTH_PATH) => Ok(()),
+            Some(key @ Key::Ip(_)) => self.per_ip.check(key, now),
+            None => Ok(()),
+        }
+    }
+}
+
+impl<S> Layer<S> for RateLimitLayer {
+    type Service = RateLimit<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        RateLimit {
+            inner,
+            layer: self.clone(),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub(super) struct RateLimit<S> {
+    inner: S,
+    layer: RateLimitLayer,
+}
+
+impl<S, B> Service<http::Request<B>> for RateLimit<S>
+where
+    S: Service<http::Request<B>, Response = http::Response<BoxBody>>,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = Either<Ready<Result<Self::Response, Self::Error>>, S::Future>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: http::Request<B>) -> Self::Future {
+        match self.layer.check(&request) {
+            Ok(()) => Either::Right(self.inner.call(request)),
+            Err(retry_after) => Either::Left(future::ok(exhausted(retry_after).to_http())),
+        }
+    }
+}
+
+fn exhausted(retry_after: Duration) -> Status {
+    let seconds = retry_after
+        .as_secs()
+        .saturating_add(u64::from(retry_after.subsec_nanos() > 0));
+
+    let mut status = Status::resource_exhausted("Too many requests");
+    status
+        .metadata_mut()
+        .insert("retry-after", MetadataValue::from(seconds));
+    status
+}
+
+#[cfg(test)]
+mod test {
+    use std::net::Ipv4Addr;
+
+    use super::*;
+
+    #[test]
+    fn allows_bursts_then_refills() {
+        let limiter = Limiter::new(NonZeroU32::new(60).unwrap());
+        let key = Key::User(1);
+        let now = Instant::now();
+
+        for _ in 0..60 {
+            assert!(limiter.check(key, now).is_ok());
+        }
+        assert_eq!(limiter.check(key, now), Err(Duration::from_secs(1)));
+
+        // Other callers have their own buckets.
+        assert!(limiter
+            .check(Key::Ip(Ipv4Addr::LOCALHOST.into()), now)
+            .is_ok());
+
+        let later = now + Duration::from_millis(1500);
+        assert!(limiter.check(key, later).is_ok());
+        assert_eq!(limiter.check(key, later), Err(Duration::from_millis(500)));
+    }
+
+    #[test]
+    fn sweeps_refilled_buckets_at_most_once_per_interval() {
+        let limiter = Limiter::new(NonZeroU32::new(60).unwrap());
+        let now = Instant::now();
+
+        for user in 0..=MAX_IDLE_BUCKETS as i32 {
+            limiter.check(Key::User(user), now).unwrap();
+        }
+
+        let soon = now + SWEEP_INTERVAL / 2;
+        for _ in 0..60 {
+            limiter.check(Key::User(-1), soon).unwrap();
+        }
+        assert_eq!(
+            limiter.buckets.lock().unwrap().by_key.len(),
+            MAX_IDLE_BUCKETS + 2
+        );
+
+        let later = now + SWEEP_INTERVAL;
+        limiter.check(Key::User(-2), later).unwrap();
+        // Only the bucket drained half an interval ago, and the new one, remain.
+        assert_eq!(limiter.buckets.lock().unwrap().by_key.len(), 2);
+    }
+
+    #[test]
+    fn health_checks_skip_the_ip_limit() {
+        let one = NonZeroU32::new(1).unwrap();
+        let layer = RateLimitLayer::new(Tokens::new("secret"), one, one);
+        let ip = Some(Key::Ip(Ipv4Addr::LOCALHOST.into()));
+        let now = Instant::now();
+
+        for _ in 0..3 {
+            assert!(layer
+                .check_key(ip, "/grpc.health.v1.Health/Check", now)
+                .is_ok());
+        }
+
+        assert!(layer
+            .check_key(ip, "/splitwiser.Splitwiser/Login", now)
+            .is_ok());
+        assert!(layer
+            .check_key(ip, "/splitwiser.Splitwiser/Login", now)
+            .is_err());
+    }
+
+    #[test]
+    fn keys_by_user_then_ip() {
+        let tokens = Tokens::new("secret");
+        let one = NonZeroU32::new(1).unwrap();
+        let layer = RateLimitLayer::new(tokens.clone(), one, one);
+        let peer = Some(Ipv4Addr::LOCALHOST.into());
+
+        let caller = crate::auth::Caller {
+            user_id: 3.into(),
+            session_id: 5.into(),
+        };
+        let token = tokens.issue(caller, time::OffsetDateTime::now_utc());
+        let forged = Tokens::new("other").issue(caller, time::OffsetDateTime::now_utc());
+
+        assert_eq!(
+            layer.key_for(Some(&format!("Bearer {token}")), peer),
+            Some(Key::User(3))
+        );
+        assert_eq!(
+            layer.key_for(Some(&format!("Bearer {forged}")), peer),
+            Some(Key::Ip(Ipv4Addr::LOCALHOST.into()))
+        );
+        assert_eq!(
+            layer.key_for(None, peer),
+            Some(Key::Ip(Ipv4Addr::LOCALHOST.into()))
+        );
+        assert_eq!(layer.key_for(None, None), None);
+    }
+
+    #[test]
+    fn exhausted_status_carries_retry_after() {
+        let status = exhausted(Duration::from_millis(1500));
+
+        assert_eq!(status.code(), tonic::Code::ResourceExhausted);
+        assert_eq!(status.metadata().get("retry-after").unwrap(), "2");
+
+        let status = exhausted(Duration::MAX);
+        assert_eq!(
+            status.metadata().get("retry-after").unwrap(),
+            &u64::MAX.to_string()
+        );
+    }
+}
@@ -0,0 +1,326 @@
+// `Streaming` yields `Result<_, Status>`, which clippy deems too large.
+#![allow(clippy::result_large_err)]
+
+use std::{
+    collections::{HashMap, HashSet},
+    pin::Pin,
+    sync::Arc,
+};
+
+use futures::{Stream, StreamExt};
+use prost::Message;
+use prost_types::{DescriptorProto, FileDescriptorProto, FileDescriptorSet};
+use tonic::{Request, Response, Status, Streaming};
+
+use self::proto::{
+    server_reflection_request::MessageRequest, server_reflection_response::MessageResponse,
+    server_reflection_server::ServerReflection, ErrorResponse, FileDescriptorResponse,
+    ListServiceResponse, ServerReflectionRequest, ServerReflectionResponse, ServiceResponse,
+};
+
+pub mod proto {
+    #![allow(clippy::enum_variant_names)]
+
+    tonic::include_proto!("grpc.reflection.v1alpha");
+}
+
+/// Every `.proto` file compiled by `build.rs`, including its imports, each once.
+const DESCRIPTORS: &[u8] = include_bytes!(concat!(env!("OUT_DIR"), "/descriptors.bin"));
+
+/// Services listed to clients. `SplitwiserAdmin` lives in the same file but is served on the
+/// admin socket, so it is stripped from the index altogether.
+const SERVICES: &[&str] = &[
+    "splitwiser.Splitwiser",
+    "splitwiser.v1.Splitwiser",
+    "grpc.health.v1.Health",
+    "grpc.reflection.v1alpha.ServerReflection",
+];
+
+/// Files by name and, for each fully-qualified symbol, the name of the file declaring it.
+struct Index {
+    files: HashMap<String, FileDescriptorProto>,
+    symbols: HashMap<String, String>,
+}
+
+impl Index {
+    fn load() -> Self {
+        let mut index = Self {
+            files: HashMap::new(),
+            symbols: HashMap::new(),
+        };
+
+        for mut file in descriptor_files() {
+            let name = file.name().to_owned();
+            let prefix = file.package().to_owned();
+
+            file.service
+                .retain(|service| SERVICES.contains(&qualify(&prefix, service.name()).as_str()));
+
+            for service in &file.service {
+                let service_name = qualify(&prefix, service.name());
+                for method in &service.method {
+                    index.add(qualify(&service_name, method.name()), &name);
+                }
+                index.add(service_name, &name);
+            }
+            for message in &file.message_type {
+                index.add_message(&prefix, message, &name);
+            }
+            for enumeration in &file.enum_type {
+                index.add(qualify(&prefix, enumeration.name()), &name);
+            }
+
+            index.files.insert(name, file);
+        }
+
+        index
+    }
+
+    fn add(&mut self, symbol: String, file: &str) {
+        self.symbols.insert(symbol, file.to_owned());
+    }
+
+    fn add_message(&mut self, prefix: &str, message: &DescriptorProto, file: &str) {
+        let message_name = qualify(prefix, message.name());
+
+        for nested in &message.nested_type {
+            self.add_message(&message_name, nested, file);
+        }
+        for enumeration in &message.enum_type {
+            self.add(qualify(&message_name, enumeration.name()), file);
+        }
+        self.add(message_name, file);
+    }
+
+    /// The encoded file followed by everything it transitively imports.
+    fn with_dependencies(&self, name: &str) -> Option<Vec<Vec<u8>>> {
+        let mut pending = vec![name];
+        let mut seen = Vec::new();
+
+        while let Some(name) = pending.pop() {
+            if seen.contains(&name) {
+                continue;
+            }
+            let file = self.files.get(name)?;
+            pending.extend(file.dependency.iter().map(String::as_str));
+            seen.push(name);
+        }
+
+        Some(
+            seen.into_iter()
+                .map(|name| self.files[name].encode_to_vec())
+                .collect(),
+        )
+    }
+
+    fn respond(&self, request: ServerReflectionRequest) -> ServerReflectionResponse {
+        let response = match &request.message_request {
+            Some(MessageRequest::ListServices(_)) => {
+                MessageResponse::ListServicesResponse(ListServiceResponse {
+                    service: SERVICES
+                        .iter()
+                        .map(|&name| ServiceResponse {
+                            name: name.to_owned(),
+                        })
+                        .collect(),
+                })
+            }
+            Some(MessageRequest::FileByFilename(name)) => {
+                files(self.with_dependencies(name), "File not found")
+            }
+            Some(MessageRequest::FileContainingSymbol(symbol)) => files(
+                self.symbols
+                    .get(symbol)
+                    .and_then(|name| self.with_dependencies(name)),
+                "Symbol not found",
+            ),
+            Some(MessageRequest::FileContainingExtension(_)) => {
+                error(tonic::Code::NotFound, "Extension not found")
+            }
+            Some(MessageRequest::AllExtensionNumbersOfType(_)) => {
+                error(tonic::Code::Unimplemented, "Extensions are not supported")
+            }
+            None => error(tonic::Code::InvalidArgument, "Empty request"),
+        };
+
+        ServerReflectionResponse {
+            valid_host: request.host.clone(),
+            original_request: Some(request),
+            message_response: Some(response),
+        }
+    }
+}
+
+/// The HTTP path of every RPC in the embedded descriptors, such as
+/// `/splitwiser.Splitwiser/CreateUser`.
+pub(super) fn method_paths() -> HashSet<String> {
+    descriptor_files()
+        .flat_map(|file| {
+            let package = file.package().to_owned();
+            file.service.into_iter().flat_map(move |service| {
+                let service_name = qualify(&package, service.name());
+                service
+                    .method
+                    .into_iter()
+                    .map(move |method| format!("/{service_name}/{}", method.name()))
+            })
+        })
+        .collect()
+}
+
+fn descriptor_files() -> impl Iterator<Item = FileDescriptorProto> {
+    FileDescriptorSet::decode(DESCRIPTORS)
+        .expect("Invalid descriptor set")
+        .file
+        .into_iter()
+}
+
+fn qualify(prefix: &str, name: &str) -> String {
+    if prefix.is_empty() {
+        name.to_owned()
+    } else {
+        format!("{prefix}.{name}")
+    }
+}
+
+fn files(files: Option<Vec<Vec<u8>>>, not_found: &str) -> MessageResponse {
+    match files {
+        Some(file_descriptor_proto) => {
+            MessageResponse::FileDescriptorResponse(FileDescriptorResponse {
+                file_descriptor_proto,
+            })
+        }
+        None => error(tonic::Code::NotFound, not_found),
+    }
+}
+
+fn error(code: tonic::Code, message: &str) -> MessageResponse {
+    MessageResponse::ErrorResponse(ErrorResponse {
+        error_code: code as i32,
+        error_message: message.to_owned(),
+    })
+}
+
+/// Implements `grpc.reflection.v1alpha.ServerReflection` from the descriptors embedded at build
+/// time, so tools like `grpcurl` work without local copies of the protos. Written here rather
+/// than taken from `tonic-reflection` so that it serves both packages and can strip
+/// `SplitwiserAdmin` from them.
+pub(super) struct ReflectionService(Arc<Index>);
+
+impl ReflectionService {
+    pub(super) fn new() -> Self {
+        Self(Arc::new(Index::load()))
+    }
+}
+
+#[tonic::async_trait]
+impl ServerReflection for ReflectionService {
+    type ServerReflectionInfoStream =
+        Pin<Box<dyn Stream<Item = Result<ServerReflectionResponse, Status>> + Send + 'static>>;
+
+    async fn server_reflection_info(
+        &self,
+        request: Request<Streaming<ServerReflectionRequest>>,
+    ) -> Result<Response<Self::ServerReflectionInfoStream>, Status> {
+        let index = self.0.clone();
+        let stream = request
+            .into_inner()
+            .map(move |request| request.map(|request| index.respond(request)));
+
+        Ok(Response::new(Box::pin(stream)))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn respond(request: MessageRequest) -> MessageResponse {
+        Index::load()
+            .respond(ServerReflectionRequest {
+                host: String::new(),
+                message_request: Some(request),
+            })
+            .message_response
+            .unwrap()
+    }
+
+    fn file_names(response: MessageResponse) -> Vec<String> {
+        let MessageResponse::FileDescriptorResponse(response) = response else {
+            panic!("Expected files, got {response:?}");
+        };
+
+        response
+            .file_descriptor_proto
+            .iter()
+            .map(|bytes| {
+                FileDescriptorProto::decode(bytes.as_slice())
+                    .unwrap()
+                    .name()
+                    .to_owned()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn describes_served_files() {
+        let MessageResponse::ListServicesResponse(list) =
+            respond(MessageRequest::ListServices(String::new()))
+        else {
+            panic!("Expected a service list");
+        };
+        let names: Vec<_> = list.service.into_iter().map(|s| s.name).collect();
+        assert_eq!(names, SERVICES);
+
+        let expected = ["splitwiser.proto", "google/protobuf/empty.proto"];
+        for symbol in [
+            "splitwiser.Splitwiser",
+            "splitwiser.Splitwiser.CreateUser",
+            "splitwiser.AuditEvent.EntityType",
+        ] {
+            let files = file_names(respond(MessageRequest::FileContainingSymbol(
+                symbol.to_owned(),
+            )));
+            assert_eq!(files, expected);
+        }
+
+        let files = file_names(respond(MessageRequest::FileByFilename(
+            "grpc/health/v1/health.proto".to_owned(),
+        )));
+        assert_eq!(files, ["grpc/health/v1/health.proto"]);
+
+        for symbol in ["other.Thing", "splitwiser.SplitwiserAdmin"] {
+            let MessageResponse::ErrorResponse(missing) =
+                respond(MessageRequest::FileContainingSymbol(symbol.to_owned()))
+            else {
+                panic!("Expected an error");
+            };
+            assert_eq!(missing.error_code, tonic::Code::NotFound as i32);
+        }
+    }
+
+    #[test]
+    fn embeds_each_file_once() {
+        let names: Vec<_> = descriptor_files()
+            .map(|file| file.name().to_owned())
+            .collect();
+        let unique: HashSet<_> = names.iter().collect();
+
+        assert_eq!(unique.len(), names.len());
+        assert!(unique.contains(&"google/protobuf/empty.proto".to_owned()));
+    }
+
+    #[test]
+    fn strips_the_admin_service_from_files() {
+        let MessageResponse::FileDescriptorResponse(response) = respond(
+            MessageRequest::FileByFilename("splitwiser.proto".to_owned()),
+        ) else {
+            panic!("Expected files");
+        };
+
+        let file =
+            FileDescriptorProto::decode(response.file_descriptor_proto[0].as_slice()).unwrap();
+        let services: Vec<_> = file.service.iter().map(|s| s.name()).collect();
+        assert_eq!(services, ["Splitwiser"]);
+    }
+}
@@ -0,0 +1,129 @@
+use db::types::{SessionId, UserId};
+use tonic::Status;
+
+use crate::{
+    auth::Tokens,
+    features::session::{self, CredentialsParams, IssuedSession, SessionError},
+};
+
+use super::{
+    logged,
+    proto::{
+        AuthTokens, CreateUserRequest, CreatedUser, Id, LoginRequest, RefreshSessionRequest,
+        Session, Sessions,
+    },
+};
+
+pub(super) async fn create_user(
+    db: &db::Db,
+    tokens: &Tokens,
+    request: CreateUserRequest,
+) -> Result<CreatedUser, Status> {
+    session::register(
+        db,
+        tokens,
+        CredentialsParams {
+            email: request.email,
+            password: request.password,
+            device: request.device,
+        },
+    )
+    .await
+    .map(|(id, issued)| CreatedUser {
+        id: *id,
+        tokens: Some(into_proto(issued)),
+    })
+    .map_err(into_status)
+}
+
+pub(super) async fn login(
+    db: &db::Db,
+    tokens: &Tokens,
+    request: LoginRequest,
+) -> Result<AuthTokens, Status> {
+    session::login(
+        db,
+        tokens,
+        CredentialsParams {
+            email: request.email,
+            password: request.password,
+            device: request.device,
+        },
+    )
+    .await
+    .map(into_proto)
+    .map_err(into_status)
+}
+
+pub(super) async fn refresh(
+    db: &db::Db,
+    tokens: &Tokens,
+    request: RefreshSessionRequest,
+) -> Result<AuthTokens, Status> {
+    session::refresh(db, tokens, &request.refresh_token)
+        .await
+        .map(into_proto)
+        .map_err(into_status)
+}
+
+pub(super) async fn logout(db: &db::Db, caller: UserId, current: SessionId) -> Result<(), Status> {
+    session::revoke(db, caller, *current)
+        .await
+        .map(|_| ())
+        .map_err(into_status)
+}
+
+pub(super) async fn list(
+    db: &db::Db,
+    caller: UserId,
+    current: SessionId,
+) -> Result<Sessions, Status> {
+    session::list(db, caller)
+        .await
+        .map(|sessions| Sessions {
+            sessions: sessions
+                .into_iter()
+                .map(|session| Session {
+                    id: *session.id,
+                    device: session.device,
+                    created_at: session.created_at.unix_timestamp(),
+                    refreshed_at: session.refreshed_at.unix_timestamp(),
+                    expires_at: session.expires_at.unix_timestamp(),
+                    current: *session.id == *current,
+                })
+                .collect(),
+        })
+        .map_err(into_status)
+}
+
+pub(super) async fn revoke(db: &db::Db, caller: UserId, id: i32) -> Result<Id, Status> {
+    session::revoke(db, caller, id)
+        .await
+        .map(|id| Id { id: *id })
+        .map_err(into_status)
+}
+
+pub(super) fn into_status(e: SessionError) -> Status {
+    match e {
+        SessionError::InvalidEmail | SessionError::InvalidPassword => {
+            Status::invalid_argument(e.to_string())
+        }
+        SessionError::EmailTaken => Status::already_exists(e.to_string()),
+        SessionError::InvalidCredentials | SessionError::InvalidRefreshToken => {
+            Status::unauthenticated(e.to_string())
+        }
+        SessionError::NotFound => Status::not_found(e.to_string()),
+        SessionError::DbError(_) => logged(Status::internal("Database error"), e),
+    }
+}
+
+fn into_proto(issued: IssuedSession) -> AuthTokens {
+    AuthTokens {
+        user_id: *issued.user_id,
+        session_id: *issued.session_id,
+        access_token: issued.access_token,
+        access_token_expires_at: issued.access_token_expires_at.unix_timestamp(),
+        refresh_token: issued.refresh_token,
+        refresh_token_expires_at: issued.refresh_token_expires_at.unix_timestamp(),
+    }
+}
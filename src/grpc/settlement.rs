@@ -0,0 +1,202 @@
+use db::{enums::SettlementInvoiceStatus, queries::settlement_invoices, types::UserId};
+use tonic::Status;
+
+use crate::{
+    features::settlement::{self, SettlementError},
+    lightning::LightningBackend,
+    qr::{self, QrError},
+};
+
+use super::{
+    logged,
+    proto::{
+        render_qr_code_request, settlement_invoice, BitcoinPaymentUri, GetBitcoinPaymentUriRequest,
+        GetPixPayloadRequest, Id, PixPayload, QrCode, RenderQrCodeRequest,
+        RequestSettlementInvoiceRequest, SetBitcoinAddressRequest, SetPixAccountRequest,
+        SettlementInvoice,
+    },
+};
+
+pub(super) async fn request_invoice(
+    db: &db::Db,
+    lightning: Option<&dyn LightningBackend>,
+    caller: UserId,
+    request: RequestSettlementInvoiceRequest,
+) -> Result<SettlementInvoice, Status> {
+    let lightning = lightning.ok_or_else(no_backend)?;
+
+    settlement::request_invoice(
+        db,
+        lightning,
+        caller,
+        settlement::RequestInvoiceParams {
+            payee_user_id: request.payee_user_id,
+            cents_per_btc: request.cents_per_btc,
+        },
+    )
+    .await
+    .map(into_proto)
+    .map_err(into_status)
+}
+
+pub(super) async fn get_invoice(
+    db: &db::Db,
+    lightning: Option<&dyn LightningBackend>,
+    caller: UserId,
+    id: i32,
+) -> Result<SettlementInvoice, Status> {
+    let lightning = lightning.ok_or_else(no_backend)?;
+
+    settlement::get_invoice(db, lightning, caller, id)
+        .await
+        .map(into_proto)
+        .map_err(into_status)
+}
+
+pub(super) async fn set_pix_account(
+    db: &db::Db,
+    caller: UserId,
+    request: SetPixAccountRequest,
+) -> Result<Id, Status> {
+    settlement::set_pix_account(
+        db,
+        caller,
+        settlement::SetPixAccountParams {
+            key: request.key,
+            merchant_name: request.merchant_name,
+            merchant_city: request.merchant_city,
+        },
+    )
+    .await
+    .map(|id| Id { id: *id })
+    .map_err(into_status)
+}
+
+pub(super) async fn pix_payload(
+    db: &db::Db,
+    caller: UserId,
+    request: GetPixPayloadRequest,
+) -> Result<PixPayload, Status> {
+    settlement::pix_payload(
+        db,
+        caller,
+        settlement::PixPayloadParams {
+            payee_user_id: request.payee_user_id,
+            txid: request.txid,
+            location: request.location,
+        },
+    )
+    .await
+    .map(|payload| PixPayload {
+        payload: payload.payload,
+        amount_cents: payload.amount_cents as u64,
+    })
+    .map_err(into_status)
+}
+
+pub(super) async fn set_bitcoin_address(
+    db: &db::Db,
+    caller: UserId,
+    request: SetBitcoinAddressRequest,
+) -> Result<Id, Status> {
+    settlement::set_bitcoin_address(db, caller, &request.address)
+        .await
+        .map(|_| Id { id: *caller })
+        .map_err(into_status)
+}
+
+pub(super) async fn bitcoin_payment_uri(
+    db: &db::Db,
+    caller: UserId,
+    request: GetBitcoinPaymentUriRequest,
+) -> Result<BitcoinPaymentUri, Status> {
+    settlement::bitcoin_payment_uri(
+        db,
+        caller,
+        settlement::BitcoinPaymentUriParams {
+            payee_user_id: request.payee_user_id,
+            cents_per_btc: request.cents_per_btc,
+        },
+    )
+    .await
+    .map(|uri| BitcoinPaymentUri {
+        uri: uri.uri,
+        amount_cents: uri.amount_cents as u64,
+        amount_sats: uri.amount_sats as u64,
+    })
+    .map_err(into_status)
+}
+
+pub(super) async fn render_qr_code(request: RenderQrCodeRequest) -> Result<QrCode, Status> {
+    let format = match request.format() {
+        render_qr_code_request::Format::Svg => qr::Format::Svg,
+        render_qr_code_request::Format::Png => qr::Format::Png,
+    };
+
+    let error_correction = match request.error_correction() {
+        render_qr_code_request::ErrorCorrection::Low => qr::ErrorCorrection::Low,
+        render_qr_code_request::ErrorCorrection::Medium => qr::ErrorCorrection::Medium,
+        render_qr_code_request::ErrorCorrection::Quartile => qr::ErrorCorrection::Quartile,
+        render_qr_code_request::ErrorCorrection::High => qr::ErrorCorrection::High,
+    };
+
+    match qr::render(&request.payload, error_correction, format) {
+        Ok(content) => Ok(QrCode {
+            content,
+            content_type: format.content_type().to_owned(),
+        }),
+        Err(e @ (QrError::EmptyPayload | QrError::Encoding(_))) => {
+            Err(Status::invalid_argument(e.to_string()))
+        }
+        Err(e @ QrError::Image(_)) => Err(logged(Status::internal("Could not render QR code"), e)),
+    }
+}
+
+pub(super) fn no_backend() -> Status {
+    Status::unimplemented("No lightning backend configured")
+}
+
+pub(super) fn into_status(e: SettlementError) -> Status {
+    match e {
+        SettlementError::NothingToSettle => Status::failed_precondition("Nothing to settle"),
+        SettlementError::InvalidRate => Status::invalid_argument("Invalid cents_per_btc"),
+        SettlementError::NotFound => Status::not_found("Settlement invoice not found"),
+        SettlementError::PendingInvoiceMismatch => {
+            Status::failed_precondition("A pending invoice is for a different amount")
+        }
+        SettlementError::Forbidden(denied) => Status::permission_denied(denied.to_string()),
+        SettlementError::UserNotFound => Status::not_found("User not found"),
+        SettlementError::NoPixAccount => Status::failed_precondition("Payee has no PIX account"),
+        SettlementError::PixError(e) => Status::invalid_argument(e.to_string()),
+        SettlementError::NoBitcoinAddress => {
+            Status::failed_precondition("Payee has no bitcoin address")
+        }
+        SettlementError::BitcoinError(e) => Status::invalid_argument(e.to_string()),
+        SettlementError::LightningError(_) => {
+            logged(Status::unavailable("Lightning backend error"), e)
+        }
+        SettlementError::DbError(_) => logged(Status::internal("Database error"), e),
+    }
+}
+
+fn into_proto(invoice: settlement_invoices::SettlementInvoice) -> SettlementInvoice {
+    let status = match invoice.status {
+        SettlementInvoiceStatus::Pending => settlement_invoice::Status::Pending,
+        SettlementInvoiceStatus::Paid => settlement_invoice::Status::Paid,
+        SettlementInvoiceStatus::Expired => settlement_invoice::Status::Expired,
+    };
+
+    SettlementInvoice {
+        id: *invoice.id,
+        payer_user_id: invoice.payer_user_id,
+        payee_user_id: invoice.payee_user_id,
+        amount_cents: invoice.amount_cents as u64,
+        amount_msats: invoice.amount_msats as u64,
+        payment_hash: invoice.payment_hash,
+        payment_request: invoice.payment_request,
+        status: status.into(),
+        expires_at: invoice.expires_at.unix_timestamp(),
+        paid_at: invoice.paid_at.map(|t| t.unix_timestamp()),
+        user_payment_id: invoice.user_payment_id.map(|id| *id),
+    }
+}
@@ -0,0 +1,128 @@
+use std::task::{Context, Poll};
+
+use futures::future::BoxFuture;
+use tonic::codegen::http::{self, HeaderValue};
+use tower::{Layer, Service};
+use tracing::{field, Instrument};
+
+const REQUEST_ID: &str = "x-request-id";
+
+/// Longest client-supplied request id that is kept rather than replaced.
+const MAX_REQUEST_ID_LEN: usize = 128;
+
+/// Runs each request in an `rpc` span carrying a request id, taken from `x-request-id` or
+/// generated, and echoes the id back in the response headers. The span records the HTTP status
+/// and, for RPCs that fail before sending a message, the gRPC status.
+#[derive(Clone, Copy)]
+pub(super) struct TraceLayer;
+
+impl<S> Layer<S> for TraceLayer {
+    type Service = Trace<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        Trace { inner }
+    }
+}
+
+#[derive(Clone)]
+pub(super) struct Trace<S> {
+    inner: S,
+}
+
+impl<S, B, ResBody> Service<http::Request<B>> for Trace<S>
+where
+    S: Service<http::Request<B>, Response = http::Response<ResBody>>,
+    S::Future: Send + 'static,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = BoxFuture<'static, Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, mut request: http::Request<B>) -> Self::Future {
+        let request_id = request
+            .headers()
+            .get(REQUEST_ID)
+            .filter(|id| is_valid(id))
+            .cloned()
+            .unwrap_or_else(new_request_id);
+        request.headers_mut().insert(REQUEST_ID, request_id.clone());
+
+        let span = tracing::info_span!(
+            "rpc",
+            request_id = request_id.to_str().unwrap_or_default(),
+            method = request.uri().path(),
+            http_status = field::Empty,
+            grpc_status = field::Empty,
+        );
+        let response = span.in_scope(|| self.inner.call(request));
+
+        Box::pin(
+            async move {
+                let mut response = response.await?;
+
+                let span = tracing::Span::current();
+                span.record("http_status", response.status().as_u16());
+                if let Some(code) = response.headers().get("grpc-status") {
+                    span.record("grpc_status", code.to_str().unwrap_or_default());
+                }
+
+                response.headers_mut().insert(REQUEST_ID, request_id);
+                Ok(response)
+            }
+            .instrument(span),
+        )
+    }
+}
+
+fn is_valid(id: &HeaderValue) -> bool {
+    !id.is_empty() && id.len() <= MAX_REQUEST_ID_LEN && id.to_str().is_ok()
+}
+
+fn new_request_id() -> HeaderValue {
+    let id = base64::encode_config(crate::auth::random::<16>(), base64::URL_SAFE_NO_PAD);
+    HeaderValue::from_str(&id).expect("Base64 is a valid header value")
+}
+
+#[cfg(test)]
+mod test {
+    use futures::future;
+
+    use super::*;
+
+    async fn respond(request_id: Option<&str>) -> (String, String) {
+        let mut service = TraceLayer.layer(tower::service_fn(|request: http::Request<()>| {
+            let seen = request.headers()[REQUEST_ID].to_str().unwrap().to_owned();
+            future::ok::<_, std::convert::Infallible>(http::Response::new(seen))
+        }));
+
+        let mut request = http::Request::new(());
+        if let Some(id) = request_id {
+            request
+                .headers_mut()
+                .insert(REQUEST_ID, id.parse().unwrap());
+        }
+
+        let response = service.call(request).await.unwrap();
+        let echoed = response.headers()[REQUEST_ID].to_str().unwrap().to_owned();
+        (response.into_body(), echoed)
+    }
+
+    #[tokio::test]
+    async fn propagates_or_generates_request_ids() {
+        assert_eq!(
+            respond(Some("abc")).await,
+            ("abc".to_owned(), "abc".to_owned())
+        );
+
+        let (seen, echoed) = respond(None).await;
+        assert_eq!(seen, echoed);
+        assert_eq!(seen.len(), 22);
+
+        let (seen, _) = respond(Some(&"a".repeat(MAX_REQUEST_ID_LEN + 1))).await;
+        assert_eq!(seen.len(), 22);
+    }
+}
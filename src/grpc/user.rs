@@ -1,27 +1,36 @@
-use db::enums::UserExpensesChargeMethod;
+use db::{
+    enums::{UserExpensesChargeMethod, UserPaymentMethod, UserPaymentStatus},
+    queries::user_expenses::UserExpense,
+    types::UserId,
+};
 use tonic::Status;
 
-use crate::features::user::{self, CreateExpenseOutcome, UserError};
-
-use super::proto::{
-    create_expense_request, CreateExpenseRequest, CreatePaymentRequest, CreateRevenueRequest, Id,
+use crate::{
+    features::user::{
+        self, CreateExpenseOutcome, DeleteExpenseOutcome, ReviewPaymentOutcome,
+        UpdateExpenseOutcome, UserError,
+    },
+    policy::Denied,
 };
 
-pub(super) async fn create(db: &db::Db) -> Result<Id, Status> {
-    match user::create(db).await {
-        Ok(id) => Ok(Id { id: *id }),
-        Err(_) => Err(Status::internal("Database error")),
-    }
-}
+use super::{
+    logged,
+    proto::{
+        confirm_payment_request, create_expense_request, create_payment_request, Balance,
+        ConfirmPaymentRequest, CreateExpenseRequest, CreatePaymentRequest, CreateRevenueRequest,
+        Expense, GetBalanceRequest, Id, UpdateExpenseRequest,
+    },
+};
 
 pub(super) async fn create_revenue(
     db: &db::Db,
+    caller: UserId,
     request: CreateRevenueRequest,
 ) -> Result<Id, Status> {
     match user::create_revenue(
         db,
+        caller,
         user::CreateRevenueParams {
-            user_id: request.user_id,
             amount_cents: request.amount_cents as i64,
             description: request.description,
             incoming_at: request.incoming_at,
@@ -29,40 +38,106 @@ pub(super) async fn create_revenue(
     )
     .await
     {
-        Ok(id) => Ok(Id { id: *id }),
+        Ok(created) => Ok(Id { id: *created.id }),
         Err(_e @ UserError::TimeError(_)) => Err(Status::out_of_range(
             "Invalid timestamp for begin_charging_at",
         )),
-        Err(_e @ UserError::DbError(_)) => Err(Status::internal("Database error")),
+        Err(UserError::Forbidden(denied)) => Err(permission_denied(&denied)),
+        Err(e @ UserError::DbError(_)) => Err(logged(Status::internal("Database error"), e)),
     }
 }
 
 pub(super) async fn create_payment(
     db: &db::Db,
+    caller: UserId,
     request: CreatePaymentRequest,
 ) -> Result<Id, Status> {
+    let method = match create_payment_request::Method::from_i32(request.method) {
+        Some(create_payment_request::Method::Cash) => UserPaymentMethod::Cash,
+        Some(create_payment_request::Method::BankTransfer) => UserPaymentMethod::BankTransfer,
+        Some(create_payment_request::Method::Pix) => UserPaymentMethod::Pix,
+        Some(create_payment_request::Method::Lightning) => UserPaymentMethod::Lightning,
+        Some(create_payment_request::Method::Unspecified) | None => {
+            return Err(Status::invalid_argument("method must be specified"))
+        }
+    };
+
     match user::create_payment(
         db,
+        caller,
         user::CreatePaymentParams {
-            created_by: request.created_by,
             amount_cents: request.amount_cents as i64,
             payee_user_id: request.payee_user_id,
             payer_user_id: request.payer_user_id,
             payed_at: request.payed_at,
+            method,
+            proof: request.proof,
         },
     )
     .await
     {
-        Ok(id) => Ok(Id { id: *id }),
+        Ok(created) => Ok(Id { id: *created.id }),
         Err(_e @ UserError::TimeError(_)) => Err(Status::out_of_range(
             "Invalid timestamp for begin_charging_at",
         )),
-        Err(_e @ UserError::DbError(_)) => Err(Status::internal("Database error")),
+        Err(UserError::Forbidden(denied)) => Err(permission_denied(&denied)),
+        Err(e @ UserError::DbError(_)) => Err(logged(Status::internal("Database error"), e)),
+    }
+}
+
+pub(super) async fn confirm_payment(
+    db: &db::Db,
+    caller: UserId,
+    request: ConfirmPaymentRequest,
+) -> Result<Id, Status> {
+    let status = match confirm_payment_request::Decision::from_i32(request.decision) {
+        Some(confirm_payment_request::Decision::Confirm) => UserPaymentStatus::Confirmed,
+        Some(confirm_payment_request::Decision::Reject) => UserPaymentStatus::Rejected,
+        Some(confirm_payment_request::Decision::Unspecified) | None => {
+            return Err(Status::invalid_argument("decision must be specified"))
+        }
+    };
+
+    match user::review_payment(
+        db,
+        caller,
+        user::ReviewPaymentParams {
+            payment_id: request.payment_id,
+            status,
+        },
+    )
+    .await
+    {
+        Ok(ReviewPaymentOutcome::Reviewed(payment)) => Ok(Id { id: *payment.id }),
+        Ok(ReviewPaymentOutcome::NotFound) => Err(Status::not_found("Pending payment not found")),
+        Err(UserError::Forbidden(denied)) => Err(permission_denied(&denied)),
+        Err(e) => Err(logged(Status::internal("Database error"), e)),
+    }
+}
+
+pub(super) async fn balance(
+    db: &db::Db,
+    caller: UserId,
+    request: GetBalanceRequest,
+) -> Result<Balance, Status> {
+    match user::balance(
+        db,
+        caller,
+        user::BalanceParams {
+            other_user_id: request.other_user_id,
+            include_unconfirmed: request.include_unconfirmed,
+        },
+    )
+    .await
+    {
+        Ok(owed_cents) => Ok(Balance { owed_cents }),
+        Err(e) => Err(logged(Status::internal("Database error"), e)),
     }
 }
 
 pub(super) async fn create_expense(
     db: &db::Db,
+    caller: UserId,
     request: CreateExpenseRequest,
 ) -> Result<Id, Status> {
     let charge_method = match request.method() {
@@ -73,10 +148,10 @@ pub(super) async fn create_expense(
 
     match crate::features::user::create_expense(
         db,
+        caller,
         crate::features::user::CreateExpenseParams {
             amount_cents: request.amount_cents as i64,
             begin_charging_at: request.begin_charging_at,
-            created_by: request.created_by,
             charged_user_id: request.charged_user_id,
             chargee_user_id: request.chargee_user_id,
             charge_method,
@@ -86,10 +161,71 @@ pub(super) async fn create_expense(
     )
     .await
     {
-        Ok(CreateExpenseOutcome::Created(id)) => Ok(Id { id: *id }),
+        Ok(CreateExpenseOutcome::Created(expense)) => Ok(Id { id: *expense.id }),
         Err(_e @ UserError::TimeError(_)) => Err(Status::out_of_range(
             "Invalid timestamp for begin_charging_at",
         )),
-        Err(_e @ UserError::DbError(_)) => Err(Status::internal("Database error")),
+        Err(UserError::Forbidden(denied)) => Err(permission_denied(&denied)),
+        Err(e @ UserError::DbError(_)) => Err(logged(Status::internal("Database error"), e)),
+    }
+}
+
+pub(super) async fn get_expense(db: &db::Db, caller: UserId, id: i32) -> Result<Expense, Status> {
+    match user::get_expense(db, caller, id).await {
+        Ok(Some(expense)) => Ok(expense_into_proto(expense)),
+        Ok(None) => Err(Status::not_found("Expense not found")),
+        Err(UserError::Forbidden(denied)) => Err(permission_denied(&denied)),
+        Err(e) => Err(logged(Status::internal("Database error"), e)),
     }
 }
+
+pub(super) async fn update_expense(
+    db: &db::Db,
+    caller: UserId,
+    request: UpdateExpenseRequest,
+) -> Result<Expense, Status> {
+    let params = user::UpdateExpenseParams {
+        id: request.expense_id,
+        description: request.description,
+    };
+
+    match user::update_expense(db, caller, params).await {
+        Ok(UpdateExpenseOutcome::Updated(expense)) => Ok(expense_into_proto(expense)),
+        Ok(UpdateExpenseOutcome::NotFound) => Err(Status::not_found("Expense not found")),
+        Err(UserError::Forbidden(denied)) => Err(permission_denied(&denied)),
+        Err(e) => Err(logged(Status::internal("Database error"), e)),
+    }
+}
+
+fn expense_into_proto(expense: UserExpense) -> Expense {
+    let method = match expense.charge_method {
+        UserExpensesChargeMethod::Even => create_expense_request::Method::Even,
+        UserExpensesChargeMethod::Proportional => create_expense_request::Method::Proportional,
+        UserExpensesChargeMethod::Full => create_expense_request::Method::Full,
+    };
+
+    Expense {
+        id: *expense.id,
+        created_by: expense.created_by,
+        amount_cents: expense.amount_cents as u64,
+        description: expense.description,
+        chargee_user_id: expense.chargee_user_id,
+        charged_user_id: expense.charged_user_id,
+        begin_charging_at: expense.begin_charging_at.unix_timestamp(),
+        method: method.into(),
+        created_at: expense.created_at.unix_timestamp(),
+    }
+}
+
+pub(super) async fn delete_expense(db: &db::Db, caller: UserId, id: i32) -> Result<Id, Status> {
+    match user::delete_expense(db, caller, id).await {
+        Ok(DeleteExpenseOutcome::Deleted(id)) => Ok(Id { id: *id }),
+        Ok(DeleteExpenseOutcome::NotFound) => Err(Status::not_found("Expense not found")),
+        Err(UserError::Forbidden(denied)) => Err(permission_denied(&denied)),
+        Err(e) => Err(logged(Status::internal("Database error"), e)),
+    }
+}
+
+fn permission_denied(denied: &Denied) -> Status {
+    Status::permission_denied(denied.to_string())
+}
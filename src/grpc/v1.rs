@@ -0,0 +1,537 @@
+//! `splitwiser.v1`, served next to the original package while clients migrate. It calls the
+//! same features and maps their errors the same way; only the messages differ.
+// Handlers return `Status` as their error, which clippy deems too large.
+#![allow(clippy::result_large_err)]
+
+use futures::TryFutureExt;
+use prost_types::Timestamp;
+use time::OffsetDateTime;
+use tonic::{Request, Response, Status};
+
+use self::proto::{
+    AuthTokens, Balance, BitcoinAddress, BitcoinPaymentUri, CreateExpenseRequest,
+    CreatePaymentRequest, CreateRevenueRequest, CreateUserRequest, CreateUserResponse,
+    DeleteExpenseRequest, Expense, GetBalanceRequest, GetBitcoinPaymentUriRequest,
+    GetExpenseRequest, GetPixPayloadRequest, GetSettlementInvoiceRequest, GetUserRequest,
+    ListAuditEventsRequest, ListAuditEventsResponse, ListSessionsResponse, LoginRequest, Money,
+    Payment, PixAccount, PixPayload, QrCode, RefreshSessionRequest, RenderQrCodeRequest,
+    RequestSettlementInvoiceRequest, Revenue, ReviewPaymentRequest, RevokeSessionRequest,
+    SearchUsersRequest, SearchUsersResponse, SetBitcoinAddressRequest, SetPixAccountRequest,
+    SettlementInvoice, UpdateExpenseRequest, UpdateProfileRequest, User,
+};
+use super::{auth, logged, Server};
+use crate::features::CURRENCY;
+
+mod audit;
+mod profile;
+mod session;
+mod settlement;
+mod user;
+
+pub mod proto {
+    tonic::include_proto!("splitwiser.v1");
+}
+
+#[tonic::async_trait]
+impl proto::splitwiser_server::Splitwiser for Server {
+    async fn create_user(
+        &self,
+        request: Request<CreateUserRequest>,
+    ) -> Result<Response<CreateUserResponse>, Status> {
+        session::create_user(&self.db, &self.tokens, request.into_inner())
+            .map_ok(Response::new)
+            .await
+    }
+
+    async fn login(&self, request: Request<LoginRequest>) -> Result<Response<AuthTokens>, Status> {
+        session::login(&self.db, &self.tokens, request.into_inner())
+            .map_ok(Response::new)
+            .await
+    }
+
+    async fn refresh_session(
+        &self,
+        request: Request<RefreshSessionRequest>,
+    ) -> Result<Response<AuthTokens>, Status> {
+        session::refresh(&self.db, &self.tokens, request.into_inner())
+            .map_ok(Response::new)
+            .await
+    }
+
+    async fn logout(&self, request: Request<()>) -> Result<Response<()>, Status> {
+        let caller = auth::caller(&request)?;
+        let current = auth::session(&request)?;
+
+        session::revoke(&self.db, caller, *current)
+            .map_ok(Response::new)
+            .await
+    }
+
+    async fn list_sessions(
+        &self,
+        request: Request<()>,
+    ) -> Result<Response<ListSessionsResponse>, Status> {
+        let caller = auth::caller(&request)?;
+        let current = auth::session(&request)?;
+
+        session::list(&self.db, caller, current)
+            .map_ok(Response::new)
+            .await
+    }
+
+    async fn revoke_session(
+        &self,
+        request: Request<RevokeSessionRequest>,
+    ) -> Result<Response<()>, Status> {
+        let caller = auth::caller(&request)?;
+
+        session::revoke(&self.db, caller, request.into_inner().session_id)
+            .map_ok(Response::new)
+            .await
+    }
+
+    async fn update_profile(
+        &self,
+        request: Request<UpdateProfileRequest>,
+    ) -> Result<Response<User>, Status> {
+        let caller = auth::caller(&request)?;
+
+        profile::update(&self.db, caller, request.into_inner())
+            .map_ok(Response::new)
+            .await
+    }
+
+    async fn get_user(&self, request: Request<GetUserRequest>) -> Result<Response<User>, Status> {
+        let caller = auth::caller(&request)?;
+
+        profile::get(&self.db, caller, request.into_inner().user_id)
+            .map_ok(Response::new)
+            .await
+    }
+
+    async fn search_users(
+        &self,
+        request: Request<SearchUsersRequest>,
+    ) -> Result<Response<SearchUsersResponse>, Status> {
+        let caller = auth::caller(&request)?;
+
+        profile::search(&self.db, caller, request.into_inner())
+            .map_ok(Response::new)
+            .await
+    }
+
+    async fn create_revenue(
+        &self,
+        request: Request<CreateRevenueRequest>,
+    ) -> Result<Response<Revenue>, Status> {
+        let caller = auth::caller(&request)?;
+
+        user::create_revenue(&self.db, caller, request.into_inner())
+            .map_ok(Response::new)
+            .await
+    }
+
+    async fn create_payment(
+        &self,
+        request: Request<CreatePaymentRequest>,
+    ) -> Result<Response<Payment>, Status> {
+        let caller = auth::caller(&request)?;
+
+        user::create_payment(&self.db, caller, request.into_inner())
+            .map_ok(Response::new)
+            .await
+    }
+
+    async fn review_payment(
+        &self,
+        request: Request<ReviewPaymentRequest>,
+    ) -> Result<Response<Payment>, Status> {
+        let caller = auth::caller(&request)?;
+
+        user::review_payment(&self.db, caller, request.into_inner())
+            .map_ok(Response::new)
+            .await
+    }
+
+    async fn create_expense(
+        &self,
+        request: Request<CreateExpenseRequest>,
+    ) -> Result<Response<Expense>, Status> {
+        let caller = auth::caller(&request)?;
+
+        user::create_expense(&self.db, caller, request.into_inner())
+            .map_ok(Response::new)
+            .await
+    }
+
+    async fn get_expense(
+        &self,
+        request: Request<GetExpenseRequest>,
+    ) -> Result<Response<Expense>, Status> {
+        let caller = auth::caller(&request)?;
+
+        user::get_expense(&self.db, caller, request.into_inner().expense_id)
+            .map_ok(Response::new)
+            .await
+    }
+
+    async fn update_expense(
+        &self,
+        request: Request<UpdateExpenseRequest>,
+    ) -> Result<Response<Expense>, Status> {
+        let caller = auth::caller(&request)?;
+
+        user::update_expense(&self.db, caller, request.into_inner())
+            .map_ok(Response::new)
+            .await
+    }
+
+    async fn delete_expense(
+        &self,
+        request: Request<DeleteExpenseRequest>,
+    ) -> Result<Response<()>, Status> {
+        let caller = auth::caller(&request)?;
+
+        user::delete_expense(&self.db, caller, request.into_inner().expense_id)
+            .map_ok(Response::new)
+            .await
+    }
+
+    async fn get_balance(
+        &self,
+        request: Request<GetBalanceRequest>,
+    ) -> Result<Response<Balance>, Status> {
+        let caller = auth::caller(&request)?;
+
+        user::balance(&self.db, caller, request.into_inner())
+            .map_ok(Response::new)
+            .await
+    }
+
+    async fn request_settlement_invoice(
+        &self,
+        request: Request<RequestSettlementInvoiceRequest>,
+    ) -> Result<Response<SettlementInvoice>, Status> {
+        let caller = auth::caller(&request)?;
+
+        settlement::request_invoice(
+            &self.db,
+            self.lightning.as_deref(),
+            caller,
+            request.into_inner(),
+        )
+        .map_ok(Response::new)
+        .await
+    }
+
+    async fn get_settlement_invoice(
+        &self,
+        request: Request<GetSettlementInvoiceRequest>,
+    ) -> Result<Response<SettlementInvoice>, Status> {
+        let caller = auth::caller(&request)?;
+
+        settlement::get_invoice(
+            &self.db,
+            self.lightning.as_deref(),
+            caller,
+            request.into_inner().invoice_id,
+        )
+        .map_ok(Response::new)
+        .await
+    }
+
+    async fn set_pix_account(
+        &self,
+        request: Request<SetPixAccountRequest>,
+    ) -> Result<Response<PixAccount>, Status> {
+        let caller = auth::caller(&request)?;
+
+        settlement::set_pix_account(&self.db, caller, request.into_inner())
+            .map_ok(Response::new)
+            .await
+    }
+
+    async fn get_pix_payload(
+        &self,
+        request: Request<GetPixPayloadRequest>,
+    ) -> Result<Response<PixPayload>, Status> {
+        let caller = auth::caller(&request)?;
+
+        settlement::pix_payload(&self.db, caller, request.into_inner())
+            .map_ok(Response::new)
+            .await
+    }
+
+    async fn set_bitcoin_address(
+        &self,
+        request: Request<SetBitcoinAddressRequest>,
+    ) -> Result<Response<BitcoinAddress>, Status> {
+        let caller = auth::caller(&request)?;
+
+        settlement::set_bitcoin_address(&self.db, caller, request.into_inner())
+            .map_ok(Response::new)
+            .await
+    }
+
+    async fn get_bitcoin_payment_uri(
+        &self,
+        request: Request<GetBitcoinPaymentUriRequest>,
+    ) -> Result<Response<BitcoinPaymentUri>, Status> {
+        let caller = auth::caller(&request)?;
+
+        settlement::bitcoin_payment_uri(&self.db, caller, request.into_inner())
+            .map_ok(Response::new)
+            .await
+    }
+
+    async fn render_qr_code(
+        &self,
+        request: Request<RenderQrCodeRequest>,
+    ) -> Result<Response<QrCode>, Status> {
+        auth::caller(&request)?;
+
+        settlement::render_qr_code(request.into_inner())
+            .map_ok(Response::new)
+            .await
+    }
+
+    async fn list_audit_events(
+        &self,
+        request: Request<ListAuditEventsRequest>,
+    ) -> Result<Response<ListAuditEventsResponse>, Status> {
+        let caller = auth::caller(&request)?;
+
+        audit::list(&self.db, caller, request.into_inner())
+            .map_ok(Response::new)
+            .await
+    }
+}
+
+fn money(units: i64) -> Money {
+    Money {
+        currency: CURRENCY.to_owned(),
+        units,
+    }
+}
+
+/// The units of a required, positive amount in the one supported currency.
+fn positive_units(amount: Option<Money>, field: &str) -> Result<i64, Status> {
+    let amount = amount.ok_or_else(|| missing(field))?;
+
+    if amount.currency != CURRENCY {
+        return Err(Status::invalid_argument(format!(
+            "{field} must be in {CURRENCY}, not {:?}",
+            amount.currency
+        )));
+    }
+    if amount.units <= 0 {
+        return Err(Status::invalid_argument(format!(
+            "{field} must be positive"
+        )));
+    }
+
+    Ok(amount.units)
+}
+
+fn timestamp(at: OffsetDateTime) -> Timestamp {
+    Timestamp {
+        seconds: at.unix_timestamp(),
+        nanos: at.nanosecond() as i32,
+    }
+}
+
+/// The unix seconds of a required timestamp, which is what the features take; nanoseconds are
+/// dropped.
+fn seconds(at: Option<Timestamp>, field: &str) -> Result<i64, Status> {
+    at.map(|at| at.seconds).ok_or_else(|| missing(field))
+}
+
+fn missing(field: &str) -> Status {
+    Status::invalid_argument(format!("{field} is required"))
+}
+
+/// For enum fields left at their `UNSPECIFIED` zero value, or set to a value this server does
+/// not know.
+fn unspecified(field: &str) -> Status {
+    Status::invalid_argument(format!("{field} must be specified"))
+}
+
+#[cfg(test)]
+mod test {
+    use prost::Message;
+    use tonic::{body::BoxBody, codegen::http, transport::Body};
+    use tower::{Layer, ServiceExt};
+
+    use super::*;
+    use crate::auth::Tokens;
+
+    /// Sends `message` to the `method` of a fresh server as a unary gRPC call, through the same
+    /// auth layer as the listener.
+    async fn call<M: Message, R: Message + Default>(
+        db: &db::Db,
+        method: &str,
+        token: Option<&str>,
+        message: M,
+    ) -> Result<R, Status> {
+        let encoded = message.encode_to_vec();
+        let mut frame = vec![0];
+        frame.extend_from_slice(&(encoded.len() as u32).to_be_bytes());
+        frame.extend_from_slice(&encoded);
+
+        let mut request = http::Request::post(format!("/splitwiser.v1.Splitwiser/{method}"))
+            .header("content-type", "application/grpc")
+            .header("te", "trailers");
+        if let Some(token) = token {
+            request = request.header("authorization", format!("Bearer {token}"));
+        }
+
+        let tokens = Tokens::new("secret");
+        let server = Server {
+            db: db.clone(),
+            env: crate::env::Env::test(),
+            lightning: None,
+            tokens: tokens.clone(),
+        };
+        let response: http::Response<BoxBody> = auth::AuthLayer {
+            db: db.clone(),
+            tokens,
+        }
+        .layer(proto::splitwiser_server::SplitwiserServer::new(server))
+        .oneshot(request.body(Body::from(frame)).unwrap())
+        .await
+        .unwrap();
+
+        // Failures come back as trailers-only responses, with the status in the headers.
+        match Status::from_header_map(response.headers()) {
+            Some(status) if status.code() != tonic::Code::Ok => Err(status),
+            _ => {
+                let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+                Ok(R::decode(&body[5..]).unwrap())
+            }
+        }
+    }
+
+    #[test]
+    fn maps_timestamps_both_ways() {
+        let at = time::macros::datetime!(2024-03-01 14:30:15.25 UTC);
+        let mapped = timestamp(at);
+
+        assert_eq!(mapped.nanos, 250_000_000);
+        assert_eq!(seconds(Some(mapped), "at").unwrap(), at.unix_timestamp());
+        assert_eq!(
+            seconds(None, "at").unwrap_err().code(),
+            tonic::Code::InvalidArgument
+        );
+    }
+
+    #[test]
+    fn maps_money_both_ways() {
+        let mapped = money(12_050);
+
+        assert_eq!(mapped.currency, CURRENCY);
+        assert_eq!(positive_units(Some(mapped), "amount").unwrap(), 12_050);
+    }
+
+    #[tokio::test]
+    async fn serves_authenticated_calls() {
+        let db = db::test::db();
+
+        let created: CreateUserResponse = call(
+            &db,
+            "CreateUser",
+            None,
+            CreateUserRequest {
+                email: "a@example.com".to_owned(),
+                password: "password".to_owned(),
+                device: None,
+            },
+        )
+        .await
+        .unwrap();
+        let user_id = created.user.unwrap().id;
+        let tokens = created.tokens.unwrap();
+        assert_eq!(tokens.user_id, user_id);
+
+        let request = CreateRevenueRequest {
+            amount: Some(money(12_050)),
+            incoming_at: Some(Timestamp {
+                seconds: 1_700_000_000,
+                nanos: 0,
+            }),
+            description: None,
+        };
+
+        let status = call::<_, Revenue>(&db, "CreateRevenue", None, request.clone())
+            .await
+            .unwrap_err();
+        assert_eq!(status.code(), tonic::Code::Unauthenticated);
+
+        let revenue: Revenue = call(
+            &db,
+            "CreateRevenue",
+            Some(&tokens.access_token),
+            request.clone(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(revenue.user_id, user_id);
+        assert_eq!(revenue.amount, request.amount);
+        assert_eq!(revenue.incoming_at, request.incoming_at);
+    }
+
+    #[tokio::test]
+    async fn rejects_unspecified_enums() {
+        let db = db::test::db();
+        let caller = db::types::UserId::from(1);
+
+        let payment = CreatePaymentRequest {
+            amount: Some(money(100)),
+            payee_user_id: 2,
+            payer_user_id: 1,
+            payed_at: Some(timestamp(OffsetDateTime::now_utc())),
+            method: proto::payment::Method::Unspecified.into(),
+            proof: None,
+        };
+        let status = user::create_payment(&db, caller, payment)
+            .await
+            .unwrap_err();
+        assert_eq!(status.message(), "method must be specified");
+
+        let review = ReviewPaymentRequest {
+            payment_id: 1,
+            decision: 7,
+        };
+        let status = user::review_payment(&db, caller, review).await.unwrap_err();
+        assert_eq!(status.message(), "decision must be specified");
+
+        let qr = RenderQrCodeRequest {
+            payload: "payload".to_owned(),
+            format: proto::render_qr_code_request::Format::Png.into(),
+            error_correction: 0,
+        };
+        let status = settlement::render_qr_code(qr).await.unwrap_err();
+        assert_eq!(status.code(), tonic::Code::InvalidArgument);
+    }
+
+    #[test]
+    fn accepts_positive_amounts_in_reais() {
+        let amount = |currency: &str, units| {
+            positive_units(
+                Some(Money {
+                    currency: currency.to_owned(),
+                    units,
+                }),
+                "amount",
+            )
+        };
+
+        assert_eq!(amount("BRL", 12_050).unwrap(), 12_050);
+        for rejected in [amount("USD", 100), amount("", 100), amount("BRL", 0)] {
+            assert_eq!(rejected.unwrap_err().code(), tonic::Code::InvalidArgument);
+        }
+        assert_eq!(
+            positive_units(None, "amount").unwrap_err().message(),
+            "amount is required"
+        );
+    }
+}
@@ -0,0 +1,71 @@
+use db::{enums::AuditEntityType, queries::audit_events, types::UserId};
+use tonic::Status;
+
+use crate::features::audit::{self, ListParams};
+
+use super::{
+    logged,
+    proto::{audit_event, AuditEvent, ListAuditEventsRequest, ListAuditEventsResponse},
+    timestamp, unspecified,
+};
+
+pub(super) async fn list(
+    db: &db::Db,
+    caller: UserId,
+    request: ListAuditEventsRequest,
+) -> Result<ListAuditEventsResponse, Status> {
+    let entity = match (request.entity_type, request.entity_id) {
+        (Some(entity_type), Some(id)) => {
+            let entity_type = match audit_event::EntityType::from_i32(entity_type) {
+                Some(audit_event::EntityType::Revenue) => AuditEntityType::UserRevenue,
+                Some(audit_event::EntityType::Payment) => AuditEntityType::UserPayment,
+                Some(audit_event::EntityType::Expense) => AuditEntityType::UserExpense,
+                Some(audit_event::EntityType::Unspecified) | None => {
+                    return Err(unspecified("entity_type"))
+                }
+            };
+            Some((entity_type, id))
+        }
+        (None, None) => None,
+        _ => {
+            return Err(Status::invalid_argument(
+                "entity_type and entity_id must be set together",
+            ))
+        }
+    };
+
+    audit::list(
+        db,
+        caller,
+        ListParams {
+            entity,
+            actor_user_id: request.actor_user_id,
+            before_id: request.before_id,
+            limit: request.limit,
+        },
+    )
+    .await
+    .map(|events| ListAuditEventsResponse {
+        events: events.into_iter().map(into_proto).collect(),
+    })
+    .map_err(|e| logged(Status::internal("Database error"), e))
+}
+
+fn into_proto(event: audit_events::AuditEvent) -> AuditEvent {
+    let entity_type = match event.entity_type {
+        AuditEntityType::UserRevenue => audit_event::EntityType::Revenue,
+        AuditEntityType::UserPayment => audit_event::EntityType::Payment,
+        AuditEntityType::UserExpense => audit_event::EntityType::Expense,
+    };
+
+    AuditEvent {
+        id: *event.id,
+        actor_user_id: event.actor_user_id,
+        rpc: event.rpc,
+        entity_type: entity_type.into(),
+        entity_id: event.entity_id,
+        before: event.before.map(|before| before.to_string()),
+        after: event.after.map(|after| after.to_string()),
+        created_at: Some(timestamp(event.created_at)),
+    }
+}
@@ -0,0 +1,117 @@
+use db::types::{SessionId, UserId};
+use tonic::Status;
+
+use crate::{
+    auth::Tokens,
+    features::{
+        self,
+        session::{CredentialsParams, IssuedSession},
+    },
+    grpc::{profile::into_status as profile_status, session::into_status},
+};
+
+use super::{
+    proto::{
+        AuthTokens, CreateUserRequest, CreateUserResponse, ListSessionsResponse, LoginRequest,
+        RefreshSessionRequest, Session,
+    },
+    timestamp,
+};
+
+pub(super) async fn create_user(
+    db: &db::Db,
+    tokens: &Tokens,
+    request: CreateUserRequest,
+) -> Result<CreateUserResponse, Status> {
+    let (id, issued) = features::session::register(
+        db,
+        tokens,
+        CredentialsParams {
+            email: request.email,
+            password: request.password,
+            device: request.device,
+        },
+    )
+    .await
+    .map_err(into_status)?;
+
+    let profile = features::profile::get(db, id, *id)
+        .await
+        .map_err(profile_status)?;
+
+    Ok(CreateUserResponse {
+        user: Some(super::profile::into_proto(profile)),
+        tokens: Some(into_proto(issued)),
+    })
+}
+
+pub(super) async fn login(
+    db: &db::Db,
+    tokens: &Tokens,
+    request: LoginRequest,
+) -> Result<AuthTokens, Status> {
+    features::session::login(
+        db,
+        tokens,
+        CredentialsParams {
+            email: request.email,
+            password: request.password,
+            device: request.device,
+        },
+    )
+    .await
+    .map(into_proto)
+    .map_err(into_status)
+}
+
+pub(super) async fn refresh(
+    db: &db::Db,
+    tokens: &Tokens,
+    request: RefreshSessionRequest,
+) -> Result<AuthTokens, Status> {
+    features::session::refresh(db, tokens, &request.refresh_token)
+        .await
+        .map(into_proto)
+        .map_err(into_status)
+}
+
+pub(super) async fn list(
+    db: &db::Db,
+    caller: UserId,
+    current: SessionId,
+) -> Result<ListSessionsResponse, Status> {
+    features::session::list(db, caller)
+        .await
+        .map(|sessions| ListSessionsResponse {
+            sessions: sessions
+                .into_iter()
+                .map(|session| Session {
+                    id: *session.id,
+                    device: session.device,
+                    created_at: Some(timestamp(session.created_at)),
+                    refreshed_at: Some(timestamp(session.refreshed_at)),
+                    expires_at: Some(timestamp(session.expires_at)),
+                    current: *session.id == *current,
+                })
+                .collect(),
+        })
+        .map_err(into_status)
+}
+
+pub(super) async fn revoke(db: &db::Db, caller: UserId, id: i32) -> Result<(), Status> {
+    features::session::revoke(db, caller, id)
+        .await
+        .map(|_| ())
+        .map_err(into_status)
+}
+
+fn into_proto(issued: IssuedSession) -> AuthTokens {
+    AuthTokens {
+        user_id: *issued.user_id,
+        session_id: *issued.session_id,
+        access_token: issued.access_token,
+        access_token_expires_at: Some(timestamp(issued.access_token_expires_at)),
+        refresh_token: issued.refresh_token,
+        refresh_token_expires_at: Some(timestamp(issued.refresh_token_expires_at)),
+    }
+}
@@ -0,0 +1,193 @@
+use db::{enums::SettlementInvoiceStatus, queries::settlement_invoices, types::UserId};
+use tonic::Status;
+
+use crate::{
+    features::settlement,
+    grpc::{
+        proto::{self as legacy, render_qr_code_request as legacy_qr},
+        settlement::{self as legacy_settlement, into_status, no_backend},
+    },
+    lightning::LightningBackend,
+};
+
+use super::{
+    money, positive_units,
+    proto::{
+        render_qr_code_request::{ErrorCorrection, Format},
+        settlement_invoice, BitcoinAddress, BitcoinPaymentUri, GetBitcoinPaymentUriRequest,
+        GetPixPayloadRequest, PixAccount, PixPayload, QrCode, RenderQrCodeRequest,
+        RequestSettlementInvoiceRequest, SetBitcoinAddressRequest, SetPixAccountRequest,
+        SettlementInvoice,
+    },
+    timestamp, unspecified,
+};
+
+pub(super) async fn request_invoice(
+    db: &db::Db,
+    lightning: Option<&dyn LightningBackend>,
+    caller: UserId,
+    request: RequestSettlementInvoiceRequest,
+) -> Result<SettlementInvoice, Status> {
+    let lightning = lightning.ok_or_else(no_backend)?;
+    let cents_per_btc = positive_units(request.btc_price, "btc_price")?;
+
+    settlement::request_invoice(
+        db,
+        lightning,
+        caller,
+        settlement::RequestInvoiceParams {
+            payee_user_id: request.payee_user_id,
+            cents_per_btc: cents_per_btc as u64,
+        },
+    )
+    .await
+    .map(into_proto)
+    .map_err(into_status)
+}
+
+pub(super) async fn get_invoice(
+    db: &db::Db,
+    lightning: Option<&dyn LightningBackend>,
+    caller: UserId,
+    id: i32,
+) -> Result<SettlementInvoice, Status> {
+    let lightning = lightning.ok_or_else(no_backend)?;
+
+    settlement::get_invoice(db, lightning, caller, id)
+        .await
+        .map(into_proto)
+        .map_err(into_status)
+}
+
+pub(super) async fn set_pix_account(
+    db: &db::Db,
+    caller: UserId,
+    request: SetPixAccountRequest,
+) -> Result<PixAccount, Status> {
+    let account = PixAccount {
+        key: request.key.clone(),
+        merchant_name: request.merchant_name.clone(),
+        merchant_city: request.merchant_city.clone(),
+    };
+
+    settlement::set_pix_account(
+        db,
+        caller,
+        settlement::SetPixAccountParams {
+            key: request.key,
+            merchant_name: request.merchant_name,
+            merchant_city: request.merchant_city,
+        },
+    )
+    .await
+    .map(|_| account)
+    .map_err(into_status)
+}
+
+pub(super) async fn pix_payload(
+    db: &db::Db,
+    caller: UserId,
+    request: GetPixPayloadRequest,
+) -> Result<PixPayload, Status> {
+    settlement::pix_payload(
+        db,
+        caller,
+        settlement::PixPayloadParams {
+            payee_user_id: request.payee_user_id,
+            txid: request.txid,
+            location: request.location,
+        },
+    )
+    .await
+    .map(|payload| PixPayload {
+        payload: payload.payload,
+        amount: Some(money(payload.amount_cents)),
+    })
+    .map_err(into_status)
+}
+
+pub(super) async fn set_bitcoin_address(
+    db: &db::Db,
+    caller: UserId,
+    request: SetBitcoinAddressRequest,
+) -> Result<BitcoinAddress, Status> {
+    settlement::set_bitcoin_address(db, caller, &request.address)
+        .await
+        .map(|address| BitcoinAddress { address })
+        .map_err(into_status)
+}
+
+pub(super) async fn bitcoin_payment_uri(
+    db: &db::Db,
+    caller: UserId,
+    request: GetBitcoinPaymentUriRequest,
+) -> Result<BitcoinPaymentUri, Status> {
+    let cents_per_btc = positive_units(request.btc_price, "btc_price")?;
+
+    settlement::bitcoin_payment_uri(
+        db,
+        caller,
+        settlement::BitcoinPaymentUriParams {
+            payee_user_id: request.payee_user_id,
+            cents_per_btc: cents_per_btc as u64,
+        },
+    )
+    .await
+    .map(|uri| BitcoinPaymentUri {
+        uri: uri.uri,
+        amount: Some(money(uri.amount_cents)),
+        amount_sats: uri.amount_sats as u64,
+    })
+    .map_err(into_status)
+}
+
+/// Renders through the original package, whose enums have no `UNSPECIFIED` value.
+pub(super) async fn render_qr_code(request: RenderQrCodeRequest) -> Result<QrCode, Status> {
+    let format = match Format::from_i32(request.format) {
+        Some(Format::Svg) => legacy_qr::Format::Svg,
+        Some(Format::Png) => legacy_qr::Format::Png,
+        Some(Format::Unspecified) | None => return Err(unspecified("format")),
+    };
+
+    let error_correction = match ErrorCorrection::from_i32(request.error_correction) {
+        Some(ErrorCorrection::Low) => legacy_qr::ErrorCorrection::Low,
+        Some(ErrorCorrection::Medium) => legacy_qr::ErrorCorrection::Medium,
+        Some(ErrorCorrection::Quartile) => legacy_qr::ErrorCorrection::Quartile,
+        Some(ErrorCorrection::High) => legacy_qr::ErrorCorrection::High,
+        Some(ErrorCorrection::Unspecified) | None => return Err(unspecified("error_correction")),
+    };
+
+    let code = legacy_settlement::render_qr_code(legacy::RenderQrCodeRequest {
+        payload: request.payload,
+        format: format.into(),
+        error_correction: error_correction.into(),
+    })
+    .await?;
+
+    Ok(QrCode {
+        content: code.content,
+        content_type: code.content_type,
+    })
+}
+
+fn into_proto(invoice: settlement_invoices::SettlementInvoice) -> SettlementInvoice {
+    let status = match invoice.status {
+        SettlementInvoiceStatus::Pending => settlement_invoice::Status::Pending,
+        SettlementInvoiceStatus::Paid => settlement_invoice::Status::Paid,
+        SettlementInvoiceStatus::Expired => settlement_invoice::Status::Expired,
+    };
+
+    SettlementInvoice {
+        id: *invoice.id,
+        payer_user_id: invoice.payer_user_id,
+        payee_user_id: invoice.payee_user_id,
+        amount: Some(money(invoice.amount_cents)),
+        amount_msats: invoice.amount_msats as u64,
+        payment_hash: invoice.payment_hash,
+        payment_request: invoice.payment_request,
+        status: status.into(),
+        expires_at: Some(timestamp(invoice.expires_at)),
+        paid_at: invoice.paid_at.map(timestamp),
+        user_payment_id: invoice.user_payment_id.map(|id| *id),
+    }
+}
@@ -0,0 +1,244 @@
+use db::{
+    enums::{UserExpensesChargeMethod, UserPaymentMethod, UserPaymentStatus},
+    queries::{user_expenses::UserExpense, user_payments::UserPayment, user_revenues::UserRevenue},
+    types::UserId,
+};
+use tonic::Status;
+
+use crate::features::user::{
+    self, CreateExpenseOutcome, DeleteExpenseOutcome, ReviewPaymentOutcome, UpdateExpenseOutcome,
+    UserError,
+};
+
+use super::{
+    logged, money, positive_units,
+    proto::{
+        expense, payment, review_payment_request, Balance, CreateExpenseRequest,
+        CreatePaymentRequest, CreateRevenueRequest, Expense, GetBalanceRequest, Payment, Revenue,
+        ReviewPaymentRequest, UpdateExpenseRequest,
+    },
+    seconds, timestamp, unspecified,
+};
+
+pub(super) async fn create_revenue(
+    db: &db::Db,
+    caller: UserId,
+    request: CreateRevenueRequest,
+) -> Result<Revenue, Status> {
+    let params = user::CreateRevenueParams {
+        amount_cents: positive_units(request.amount, "amount")?,
+        description: request.description,
+        incoming_at: seconds(request.incoming_at, "incoming_at")?,
+    };
+
+    user::create_revenue(db, caller, params)
+        .await
+        .map(revenue_into_proto)
+        .map_err(into_status)
+}
+
+pub(super) async fn create_payment(
+    db: &db::Db,
+    caller: UserId,
+    request: CreatePaymentRequest,
+) -> Result<Payment, Status> {
+    let method = match payment::Method::from_i32(request.method) {
+        Some(payment::Method::Cash) => UserPaymentMethod::Cash,
+        Some(payment::Method::BankTransfer) => UserPaymentMethod::BankTransfer,
+        Some(payment::Method::Pix) => UserPaymentMethod::Pix,
+        Some(payment::Method::Lightning) => UserPaymentMethod::Lightning,
+        Some(payment::Method::Unspecified) | None => return Err(unspecified("method")),
+    };
+
+    let params = user::CreatePaymentParams {
+        amount_cents: positive_units(request.amount, "amount")?,
+        payee_user_id: request.payee_user_id,
+        payer_user_id: request.payer_user_id,
+        payed_at: seconds(request.payed_at, "payed_at")?,
+        method,
+        proof: request.proof,
+    };
+
+    user::create_payment(db, caller, params)
+        .await
+        .map(payment_into_proto)
+        .map_err(into_status)
+}
+
+pub(super) async fn review_payment(
+    db: &db::Db,
+    caller: UserId,
+    request: ReviewPaymentRequest,
+) -> Result<Payment, Status> {
+    let status = match review_payment_request::Decision::from_i32(request.decision) {
+        Some(review_payment_request::Decision::Confirm) => UserPaymentStatus::Confirmed,
+        Some(review_payment_request::Decision::Reject) => UserPaymentStatus::Rejected,
+        Some(review_payment_request::Decision::Unspecified) | None => {
+            return Err(unspecified("decision"))
+        }
+    };
+
+    match user::review_payment(
+        db,
+        caller,
+        user::ReviewPaymentParams {
+            payment_id: request.payment_id,
+            status,
+        },
+    )
+    .await
+    {
+        Ok(ReviewPaymentOutcome::Reviewed(payment)) => Ok(payment_into_proto(payment)),
+        Ok(ReviewPaymentOutcome::NotFound) => Err(Status::not_found("Pending payment not found")),
+        Err(e) => Err(into_status(e)),
+    }
+}
+
+pub(super) async fn balance(
+    db: &db::Db,
+    caller: UserId,
+    request: GetBalanceRequest,
+) -> Result<Balance, Status> {
+    user::balance(
+        db,
+        caller,
+        user::BalanceParams {
+            other_user_id: request.other_user_id,
+            include_unconfirmed: request.include_unconfirmed,
+        },
+    )
+    .await
+    .map(|owed_cents| Balance {
+        owed: Some(money(owed_cents)),
+    })
+    .map_err(|e| logged(Status::internal("Database error"), e))
+}
+
+pub(super) async fn create_expense(
+    db: &db::Db,
+    caller: UserId,
+    request: CreateExpenseRequest,
+) -> Result<Expense, Status> {
+    let charge_method = match expense::Method::from_i32(request.method) {
+        Some(expense::Method::Even) => UserExpensesChargeMethod::Even,
+        Some(expense::Method::Proportional) => UserExpensesChargeMethod::Proportional,
+        Some(expense::Method::Full) => UserExpensesChargeMethod::Full,
+        Some(expense::Method::Unspecified) | None => return Err(unspecified("method")),
+    };
+
+    let params = user::CreateExpenseParams {
+        amount_cents: positive_units(request.amount, "amount")?,
+        begin_charging_at: seconds(request.begin_charging_at, "begin_charging_at")?,
+        charged_user_id: request.charged_user_id,
+        chargee_user_id: request.chargee_user_id,
+        charge_method,
+        description: request.description,
+        installments: request.installments,
+    };
+
+    match user::create_expense(db, caller, params).await {
+        Ok(CreateExpenseOutcome::Created(expense)) => Ok(expense_into_proto(expense)),
+        Err(e) => Err(into_status(e)),
+    }
+}
+
+pub(super) async fn get_expense(db: &db::Db, caller: UserId, id: i32) -> Result<Expense, Status> {
+    match user::get_expense(db, caller, id).await {
+        Ok(Some(expense)) => Ok(expense_into_proto(expense)),
+        Ok(None) => Err(Status::not_found("Expense not found")),
+        Err(e) => Err(into_status(e)),
+    }
+}
+
+pub(super) async fn update_expense(
+    db: &db::Db,
+    caller: UserId,
+    request: UpdateExpenseRequest,
+) -> Result<Expense, Status> {
+    let params = user::UpdateExpenseParams {
+        id: request.expense_id,
+        description: request.description,
+    };
+
+    match user::update_expense(db, caller, params).await {
+        Ok(UpdateExpenseOutcome::Updated(expense)) => Ok(expense_into_proto(expense)),
+        Ok(UpdateExpenseOutcome::NotFound) => Err(Status::not_found("Expense not found")),
+        Err(e) => Err(into_status(e)),
+    }
+}
+
+pub(super) async fn delete_expense(db: &db::Db, caller: UserId, id: i32) -> Result<(), Status> {
+    match user::delete_expense(db, caller, id).await {
+        Ok(DeleteExpenseOutcome::Deleted(_)) => Ok(()),
+        Ok(DeleteExpenseOutcome::NotFound) => Err(Status::not_found("Expense not found")),
+        Err(e) => Err(into_status(e)),
+    }
+}
+
+fn into_status(e: UserError) -> Status {
+    match e {
+        UserError::TimeError(_) => Status::out_of_range("Timestamp out of range"),
+        UserError::Forbidden(denied) => Status::permission_denied(denied.to_string()),
+        UserError::DbError(_) => logged(Status::internal("Database error"), e),
+    }
+}
+
+fn revenue_into_proto(revenue: UserRevenue) -> Revenue {
+    Revenue {
+        id: *revenue.id,
+        user_id: revenue.user_id,
+        amount: Some(money(revenue.amount_cents)),
+        incoming_at: Some(timestamp(revenue.incoming_at)),
+        description: revenue.description,
+        created_at: Some(timestamp(revenue.created_at)),
+    }
+}
+
+fn payment_into_proto(payment: UserPayment) -> Payment {
+    let method = match payment.method {
+        UserPaymentMethod::Cash => payment::Method::Cash,
+        UserPaymentMethod::BankTransfer => payment::Method::BankTransfer,
+        UserPaymentMethod::Pix => payment::Method::Pix,
+        UserPaymentMethod::Lightning => payment::Method::Lightning,
+    };
+
+    let status = match payment.status {
+        UserPaymentStatus::Pending => payment::Status::Pending,
+        UserPaymentStatus::Confirmed => payment::Status::Confirmed,
+        UserPaymentStatus::Rejected => payment::Status::Rejected,
+    };
+
+    Payment {
+        id: *payment.id,
+        created_by: payment.created_by,
+        amount: Some(money(payment.amount_cents)),
+        payee_user_id: payment.payee_user_id,
+        payer_user_id: payment.payer_user_id,
+        payed_at: Some(timestamp(payment.payed_at)),
+        method: method.into(),
+        proof: payment.proof,
+        status: status.into(),
+        reviewed_at: payment.reviewed_at.map(timestamp),
+        created_at: Some(timestamp(payment.created_at)),
+    }
+}
+
+fn expense_into_proto(expense: UserExpense) -> Expense {
+    let method = match expense.charge_method {
+        UserExpensesChargeMethod::Even => expense::Method::Even,
+        UserExpensesChargeMethod::Proportional => expense::Method::Proportional,
+        UserExpensesChargeMethod::Full => expense::Method::Full,
+    };
+
+    Expense {
+        id: *expense.id,
+        created_by: expense.created_by,
+        amount: Some(money(expense.amount_cents)),
+        description: expense.description,
+        chargee_user_id: expense.chargee_user_id,
+        charged_user_id: expense.charged_user_id,
+        begin_charging_at: Some(timestamp(expense.begin_charging_at)),
+        method: method.into(),
+        created_at: Some(timestamp(expense.created_at)),
+    }
+}
@@ -0,0 +1,403 @@
+use std::{
+    pin::Pin,
+    task::{Context, Poll},
+    time::Duration,
+};
+
+use futures::{
+    future::{Either, MapOk},
+    ready, Stream, StreamExt, TryFutureExt,
+};
+use tonic::{
+    body::BoxBody,
+    codegen::{
+        http::{self, header, HeaderMap, HeaderName, HeaderValue, Method},
+        Body, Bytes,
+    },
+    Status,
+};
+use tower::{layer::util::Stack, Layer, Service};
+use tower_http::cors::{AllowOrigin, CorsLayer};
+
+const GRPC: &str = "application/grpc";
+const GRPC_WEB: &str = "application/grpc-web";
+const GRPC_WEB_PROTO: &str = "application/grpc-web+proto";
+const GRPC_WEB_TEXT: &str = "application/grpc-web-text";
+const GRPC_WEB_TEXT_PROTO: &str = "application/grpc-web-text+proto";
+
+/// Flag marking a gRPC-Web frame that carries trailers rather than a message.
+const TRAILERS_FLAG: u8 = 0x80;
+
+/// How long browsers may cache a preflight response.
+const PREFLIGHT_MAX_AGE: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Accepts gRPC-Web from browsers in `origins`, answering CORS preflights before anything else
+/// sees them.
+pub(super) fn layer(origins: &[String]) -> Stack<GrpcWebLayer, CorsLayer> {
+    let origins = origins.iter().map(|origin| {
+        origin
+            .parse::<HeaderValue>()
+            .unwrap_or_else(|_| panic!("Invalid gRPC-Web origin: {origin}"))
+    });
+
+    let cors = CorsLayer::new()
+        .allow_origin(AllowOrigin::list(origins))
+        .allow_methods([Method::POST])
+        .allow_headers([
+            header::AUTHORIZATION,
+            header::CONTENT_TYPE,
+            HeaderName::from_static("grpc-timeout"),
+            HeaderName::from_static("x-grpc-web"),
+            HeaderName::from_static("x-user-agent"),
+        ])
+        .expose_headers([
+            HeaderName::from_static("grpc-status"),
+            HeaderName::from_static("grpc-message"),
+            HeaderName::from_static("retry-after"),
+        ])
+        .max_age(PREFLIGHT_MAX_AGE);
+
+    Stack::new(GrpcWebLayer, cors)
+}
+
+/// Translates gRPC-Web requests into gRPC and back. Request bodies are framed the same way in both
+/// protocols; responses move their trailers into a final body frame, since browsers cannot read
+/// HTTP trailers. `grpc-web-text` bodies are additionally base64-encoded, one chunk per frame on
+/// the way out.
+#[derive(Clone, Copy)]
+pub(super) struct GrpcWebLayer;
+
+impl<S> Layer<S> for GrpcWebLayer {
+    type Service = GrpcWeb<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        GrpcWeb { inner }
+    }
+}
+
+#[derive(Clone)]
+pub(super) struct GrpcWeb<S> {
+    inner: S,
+}
+
+type ToWeb = fn(http::Response<BoxBody>) -> http::Response<BoxBody>;
+
+impl<S> Service<http::Request<hyper::Body>> for GrpcWeb<S>
+where
+    S: Service<http::Request<hyper::Body>, Response = http::Response<BoxBody>>,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = Either<MapOk<S::Future, ToWeb>, S::Future>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, mut request: http::Request<hyper::Body>) -> Self::Future {
+        let content_type = request
+            .headers()
+            .get(header::CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok());
+
+        let to_web: ToWeb = match content_type {
+            Some(GRPC_WEB | GRPC_WEB_PROTO) => into_web,
+            Some(content_type) if content_type.starts_with(GRPC_WEB_TEXT) => {
+                request = request.map(|body| hyper::Body::wrap_stream(decode_text(body)));
+                into_web_text
+            }
+            _ => return Either::Right(self.inner.call(request)),
+        };
+
+        request
+            .headers_mut()
+            .insert(header::CONTENT_TYPE, HeaderValue::from_static(GRPC));
+        Either::Left(self.inner.call(request).map_ok(to_web))
+    }
+}
+
+/// Decodes a base64 request body, which may be split anywhere between chunks.
+fn decode_text(
+    body: hyper::Body,
+) -> impl Stream<Item = Result<Bytes, Box<dyn std::error::Error + Send + Sync>>> {
+    futures::stream::unfold((body, Vec::new()), |(mut body, mut pending)| async move {
+        loop {
+            match body.next().await {
+                Some(Ok(chunk)) => {
+                    pending.extend_from_slice(&chunk);
+                    let end = pending.len() / 4 * 4;
+                    if end == 0 {
+                        continue;
+                    }
+
+                    let decoded = base64::decode(&pending[..end]);
+                    pending.drain(..end);
+                    return Some((
+                        decoded.map(Bytes::from).map_err(Into::into),
+                        (body, pending),
+                    ));
+                }
+                Some(Err(e)) => return Some((Err(e.into()), (body, pending))),
+                None if pending.is_empty() => return None,
+                None => {
+                    return Some((Err("Truncated base64 body".into()), (body, Vec::new())));
+                }
+            }
+        }
+    })
+}
+
+fn into_web(response: http::Response<BoxBody>) -> http::Response<BoxBody> {
+    web_response(response, GRPC_WEB_PROTO, false)
+}
+
+fn into_web_text(response: http::Response<BoxBody>) -> http::Response<BoxBody> {
+    web_response(response, GRPC_WEB_TEXT_PROTO, true)
+}
+
+fn web_response(
+    response: http::Response<BoxBody>,
+    content_type: &'static str,
+    text: bool,
+) -> http::Response<BoxBody> {
+    let (mut parts, body) = response.into_parts();
+
+    parts
+        .headers
+        .insert(header::CONTENT_TYPE, HeaderValue::from_static(content_type));
+
+    let body = WebBody {
+        inner: body,
+        text,
+        done: false,
+    };
+
+    http::Response::from_parts(parts, body.boxed_unsync())
+}
+
+/// Passes messages through, then sends the trailers of `inner` as a gRPC-Web trailers frame.
+/// With `text`, every chunk is base64-encoded on its own.
+struct WebBody {
+    inner: BoxBody,
+    text: bool,
+    done: bool,
+}
+
+impl WebBody {
+    fn encode(&self, data: Bytes) -> Bytes {
+        if self.text {
+            base64::encode(data).into()
+        } else {
+            data
+        }
+    }
+}
+
+impl Body for WebBody {
+    type Data = Bytes;
+    type Error = Status;
+
+    fn poll_data(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Self::Data, Self::Error>>> {
+        if self.done {
+            return Poll::Ready(None);
+        }
+
+        if let Some(data) = ready!(Pin::new(&mut self.inner).poll_data(cx)) {
+            return Poll::Ready(Some(data.map(|data| self.encode(data))));
+        }
+
+        let trailers = ready!(Pin::new(&mut self.inner).poll_trailers(cx));
+        self.done = true;
+
+        Poll::Ready(match trailers {
+            Ok(Some(trailers)) => Some(Ok(self.encode(trailers_frame(&trailers)))),
+            Ok(None) => None,
+            Err(status) => Some(Err(status)),
+        })
+    }
+
+    fn poll_trailers(
+        self: Pin<&mut Self>,
+        _cx: &mut Context<'_>,
+    ) -> Poll<Result<Option<HeaderMap>, Self::Error>> {
+        Poll::Ready(Ok(None))
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.done
+    }
+}
+
+fn trailers_frame(trailers: &HeaderMap) -> Bytes {
+    let mut block = Vec::new();
+    for (name, value) in trailers {
+        block.extend_from_slice(name.as_str().as_bytes());
+        block.push(b':');
+        block.extend_from_slice(value.as_bytes());
+        block.extend_from_slice(b"\r\n");
+    }
+
+    let length = u32::try_from(block.len()).expect("Trailers exceed a gRPC-Web frame");
+
+    let mut frame = Vec::with_capacity(5 + block.len());
+    frame.push(TRAILERS_FLAG);
+    frame.extend_from_slice(&length.to_be_bytes());
+    frame.extend_from_slice(&block);
+    frame.into()
+}
+
+#[cfg(test)]
+mod test {
+    use futures::stream;
+    use hyper::{body, client::conn, Body as HyperBody};
+    use prost::Message;
+    use tokio::{net::TcpListener, sync::watch};
+
+    use super::*;
+    use crate::grpc::health::{
+        proto::{
+            health_check_response::ServingStatus, health_server::HealthServer, HealthCheckRequest,
+            HealthCheckResponse,
+        },
+        HealthService,
+    };
+
+    const ORIGIN: &str = "https://app.example.com";
+
+    /// Serves the health service behind the gRPC-Web layers and opens an HTTP/1.1 connection.
+    async fn connect() -> conn::SendRequest<HyperBody> {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let incoming = stream::unfold(listener, |listener| async move {
+            Some((listener.accept().await.map(|(s, _)| s), listener))
+        });
+
+        let (_tx, shutdown) = watch::channel(false);
+        let health = HealthService {
+            db: db::test::db(),
+            shutdown,
+        };
+
+        tokio::spawn(
+            tonic::transport::Server::builder()
+                .accept_http1(true)
+                .layer(layer(&[ORIGIN.to_owned()]))
+                .add_service(HealthServer::new(health))
+                .serve_with_incoming(incoming),
+        );
+
+        let stream = tokio::net::TcpStream::connect(addr).await.unwrap();
+        let (sender, connection) = conn::handshake(stream).await.unwrap();
+        tokio::spawn(connection);
+        sender
+    }
+
+    fn frame(flag: u8, payload: &[u8]) -> Vec<u8> {
+        let mut frame = vec![flag];
+        frame.extend_from_slice(&u32::try_from(payload.len()).unwrap().to_be_bytes());
+        frame.extend_from_slice(payload);
+        frame
+    }
+
+    #[tokio::test]
+    async fn serves_grpc_web_over_http1() {
+        let mut client = connect().await;
+
+        let preflight = http::Request::builder()
+            .method(Method::OPTIONS)
+            .uri("/grpc.health.v1.Health/Check")
+            .header(header::HOST, "localhost")
+            .header(header::ORIGIN, ORIGIN)
+            .header(header::ACCESS_CONTROL_REQUEST_METHOD, "POST")
+            .header(
+                header::ACCESS_CONTROL_REQUEST_HEADERS,
+                "content-type,x-grpc-web",
+            )
+            .body(HyperBody::empty())
+            .unwrap();
+        let response = client.send_request(preflight).await.unwrap();
+        assert_eq!(
+            response.headers()[header::ACCESS_CONTROL_ALLOW_ORIGIN],
+            ORIGIN
+        );
+
+        let message = HealthCheckRequest {
+            service: String::new(),
+        };
+        let request = http::Request::builder()
+            .method(Method::POST)
+            .uri("/grpc.health.v1.Health/Check")
+            .header(header::HOST, "localhost")
+            .header(header::ORIGIN, ORIGIN)
+            .header(header::CONTENT_TYPE, GRPC_WEB_PROTO)
+            .header("x-grpc-web", "1")
+            .body(HyperBody::from(frame(0, &message.encode_to_vec())))
+            .unwrap();
+        let response = client.send_request(request).await.unwrap();
+
+        assert_eq!(response.version(), http::Version::HTTP_11);
+        assert_eq!(response.headers()[header::CONTENT_TYPE], GRPC_WEB_PROTO);
+        assert_eq!(
+            response.headers()[header::ACCESS_CONTROL_ALLOW_ORIGIN],
+            ORIGIN
+        );
+
+        let body = body::to_bytes(response.into_body()).await.unwrap();
+
+        let (flag, length) = (body[0], u32::from_be_bytes(body[1..5].try_into().unwrap()));
+        assert_eq!(flag, 0);
+        let (message, trailers) = body[5..].split_at(length as usize);
+        let message = HealthCheckResponse::decode(message).unwrap();
+        assert_eq!(message.status(), ServingStatus::Serving);
+
+        assert_eq!(trailers[0], TRAILERS_FLAG);
+        let trailers = std::str::from_utf8(&trailers[5..]).unwrap();
+        assert!(trailers.contains("grpc-status:0\r\n"), "{trailers}");
+    }
+
+    #[tokio::test]
+    async fn serves_grpc_web_text() {
+        let mut client = connect().await;
+
+        let message = HealthCheckRequest {
+            service: String::new(),
+        };
+        let encoded = base64::encode(frame(0, &message.encode_to_vec()));
+        // Browsers may deliver the body in pieces that split base64 quanta.
+        let (head, tail) = encoded.split_at(3);
+        let chunks: Vec<Result<_, std::io::Error>> = vec![Ok(head.to_owned()), Ok(tail.to_owned())];
+
+        let request = http::Request::builder()
+            .method(Method::POST)
+            .uri("/grpc.health.v1.Health/Check")
+            .header(header::HOST, "localhost")
+            .header(header::CONTENT_TYPE, GRPC_WEB_TEXT)
+            .body(HyperBody::wrap_stream(stream::iter(chunks)))
+            .unwrap();
+        let response = client.send_request(request).await.unwrap();
+
+        assert_eq!(
+            response.headers()[header::CONTENT_TYPE],
+            GRPC_WEB_TEXT_PROTO
+        );
+
+        // Each frame is encoded separately, so padding may appear between them.
+        let body = body::to_bytes(response.into_body()).await.unwrap();
+        let body = std::str::from_utf8(&body).unwrap();
+        let split = body.find('=').map_or(body.len(), |i| {
+            i + body[i..].chars().take_while(|&c| c == '=').count()
+        });
+        let message = base64::decode(&body[..split]).unwrap();
+        let trailers = base64::decode(&body[split..]).unwrap();
+
+        assert_eq!(message[0], 0);
+        let message = HealthCheckResponse::decode(&message[5..]).unwrap();
+        assert_eq!(message.status(), ServingStatus::Serving);
+        assert_eq!(trailers[0], TRAILERS_FLAG);
+        let trailers = std::str::from_utf8(&trailers[5..]).unwrap();
+        assert!(trailers.contains("grpc-status:0\r\n"), "{trailers}");
+    }
+}
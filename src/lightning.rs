@@ -0,0 +1,57 @@
+use std::sync::Arc;
+
+use time::{Duration, OffsetDateTime};
+
+pub mod fake;
+pub mod lnd;
+
+#[derive(Debug, thiserror::Error)]
+pub enum LightningError {
+    #[error("Unknown invoice: {0}")]
+    UnknownInvoice(String),
+    #[error("Backend error: {0}")]
+    Backend(String),
+}
+
+pub struct CreateInvoiceParams {
+    pub amount_msats: u64,
+    pub description: String,
+    pub expiry: Duration,
+}
+
+#[derive(Debug, Clone)]
+pub struct Invoice {
+    pub payment_hash: String,
+    pub payment_request: String,
+    pub expires_at: OffsetDateTime,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum InvoiceStatus {
+    Pending,
+    Paid {
+        settled_at: OffsetDateTime,
+        preimage: String,
+    },
+    Expired,
+}
+
+#[tonic::async_trait]
+pub trait LightningBackend: Send + Sync {
+    async fn create_invoice(&self, params: CreateInvoiceParams) -> Result<Invoice, LightningError>;
+
+    async fn invoice_status(&self, payment_hash: &str) -> Result<InvoiceStatus, LightningError>;
+}
+
+pub fn build(env: &crate::env::Env) -> Result<Option<Arc<dyn LightningBackend>>, LightningError> {
+    match (&env.lnd_rest_url, &env.lnd_macaroon, &env.lnd_tls_cert) {
+        (Some(url), Some(macaroon), Some(tls_cert)) => {
+            let pem = std::fs::read(tls_cert)
+                .map_err(|e| LightningError::Backend(format!("Could not read {tls_cert}: {e}")))?;
+
+            Ok(Some(Arc::new(lnd::LndBackend::new(url, macaroon, &pem)?)))
+        }
+        _ if env.profile.fakes_lightning() => Ok(Some(Arc::new(fake::FakeBackend::default()))),
+        _ => Ok(None),
+    }
+}
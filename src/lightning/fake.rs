@@ -0,0 +1,89 @@
+use std::{collections::HashMap, sync::Mutex};
+
+use time::OffsetDateTime;
+
+use super::{CreateInvoiceParams, Invoice, InvoiceStatus, LightningBackend, LightningError};
+
+/// In-process backend for tests and the `dev` profile. Invoices only change state through
+/// `settle` and `expire`, and payment hashes are sequential, so runs are reproducible.
+#[derive(Default)]
+pub struct FakeBackend(Mutex<State>);
+
+#[derive(Default)]
+struct State {
+    next: u64,
+    invoices: HashMap<String, InvoiceStatus>,
+}
+
+#[cfg(test)]
+impl FakeBackend {
+    pub fn settle(
+        &self,
+        payment_hash: &str,
+        settled_at: OffsetDateTime,
+    ) -> Result<(), LightningError> {
+        let preimage = payment_hash.chars().rev().collect();
+        self.transition(
+            payment_hash,
+            InvoiceStatus::Paid {
+                settled_at,
+                preimage,
+            },
+        )
+    }
+
+    pub fn expire(&self, payment_hash: &str) -> Result<(), LightningError> {
+        self.transition(payment_hash, InvoiceStatus::Expired)
+    }
+
+    fn transition(&self, payment_hash: &str, to: InvoiceStatus) -> Result<(), LightningError> {
+        let mut state = self.0.lock().unwrap();
+
+        match state.invoices.get_mut(payment_hash) {
+            Some(status @ InvoiceStatus::Pending) => {
+                *status = to;
+                Ok(())
+            }
+            Some(_) => Err(LightningError::Backend(format!(
+                "Invoice {payment_hash} is no longer pending"
+            ))),
+            None => Err(LightningError::UnknownInvoice(payment_hash.to_owned())),
+        }
+    }
+}
+
+#[tonic::async_trait]
+impl LightningBackend for FakeBackend {
+    async fn create_invoice(
+        &self,
+        CreateInvoiceParams {
+            amount_msats,
+            description: _,
+            expiry,
+        }: CreateInvoiceParams,
+    ) -> Result<Invoice, LightningError> {
+        let mut state = self.0.lock().unwrap();
+        state.next += 1;
+
+        let payment_hash = format!("{:064x}", state.next);
+        state
+            .invoices
+            .insert(payment_hash.clone(), InvoiceStatus::Pending);
+
+        Ok(Invoice {
+            payment_request: format!("lnfake{amount_msats}1{payment_hash}"),
+            payment_hash,
+            expires_at: OffsetDateTime::now_utc() + expiry,
+        })
+    }
+
+    async fn invoice_status(&self, payment_hash: &str) -> Result<InvoiceStatus, LightningError> {
+        self.0
+            .lock()
+            .unwrap()
+            .invoices
+            .get(payment_hash)
+            .cloned()
+            .ok_or_else(|| LightningError::UnknownInvoice(payment_hash.to_owned()))
+    }
+}
@@ -1,20 +1,160 @@
+mod auth;
+mod bitcoin;
 mod env;
 mod features;
 mod grpc;
+mod lightning;
+mod metrics;
+mod pix;
+mod policy;
+mod qr;
+mod tls;
+
+use std::{future::Future, sync::Arc, time::Duration};
+
+use futures::{
+    future::{self, Either},
+    FutureExt, TryFutureExt,
+};
+use tokio::{
+    signal::unix::{signal, SignalKind},
+    sync::watch,
+};
+use tracing_subscriber::fmt::format::FmtSpan;
+
+/// How long blocking database work abandoned at shutdown may take to return its connection.
+const POOL_CLOSE_TIMEOUT: Duration = Duration::from_secs(5);
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
-    let env = env::Env::load();
-    let db = db::build(&env.database_url)?;
+    let env = env::Env::load().unwrap_or_else(|e| {
+        eprintln!("{e}");
+        std::process::exit(1);
+    });
+    init_tracing(env.log_format, env.log_level);
+
+    let db = db::build(&env.database_url, env.database_pool)?;
+
+    let lightning = lightning::build(&env)?;
+
+    let settlement_watcher = lightning.clone().map(|lightning| {
+        tokio::spawn(features::settlement::watch(
+            db.clone(),
+            lightning,
+            Duration::from_secs(10),
+        ))
+    });
+
+    let tls = tls::build(&env)?;
+
+    if let Some(tls) = tls.clone() {
+        tokio::spawn(tls::reload_on_sighup(tls));
+    }
+
+    let (draining_tx, draining) = watch::channel(false);
+    let (shutdown_tx, shutdown) = watch::channel(false);
+    let drain_period = env.shutdown_drain_period;
+
+    tokio::spawn(async move {
+        if terminated().await.is_ok() {
+            let _ = draining_tx.send(true);
+            tokio::time::sleep(drain_period).await;
+            let _ = shutdown_tx.send(true);
+        }
+    });
+
+    let layers = grpc::Layers::new(
+        auth::Tokens::new(&env.auth_key),
+        env.rate_limit_per_user,
+        env.rate_limit_per_ip,
+    );
+
+    let deps = Deps {
+        db,
+        env,
+        lightning,
+        tls,
+        layers,
+        draining,
+        shutdown,
+    };
+
+    let server = grpc::serve(&deps)?;
+    let admin = optional(grpc::serve_admin(&deps)?);
+    let gateway = optional(grpc::serve_gateway(&deps)?);
+    let metrics = optional(metrics::serve(&deps));
 
-    let deps = Deps { db, env };
+    let servers = futures::future::try_join4(
+        server.err_into::<Box<dyn std::error::Error>>(),
+        admin.err_into(),
+        gateway.err_into(),
+        metrics.err_into(),
+    );
 
-    grpc::serve(&deps).await?;
+    // Health checks turn NOT_SERVING first. After the drain period the servers stop accepting
+    // connections and resolve once in-flight requests finish; requests still running after the
+    // grace period are abandoned.
+    let grace_period = deps.env.shutdown_grace_period;
+    let deadline =
+        grpc::shutdown_signal(deps.shutdown.clone()).then(|()| tokio::time::sleep(grace_period));
+
+    match future::select(Box::pin(servers), Box::pin(deadline)).await {
+        Either::Left((result, _)) => {
+            result?;
+        }
+        Either::Right(_) => {
+            tracing::warn!("Shutdown grace period elapsed with requests in flight");
+        }
+    }
+
+    if let Some(watcher) = settlement_watcher {
+        watcher.abort();
+    }
+
+    if !deps.db.close(POOL_CLOSE_TIMEOUT).await {
+        tracing::warn!("Database connections were still in use at exit");
+    }
 
     Ok(())
 }
 
+/// Logs to stdout, reporting every span when it closes so request ids and timings are kept.
+fn init_tracing(format: env::LogFormat, level: tracing::Level) {
+    let subscriber = tracing_subscriber::fmt()
+        .with_max_level(level)
+        .with_span_events(FmtSpan::CLOSE);
+
+    match format {
+        env::LogFormat::Json => subscriber.json().init(),
+        env::LogFormat::Pretty => subscriber.pretty().init(),
+    }
+}
+
+/// Runs `server` if it is configured.
+async fn optional<E>(server: Option<impl Future<Output = Result<(), E>>>) -> Result<(), E> {
+    match server {
+        Some(server) => server.await,
+        None => Ok(()),
+    }
+}
+
 struct Deps {
     db: db::Db,
     env: crate::env::Env,
+    lightning: Option<Arc<dyn lightning::LightningBackend>>,
+    tls: Option<Arc<tls::Tls>>,
+    layers: grpc::Layers,
+    /// Turns true when the process is asked to stop, so that health checks fail.
+    draining: watch::Receiver<bool>,
+    /// Turns true once the drain period has passed, so that the listeners stop.
+    shutdown: watch::Receiver<bool>,
+}
+
+/// Resolves on the first SIGTERM or SIGINT.
+async fn terminated() -> std::io::Result<()> {
+    let mut terminate = signal(SignalKind::terminate())?;
+    let mut interrupt = signal(SignalKind::interrupt())?;
+
+    futures::future::select(Box::pin(terminate.recv()), Box::pin(interrupt.recv())).await;
+    Ok(())
 }
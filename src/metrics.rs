@@ -0,0 +1,82 @@
+use std::future::Future;
+
+use axum::{extract::State, http::header, response::IntoResponse, routing::get, Router};
+use once_cell::sync::Lazy;
+use prometheus::{register_int_counter, register_int_counter_vec, IntCounter, IntCounterVec};
+
+static EXPENSES_CREATED: Lazy<IntCounter> = Lazy::new(|| {
+    register_int_counter!("splitwiser_expenses_created_total", "Expenses created.").unwrap()
+});
+
+static EXPENSE_AMOUNT_CENTS: Lazy<IntCounterVec> = Lazy::new(|| {
+    register_int_counter_vec!(
+        "splitwiser_expense_amount_cents_total",
+        "Sum of the amounts of created expenses, in cents.",
+        &["currency"]
+    )
+    .unwrap()
+});
+
+pub fn expense_created(amount_cents: i64) {
+    EXPENSES_CREATED.inc();
+    EXPENSE_AMOUNT_CENTS
+        .with_label_values(&[crate::features::CURRENCY])
+        .inc_by(amount_cents.unsigned_abs());
+}
+
+/// Serves `/metrics` in the Prometheus text format when `METRICS_SOCKET` is set.
+pub(super) fn serve(deps: &super::Deps) -> Option<impl Future<Output = Result<(), hyper::Error>>> {
+    let socket = deps.env.metrics_socket?;
+
+    let router = Router::new()
+        .route("/metrics", get(render))
+        .with_state(deps.db.clone());
+    let shutdown = crate::grpc::shutdown_signal(deps.shutdown.clone());
+
+    Some(async move {
+        axum::Server::try_bind(&socket)?
+            .serve(router.into_make_service())
+            .with_graceful_shutdown(shutdown)
+            .await
+    })
+}
+
+async fn render(State(db): State<db::Db>) -> impl IntoResponse {
+    db.record_pool_metrics();
+
+    let metrics = prometheus::TextEncoder::new()
+        .encode_to_string(&prometheus::gather())
+        .expect("Metrics are valid UTF-8");
+
+    ([(header::CONTENT_TYPE, prometheus::TEXT_FORMAT)], metrics)
+}
+
+#[cfg(test)]
+mod test {
+    use axum::{body::Body, http::Request};
+    use tower::ServiceExt;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn renders_text_format() {
+        expense_created(1_500);
+
+        let router = Router::new()
+            .route("/metrics", get(render))
+            .with_state(db::test::db());
+        let response = router
+            .oneshot(Request::get("/metrics").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(
+            response.headers()[header::CONTENT_TYPE],
+            prometheus::TEXT_FORMAT
+        );
+
+        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        let body = std::str::from_utf8(&body).unwrap();
+        assert!(body.contains("splitwiser_expense_amount_cents_total{currency=\"BRL\"}"));
+        assert!(body.contains("splitwiser_db_pool_max_size 1"), "{body}");
+    }
+}
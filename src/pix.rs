@@ -0,0 +1,275 @@
+use std::fmt::Write;
+
+const GUI: &str = "br.gov.bcb.pix";
+const MAX_KEY_LEN: usize = 77;
+const MAX_NAME_LEN: usize = 25;
+const MAX_CITY_LEN: usize = 15;
+const MAX_TXID_LEN: usize = 25;
+
+#[derive(Debug, PartialEq, Eq, thiserror::Error)]
+pub enum PixError {
+    #[error("Invalid PIX key")]
+    InvalidKey,
+    #[error("Merchant name must have between 1 and {MAX_NAME_LEN} ASCII characters")]
+    InvalidName,
+    #[error("Merchant city must have between 1 and {MAX_CITY_LEN} ASCII characters")]
+    InvalidCity,
+    #[error("txid must have up to {MAX_TXID_LEN} alphanumeric characters")]
+    InvalidTxid,
+    #[error("Field {0} is longer than 99 bytes")]
+    FieldTooLong(&'static str),
+}
+
+pub enum Account<'a> {
+    /// Reusable code pointing straight at the receiver's key.
+    Static { key: &'a str },
+    /// Single-use code whose charge is served by the receiver's PSP at `location`.
+    Dynamic { location: &'a str },
+}
+
+pub struct Payload<'a> {
+    pub account: Account<'a>,
+    pub merchant_name: &'a str,
+    pub merchant_city: &'a str,
+    pub amount_cents: Option<i64>,
+    pub txid: Option<&'a str>,
+}
+
+/// Accepts the key formats of the DICT: CPF, CNPJ, phone, e-mail and random (EVP) keys.
+pub fn validate_key(key: &str) -> Result<(), PixError> {
+    let digits = |s: &str| !s.is_empty() && s.bytes().all(|b| b.is_ascii_digit());
+
+    let valid = match key.len() {
+        0 => false,
+        len if len > MAX_KEY_LEN => false,
+        11 | 14 if digits(key) => true,
+        _ if key.starts_with('+') => digits(&key[1..]) && (11..=14).contains(&(key.len() - 1)),
+        _ if key.contains('@') => key
+            .split_once('@')
+            .is_some_and(|(user, domain)| !user.is_empty() && domain.contains('.')),
+        36 => key.bytes().enumerate().all(|(i, b)| match i {
+            8 | 13 | 18 | 23 => b == b'-',
+            _ => b.is_ascii_hexdigit(),
+        }),
+        _ => false,
+    };
+
+    if valid {
+        Ok(())
+    } else {
+        Err(PixError::InvalidKey)
+    }
+}
+
+pub fn validate_merchant(name: &str, city: &str) -> Result<(), PixError> {
+    if !name.is_ascii() || !(1..=MAX_NAME_LEN).contains(&name.len()) {
+        return Err(PixError::InvalidName);
+    }
+
+    if !city.is_ascii() || !(1..=MAX_CITY_LEN).contains(&city.len()) {
+        return Err(PixError::InvalidCity);
+    }
+
+    Ok(())
+}
+
+/// Builds an EMV-MPM "copia e cola" payload as specified by the BR Code manual.
+pub fn encode(p: &Payload) -> Result<String, PixError> {
+    validate_merchant(p.merchant_name, p.merchant_city)?;
+
+    let txid = match p.txid {
+        Some(txid)
+            if txid.is_empty()
+                || txid.len() > MAX_TXID_LEN
+                || !txid.bytes().all(|b| b.is_ascii_alphanumeric()) =>
+        {
+            return Err(PixError::InvalidTxid)
+        }
+        Some(txid) => txid,
+        None => "***",
+    };
+
+    // Omitting the point of initiation method marks the code as reusable.
+    let (initiation, account) = match p.account {
+        Account::Static { key } => {
+            validate_key(key)?;
+            (None, tlv("01", key)?)
+        }
+        Account::Dynamic { location } => (Some("12"), tlv("25", location)?),
+    };
+
+    let mut payload = tlv("00", "01")?;
+    if let Some(initiation) = initiation {
+        payload += &tlv("01", initiation)?;
+    }
+    payload += &tlv("26", &(tlv("00", GUI)? + &account))?;
+    payload += &tlv("52", "0000")?;
+    payload += &tlv("53", "986")?;
+
+    if let Some(amount_cents) = p.amount_cents {
+        let amount = format!("{}.{:02}", amount_cents / 100, amount_cents % 100);
+        payload += &tlv("54", &amount)?;
+    }
+
+    payload += &tlv("58", "BR")?;
+    payload += &tlv("59", p.merchant_name)?;
+    payload += &tlv("60", p.merchant_city)?;
+    payload += &tlv("62", &tlv("05", txid)?)?;
+
+    payload += "6304";
+    let crc = crc16(payload.as_bytes());
+    write!(payload, "{crc:04X}").expect("Writing to a String cannot fail");
+
+    Ok(payload)
+}
+
+fn tlv(id: &'static str, value: &str) -> Result<String, PixError> {
+    if value.len() > 99 {
+        return Err(PixError::FieldTooLong(id));
+    }
+
+    Ok(format!("{id}{:02}{value}", value.len()))
+}
+
+/// CRC-16/CCITT-FALSE: polynomial 0x1021, initial value 0xFFFF, no reflection.
+fn crc16(data: &[u8]) -> u16 {
+    data.iter().fold(0xFFFF, |crc, byte| {
+        (0..8).fold(crc ^ (u16::from(*byte) << 8), |crc, _| {
+            if crc & 0x8000 == 0 {
+                crc << 1
+            } else {
+                (crc << 1) ^ 0x1021
+            }
+        })
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn crc16_check_value() {
+        assert_eq!(crc16(b"123456789"), 0x29B1);
+        assert_eq!(crc16(b""), 0xFFFF);
+    }
+
+    #[test]
+    fn tlv_prefixes_id_and_length() {
+        assert_eq!(tlv("00", "01").unwrap(), "000201");
+        assert_eq!(tlv("58", "BR").unwrap(), "5802BR");
+        assert_eq!(
+            tlv("62", &"x".repeat(100)),
+            Err(PixError::FieldTooLong("62"))
+        );
+    }
+
+    #[test]
+    fn encodes_static_payload_from_bcb_manual() {
+        let payload = encode(&Payload {
+            account: Account::Static {
+                key: "123e4567-e12b-12d1-a456-426655440000",
+            },
+            merchant_name: "Fulano de Tal",
+            merchant_city: "BRASILIA",
+            amount_cents: None,
+            txid: None,
+        })
+        .unwrap();
+
+        assert_eq!(
+            payload,
+            "00020126580014br.gov.bcb.pix0136123e4567-e12b-12d1-a456-4266554400005204000053039865802BR5913Fulano de Tal6008BRASILIA62070503***63041D3D"
+        );
+    }
+
+    #[test]
+    fn encodes_amount_and_txid() {
+        let payload = encode(&Payload {
+            account: Account::Static {
+                key: "fulano@example.com",
+            },
+            merchant_name: "Fulano de Tal",
+            merchant_city: "BRASILIA",
+            amount_cents: Some(12_050),
+            txid: Some("SPLITWISER42"),
+        })
+        .unwrap();
+
+        assert!(payload.contains("5406120.50"));
+        assert!(payload.contains("62160512SPLITWISER42"));
+        assert_eq!(
+            &payload[payload.len() - 4..],
+            format!("{:04X}", crc16(&payload.as_bytes()[..payload.len() - 4]))
+        );
+    }
+
+    #[test]
+    fn encodes_dynamic_payload() {
+        let payload = encode(&Payload {
+            account: Account::Dynamic {
+                location: "pix.example.com/qr/v2/9d36b84f",
+            },
+            merchant_name: "Fulano de Tal",
+            merchant_city: "BRASILIA",
+            amount_cents: Some(100),
+            txid: None,
+        })
+        .unwrap();
+
+        assert!(payload.starts_with("000201010212"));
+        assert!(payload.contains("2530pix.example.com/qr/v2/9d36b84f"));
+    }
+
+    #[test]
+    fn validates_keys() {
+        assert!(validate_key("12345678901").is_ok());
+        assert!(validate_key("12345678000195").is_ok());
+        assert!(validate_key("+5561912345678").is_ok());
+        assert!(validate_key("fulano@example.com").is_ok());
+        assert!(validate_key("123e4567-e12b-12d1-a456-426655440000").is_ok());
+
+        assert_eq!(validate_key(""), Err(PixError::InvalidKey));
+        assert_eq!(validate_key("123"), Err(PixError::InvalidKey));
+        assert_eq!(validate_key("+55"), Err(PixError::InvalidKey));
+        assert_eq!(validate_key("fulano@"), Err(PixError::InvalidKey));
+        assert_eq!(
+            validate_key("123e4567-e12b-12d1-a456-42665544000z"),
+            Err(PixError::InvalidKey)
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_txid() {
+        let payload = |txid: &str| {
+            encode(&Payload {
+                account: Account::Static { key: "12345678901" },
+                merchant_name: "Fulano de Tal",
+                merchant_city: "BRASILIA",
+                amount_cents: None,
+                txid: Some(txid),
+            })
+        };
+
+        assert_eq!(payload("has space"), Err(PixError::InvalidTxid));
+        assert_eq!(payload(&"a".repeat(26)), Err(PixError::InvalidTxid));
+        assert!(payload("abc123").is_ok());
+    }
+
+    #[test]
+    fn validates_merchant() {
+        assert!(validate_merchant("Fulano de Tal", "SAO PAULO").is_ok());
+        assert_eq!(
+            validate_merchant("Fulano de Tal", "São Paulo"),
+            Err(PixError::InvalidCity)
+        );
+        assert_eq!(
+            validate_merchant(&"a".repeat(26), "BRASILIA"),
+            Err(PixError::InvalidName)
+        );
+        assert_eq!(
+            validate_merchant("", "BRASILIA"),
+            Err(PixError::InvalidName)
+        );
+    }
+}
@@ -0,0 +1,363 @@
+use std::fmt;
+
+use db::{
+    queries::{
+        settlement_invoices::SettlementInvoice, user_expenses::UserExpense,
+        user_payments::UserPayment,
+    },
+    types::UserId,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Resource {
+    Expense,
+    Payment,
+    SettlementInvoice,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    Create,
+    View,
+    Edit,
+    Delete,
+    Confirm,
+}
+
+/// How the caller relates to a ledger entry. A caller may hold several roles at once, e.g. the
+/// payee of a payment is also one of its participants.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Role {
+    Creator,
+    Participant,
+    Payee,
+    /// A user operators made an admin. There are no groups yet, so admins look after every
+    /// expense.
+    Admin,
+}
+
+/// The caller as far as access is concerned.
+#[derive(Debug, Clone, Copy)]
+pub struct Actor {
+    pub user_id: UserId,
+    pub admin: bool,
+}
+
+impl From<UserId> for Actor {
+    fn from(user_id: UserId) -> Self {
+        Self {
+            user_id,
+            admin: false,
+        }
+    }
+}
+
+/// The fields of a ledger entry that access depends on, for entries both stored and about to be
+/// created.
+pub enum Entry {
+    Expense {
+        created_by: i32,
+        charged_user_id: i32,
+        chargee_user_id: i32,
+    },
+    Payment {
+        created_by: i32,
+        payer_user_id: i32,
+        payee_user_id: i32,
+    },
+    /// Always requested by its payer.
+    SettlementInvoice {
+        payer_user_id: i32,
+        payee_user_id: i32,
+    },
+}
+
+impl Entry {
+    fn resource(&self) -> Resource {
+        match self {
+            Self::Expense { .. } => Resource::Expense,
+            Self::Payment { .. } => Resource::Payment,
+            Self::SettlementInvoice { .. } => Resource::SettlementInvoice,
+        }
+    }
+
+    /// The users the entry is between.
+    pub fn participants(&self) -> [i32; 2] {
+        match *self {
+            Self::Expense {
+                charged_user_id,
+                chargee_user_id,
+                ..
+            } => [charged_user_id, chargee_user_id],
+            Self::Payment {
+                payer_user_id,
+                payee_user_id,
+                ..
+            }
+            | Self::SettlementInvoice {
+                payer_user_id,
+                payee_user_id,
+            } => [payer_user_id, payee_user_id],
+        }
+    }
+
+    fn roles(&self, actor: Actor) -> Vec<Role> {
+        let caller = *actor.user_id;
+        let participants = self.participants();
+        let (created_by, payee) = match *self {
+            Self::Expense { created_by, .. } => (created_by, None),
+            Self::Payment {
+                created_by,
+                payee_user_id,
+                ..
+            } => (created_by, Some(payee_user_id)),
+            Self::SettlementInvoice {
+                payer_user_id,
+                payee_user_id,
+            } => (payer_user_id, Some(payee_user_id)),
+        };
+
+        let mut roles = Vec::new();
+        if created_by == caller {
+            roles.push(Role::Creator);
+        }
+        if participants.contains(&caller) {
+            roles.push(Role::Participant);
+        }
+        if payee == Some(caller) {
+            roles.push(Role::Payee);
+        }
+        if actor.admin {
+            roles.push(Role::Admin);
+        }
+        roles
+    }
+}
+
+impl From<&UserExpense> for Entry {
+    fn from(expense: &UserExpense) -> Self {
+        Self::Expense {
+            created_by: expense.created_by,
+            charged_user_id: expense.charged_user_id,
+            chargee_user_id: expense.chargee_user_id,
+        }
+    }
+}
+
+impl From<&UserPayment> for Entry {
+    fn from(payment: &UserPayment) -> Self {
+        Self::Payment {
+            created_by: payment.created_by,
+            payer_user_id: payment.payer_user_id,
+            payee_user_id: payment.payee_user_id,
+        }
+    }
+}
+
+impl From<&SettlementInvoice> for Entry {
+    fn from(invoice: &SettlementInvoice) -> Self {
+        Self::SettlementInvoice {
+            payer_user_id: invoice.payer_user_id,
+            payee_user_id: invoice.payee_user_id,
+        }
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+#[error("Not allowed to {action} this {resource}")]
+pub struct Denied {
+    pub action: Action,
+    pub resource: Resource,
+}
+
+impl fmt::Display for Action {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Create => "create",
+            Self::View => "view",
+            Self::Edit => "edit",
+            Self::Delete => "delete",
+            Self::Confirm => "confirm",
+        })
+    }
+}
+
+impl fmt::Display for Resource {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Expense => "expense",
+            Self::Payment => "payment",
+            Self::SettlementInvoice => "settlement invoice",
+        })
+    }
+}
+
+/// Creating an entry is allowed to its participants only, since the creator of a new entry is
+/// always the caller.
+fn permits(role: Role, action: Action, resource: Resource) -> bool {
+    matches!(
+        (role, action, resource),
+        (Role::Participant, Action::Create | Action::View, _)
+            | (Role::Creator, Action::View, _)
+            | (
+                Role::Creator,
+                Action::Edit | Action::Delete,
+                Resource::Expense
+            )
+            | (
+                Role::Admin,
+                Action::View | Action::Edit | Action::Delete,
+                Resource::Expense
+            )
+            | (Role::Payee, Action::Confirm, Resource::Payment)
+    )
+}
+
+pub fn authorize(caller: impl Into<Actor>, action: Action, entry: &Entry) -> Result<(), Denied> {
+    let resource = entry.resource();
+
+    if entry
+        .roles(caller.into())
+        .into_iter()
+        .any(|role| permits(role, action, resource))
+    {
+        Ok(())
+    } else {
+        Err(Denied { action, resource })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn permissions_table() {
+        use Action::*;
+        use Resource::*;
+        use Role::*;
+
+        #[rustfmt::skip]
+        let table = [
+            (Creator,     Create,  Expense, false),
+            (Creator,     View,    Expense, true),
+            (Creator,     Edit,    Expense, true),
+            (Creator,     Delete,  Expense, true),
+            (Creator,     Confirm, Expense, false),
+            (Participant, Create,  Expense, true),
+            (Participant, View,    Expense, true),
+            (Participant, Edit,    Expense, false),
+            (Participant, Delete,  Expense, false),
+            (Participant, Confirm, Expense, false),
+            (Payee,       Create,  Expense, false),
+            (Payee,       View,    Expense, false),
+            (Payee,       Edit,    Expense, false),
+            (Payee,       Delete,  Expense, false),
+            (Payee,       Confirm, Expense, false),
+            (Admin,       Create,  Expense, false),
+            (Admin,       View,    Expense, true),
+            (Admin,       Edit,    Expense, true),
+            (Admin,       Delete,  Expense, true),
+            (Admin,       Confirm, Expense, false),
+            (Creator,     Create,  Payment, false),
+            (Creator,     View,    Payment, true),
+            (Creator,     Edit,    Payment, false),
+            (Creator,     Delete,  Payment, false),
+            (Creator,     Confirm, Payment, false),
+            (Participant, Create,  Payment, true),
+            (Participant, View,    Payment, true),
+            (Participant, Edit,    Payment, false),
+            (Participant, Delete,  Payment, false),
+            (Participant, Confirm, Payment, false),
+            (Payee,       Create,  Payment, false),
+            (Payee,       View,    Payment, false),
+            (Payee,       Edit,    Payment, false),
+            (Payee,       Delete,  Payment, false),
+            (Payee,       Confirm, Payment, true),
+            (Admin,       Create,  Payment, false),
+            (Admin,       View,    Payment, false),
+            (Admin,       Edit,    Payment, false),
+            (Admin,       Delete,  Payment, false),
+            (Admin,       Confirm, Payment, false),
+            (Creator,     Create,  SettlementInvoice, false),
+            (Creator,     View,    SettlementInvoice, true),
+            (Creator,     Edit,    SettlementInvoice, false),
+            (Creator,     Delete,  SettlementInvoice, false),
+            (Creator,     Confirm, SettlementInvoice, false),
+            (Participant, Create,  SettlementInvoice, true),
+            (Participant, View,    SettlementInvoice, true),
+            (Participant, Edit,    SettlementInvoice, false),
+            (Participant, Delete,  SettlementInvoice, false),
+            (Participant, Confirm, SettlementInvoice, false),
+            (Payee,       Create,  SettlementInvoice, false),
+            (Payee,       View,    SettlementInvoice, false),
+            (Payee,       Edit,    SettlementInvoice, false),
+            (Payee,       Delete,  SettlementInvoice, false),
+            (Payee,       Confirm, SettlementInvoice, false),
+            (Admin,       Create,  SettlementInvoice, false),
+            (Admin,       View,    SettlementInvoice, false),
+            (Admin,       Edit,    SettlementInvoice, false),
+            (Admin,       Delete,  SettlementInvoice, false),
+            (Admin,       Confirm, SettlementInvoice, false),
+        ];
+
+        for (role, action, resource, allowed) in table {
+            assert_eq!(
+                permits(role, action, resource),
+                allowed,
+                "{role:?} {action} {resource}"
+            );
+        }
+    }
+
+    fn user(id: i32) -> Actor {
+        UserId::from(id).into()
+    }
+
+    #[test]
+    fn derives_roles_from_entry() {
+        let expense = Entry::Expense {
+            created_by: 1,
+            charged_user_id: 2,
+            chargee_user_id: 1,
+        };
+        assert_eq!(expense.roles(user(1)), [Role::Creator, Role::Participant]);
+        assert_eq!(expense.roles(user(2)), [Role::Participant]);
+        assert!(expense.roles(user(3)).is_empty());
+
+        let admin = Actor {
+            user_id: UserId::from(3),
+            admin: true,
+        };
+        assert_eq!(expense.roles(admin), [Role::Admin]);
+
+        let payment = Entry::Payment {
+            created_by: 1,
+            payer_user_id: 1,
+            payee_user_id: 2,
+        };
+        assert_eq!(payment.roles(user(1)), [Role::Creator, Role::Participant]);
+        assert_eq!(payment.roles(user(2)), [Role::Participant, Role::Payee]);
+    }
+
+    #[test]
+    fn outsiders_are_denied_everything() {
+        let payment = Entry::Payment {
+            created_by: 1,
+            payer_user_id: 1,
+            payee_user_id: 2,
+        };
+
+        for action in [
+            Action::Create,
+            Action::View,
+            Action::Edit,
+            Action::Delete,
+            Action::Confirm,
+        ] {
+            assert!(authorize(UserId::from(3), action, &payment).is_err());
+        }
+        assert!(authorize(UserId::from(2), Action::Confirm, &payment).is_ok());
+        assert!(authorize(UserId::from(1), Action::Confirm, &payment).is_err());
+    }
+}
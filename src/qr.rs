@@ -0,0 +1,212 @@
+use std::io::Cursor;
+
+use image::{ImageFormat, Luma};
+use qrcode::{render::svg, EcLevel, QrCode};
+
+const MIN_DIMENSION: u32 = 256;
+
+#[derive(Debug, thiserror::Error)]
+pub enum QrError {
+    #[error("Payload is empty")]
+    EmptyPayload,
+    #[error("Payload does not fit in a QR code: {0}")]
+    Encoding(qrcode::types::QrError),
+    #[error("Image error: {0}")]
+    Image(image::ImageError),
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum ErrorCorrection {
+    Low,
+    Medium,
+    Quartile,
+    High,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum Format {
+    Svg,
+    Png,
+}
+
+impl Format {
+    pub fn content_type(self) -> &'static str {
+        match self {
+            Self::Svg => "image/svg+xml",
+            Self::Png => "image/png",
+        }
+    }
+}
+
+pub fn render(
+    payload: &str,
+    error_correction: ErrorCorrection,
+    format: Format,
+) -> Result<Vec<u8>, QrError> {
+    if payload.is_empty() {
+        return Err(QrError::EmptyPayload);
+    }
+
+    let level = match error_correction {
+        ErrorCorrection::Low => EcLevel::L,
+        ErrorCorrection::Medium => EcLevel::M,
+        ErrorCorrection::Quartile => EcLevel::Q,
+        ErrorCorrection::High => EcLevel::H,
+    };
+
+    let code = QrCode::with_error_correction_level(payload, level).map_err(QrError::Encoding)?;
+
+    match format {
+        Format::Svg => Ok(code
+            .render::<svg::Color>()
+            .min_dimensions(MIN_DIMENSION, MIN_DIMENSION)
+            .build()
+            .into_bytes()),
+        Format::Png => {
+            let image = code
+                .render::<Luma<u8>>()
+                .min_dimensions(MIN_DIMENSION, MIN_DIMENSION)
+                .build();
+
+            let mut png = Cursor::new(Vec::new());
+            image
+                .write_to(&mut png, ImageFormat::Png)
+                .map_err(QrError::Image)?;
+
+            Ok(png.into_inner())
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::path::PathBuf;
+
+    use super::*;
+
+    const PIX: &str = "00020126580014br.gov.bcb.pix0136123e4567-e12b-12d1-a456-4266554400005204000053039865802BR5913Fulano de Tal6008BRASILIA62070503***63041D3D";
+    const BIP21: &str =
+        "bitcoin:bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4?amount=0.0001&label=Splitwiser%20settlement";
+
+    /// Compares against `src/qr/golden/<name>`, rewriting it when `UPDATE_GOLDEN` is set.
+    fn golden(name: &str, rendered: &[u8]) -> Vec<u8> {
+        let path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("src/qr/golden")
+            .join(name);
+
+        if std::env::var_os("UPDATE_GOLDEN").is_some() {
+            std::fs::write(&path, rendered).unwrap();
+        }
+
+        let golden = std::fs::read(&path).unwrap();
+        assert!(golden == rendered, "{name} differs from its golden image");
+        golden
+    }
+
+    fn decode(width: usize, height: usize, pixel: impl FnMut(usize, usize) -> u8) -> String {
+        let mut image = rqrr::PreparedImage::prepare_from_greyscale(width, height, pixel);
+        let grids = image.detect_grids();
+        assert_eq!(grids.len(), 1);
+        grids[0].decode().unwrap().1
+    }
+
+    fn decode_png(png: &[u8]) -> String {
+        let image = image::load_from_memory(png).unwrap().to_luma8();
+        decode(image.width() as usize, image.height() as usize, |x, y| {
+            image.get_pixel(x as u32, y as u32).0[0]
+        })
+    }
+
+    /// Rasterizes the `M{x} {y}h{w}v{h}H{x}V{y}` rectangles emitted by the SVG renderer.
+    fn decode_svg(svg: &[u8]) -> String {
+        let svg = std::str::from_utf8(svg).unwrap();
+        let attr = |name: &str| -> usize {
+            let start = svg.find(&format!(" {name}=\"")).unwrap() + name.len() + 3;
+            svg[start..].split('"').next().unwrap().parse().unwrap()
+        };
+        let (width, height) = (attr("width"), attr("height"));
+
+        let path = svg
+            .split(" d=\"")
+            .nth(1)
+            .unwrap()
+            .split('"')
+            .next()
+            .unwrap();
+        let mut dark = vec![false; width * height];
+
+        for rect in path.split('M').filter(|r| !r.is_empty()) {
+            let numbers: Vec<usize> = rect
+                .split(|c: char| !c.is_ascii_digit())
+                .filter(|n| !n.is_empty())
+                .map(|n| n.parse().unwrap())
+                .collect();
+            let (left, top, w, h) = (numbers[0], numbers[1], numbers[2], numbers[3]);
+
+            for y in top..top + h {
+                for x in left..left + w {
+                    dark[y * width + x] = true;
+                }
+            }
+        }
+
+        decode(
+            width,
+            height,
+            |x, y| {
+                if dark[y * width + x] {
+                    0
+                } else {
+                    255
+                }
+            },
+        )
+    }
+
+    #[test]
+    fn png_decodes_to_payload() {
+        let png = render(PIX, ErrorCorrection::Medium, Format::Png).unwrap();
+        assert_eq!(decode_png(&golden("pix-medium.png", &png)), PIX);
+    }
+
+    #[test]
+    fn svg_decodes_to_payload() {
+        let svg = render(BIP21, ErrorCorrection::High, Format::Svg).unwrap();
+        assert_eq!(decode_svg(&golden("bip21-high.svg", &svg)), BIP21);
+    }
+
+    #[test]
+    fn every_error_correction_level_decodes() {
+        for level in [
+            ErrorCorrection::Low,
+            ErrorCorrection::Medium,
+            ErrorCorrection::Quartile,
+            ErrorCorrection::High,
+        ] {
+            let png = render(BIP21, level, Format::Png).unwrap();
+            assert_eq!(decode_png(&png), BIP21);
+
+            let svg = render(PIX, level, Format::Svg).unwrap();
+            assert_eq!(decode_svg(&svg), PIX);
+        }
+    }
+
+    #[test]
+    fn higher_error_correction_grows_the_code() {
+        let low = render(PIX, ErrorCorrection::Low, Format::Svg).unwrap();
+        let high = render(PIX, ErrorCorrection::High, Format::Svg).unwrap();
+        assert!(high.len() > low.len());
+    }
+
+    #[test]
+    fn rejects_unencodable_payloads() {
+        assert!(matches!(
+            render("", ErrorCorrection::Low, Format::Png),
+            Err(QrError::EmptyPayload)
+        ));
+        assert!(matches!(
+            render(&"x".repeat(8000), ErrorCorrection::High, Format::Png),
+            Err(QrError::Encoding(_))
+        ));
+    }
+}
@@ -0,0 +1,408 @@
+use std::{
+    io,
+    net::SocketAddr,
+    sync::{Arc, RwLock},
+    time::Duration,
+};
+
+use futures::Stream;
+use rustls::{
+    server::{AllowAnyAuthenticatedClient, ServerConfig},
+    Certificate, PrivateKey, RootCertStore,
+};
+use tokio::{
+    net::{TcpListener, TcpStream},
+    sync::mpsc,
+};
+use tokio_rustls::{server::TlsStream, TlsAcceptor};
+
+/// Connections still in the TLS handshake beyond this many make the listener wait.
+const PENDING_HANDSHAKES: usize = 64;
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Debug, thiserror::Error)]
+pub enum TlsError {
+    #[error("Could not read {0}: {1}")]
+    Read(String, io::Error),
+    #[error("No certificates found in {0}")]
+    NoCertificates(String),
+    #[error("No private key found in {0}")]
+    NoPrivateKey(String),
+    #[error("Invalid TLS configuration: {0}")]
+    Config(#[from] rustls::Error),
+    #[error("Invalid CA certificate in {0}: {1}")]
+    InvalidCa(String, String),
+}
+
+/// Paths of the PEM files the listener's TLS configuration is built from.
+#[derive(Debug, Clone)]
+pub struct TlsPaths {
+    pub cert: String,
+    pub key: String,
+    /// When set, clients of the admin listener must present a certificate signed by one of these
+    /// CAs.
+    pub client_ca: Option<String>,
+}
+
+/// The listener a connection arrived on. Only the admin listener asks for client certificates,
+/// so users of the public ones never need one.
+#[derive(Debug, Clone, Copy)]
+pub enum Listener {
+    Public,
+    Admin,
+}
+
+/// A TLS configuration that can be swapped while the server runs. Handshakes in flight keep the
+/// configuration they started with.
+pub struct Tls {
+    paths: TlsPaths,
+    configs: RwLock<Configs>,
+}
+
+struct Configs {
+    public: Arc<ServerConfig>,
+    admin: Arc<ServerConfig>,
+}
+
+impl Tls {
+    pub fn load(paths: TlsPaths) -> Result<Self, TlsError> {
+        let configs = server_configs(&paths)?;
+
+        Ok(Self {
+            paths,
+            configs: RwLock::new(configs),
+        })
+    }
+
+    /// Re-reads every file, keeping the current configuration if any of them is invalid.
+    pub fn reload(&self) -> Result<(), TlsError> {
+        let configs = server_configs(&self.paths)?;
+        *self.configs.write().expect("TLS config lock poisoned") = configs;
+        Ok(())
+    }
+
+    fn acceptor(&self, listener: Listener) -> TlsAcceptor {
+        let configs = self.configs.read().expect("TLS config lock poisoned");
+
+        TlsAcceptor::from(match listener {
+            Listener::Public => configs.public.clone(),
+            Listener::Admin => configs.admin.clone(),
+        })
+    }
+}
+
+pub fn build(env: &crate::env::Env) -> Result<Option<Arc<Tls>>, TlsError> {
+    match (&env.tls_cert, &env.tls_key) {
+        (Some(cert), Some(key)) => Ok(Some(Arc::new(Tls::load(TlsPaths {
+            cert: cert.clone(),
+            key: key.clone(),
+            client_ca: env.tls_client_ca.clone(),
+        })?))),
+        _ => Ok(None),
+    }
+}
+
+/// Reloads the certificates every time the process receives SIGHUP.
+pub async fn reload_on_sighup(tls: Arc<Tls>) -> io::Result<()> {
+    use tokio::signal::unix::{signal, SignalKind};
+
+    let mut hangups = signal(SignalKind::hangup())?;
+
+    while hangups.recv().await.is_some() {
+        if let Err(e) = tls.reload() {
+            tracing::error!(error = %e, "Keeping the previous TLS configuration");
+        }
+    }
+
+    Ok(())
+}
+
+/// Binds `socket` without awaiting, so that listeners are set up before serving starts.
+pub fn bind(socket: SocketAddr) -> io::Result<TcpListener> {
+    let listener = std::net::TcpListener::bind(socket)?;
+    listener.set_nonblocking(true)?;
+    TcpListener::from_std(listener)
+}
+
+/// Accepts TCP connections on `tcp` and yields those that complete a TLS handshake with the
+/// configuration of `listener`. Handshakes run concurrently, so a slow client cannot hold up the
+/// others.
+pub fn incoming(
+    tls: Arc<Tls>,
+    listener: Listener,
+    tcp: TcpListener,
+) -> impl Stream<Item = io::Result<TlsStream<TcpStream>>> {
+    let (tx, rx) = mpsc::channel(PENDING_HANDSHAKES);
+
+    tokio::spawn(async move {
+        loop {
+            let stream = match tcp.accept().await {
+                Ok((stream, _)) => stream,
+                // Usually running out of file descriptors; retrying right away would spin.
+                Err(_) => {
+                    tokio::time::sleep(Duration::from_millis(100)).await;
+                    continue;
+                }
+            };
+            let _ = stream.set_nodelay(true);
+
+            let Ok(permit) = tx.clone().reserve_owned().await else {
+                return;
+            };
+            let acceptor = tls.acceptor(listener);
+
+            tokio::spawn(async move {
+                if let Ok(Ok(stream)) =
+                    tokio::time::timeout(HANDSHAKE_TIMEOUT, acceptor.accept(stream)).await
+                {
+                    permit.send(Ok(stream));
+                }
+            });
+        }
+    });
+
+    futures::stream::unfold(rx, |mut rx| async move {
+        rx.recv().await.map(|stream| (stream, rx))
+    })
+}
+
+fn server_configs(paths: &TlsPaths) -> Result<Configs, TlsError> {
+    let certs = read_certs(&paths.cert)?;
+    let key = read_key(&paths.key)?;
+
+    let builder = ServerConfig::builder().with_safe_defaults();
+    let public = with_alpn(
+        builder
+            .clone()
+            .with_no_client_auth()
+            .with_single_cert(certs.clone(), key.clone())?,
+    );
+
+    let admin = match &paths.client_ca {
+        Some(client_ca) => {
+            let mut roots = RootCertStore::empty();
+            for cert in read_certs(client_ca)? {
+                roots
+                    .add(&cert)
+                    .map_err(|e| TlsError::InvalidCa(client_ca.clone(), e.to_string()))?;
+            }
+
+            with_alpn(
+                builder
+                    .with_client_cert_verifier(AllowAnyAuthenticatedClient::new(roots))
+                    .with_single_cert(certs, key)?,
+            )
+        }
+        None => public.clone(),
+    };
+
+    Ok(Configs { public, admin })
+}
+
+fn with_alpn(mut config: ServerConfig) -> Arc<ServerConfig> {
+    // HTTP/1.1 is offered for gRPC-Web clients.
+    config.alpn_protocols = vec![b"h2".to_vec(), b"http/1.1".to_vec()];
+    Arc::new(config)
+}
+
+fn read(path: &str) -> Result<Vec<u8>, TlsError> {
+    std::fs::read(path).map_err(|e| TlsError::Read(path.to_owned(), e))
+}
+
+fn read_certs(path: &str) -> Result<Vec<Certificate>, TlsError> {
+    let certs = rustls_pemfile::certs(&mut &*read(path)?)
+        .map_err(|e| TlsError::Read(path.to_owned(), e))?;
+
+    if certs.is_empty() {
+        return Err(TlsError::NoCertificates(path.to_owned()));
+    }
+
+    Ok(certs.into_iter().map(Certificate).collect())
+}
+
+fn read_key(path: &str) -> Result<PrivateKey, TlsError> {
+    let items = rustls_pemfile::read_all(&mut &*read(path)?)
+        .map_err(|e| TlsError::Read(path.to_owned(), e))?;
+
+    items
+        .into_iter()
+        .find_map(|item| match item {
+            rustls_pemfile::Item::PKCS8Key(key)
+            | rustls_pemfile::Item::RSAKey(key)
+            | rustls_pemfile::Item::ECKey(key) => Some(PrivateKey(key)),
+            _ => None,
+        })
+        .ok_or_else(|| TlsError::NoPrivateKey(path.to_owned()))
+}
+
+#[cfg(test)]
+mod test {
+    use std::{
+        path::PathBuf,
+        sync::atomic::{AtomicUsize, Ordering},
+    };
+
+    use futures::StreamExt;
+    use rcgen::{BasicConstraints, CertificateParams, IsCa};
+    use rustls::{ClientConfig, ServerName};
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio_rustls::TlsConnector;
+
+    use super::*;
+
+    /// A throwaway directory with a CA and certificates it signed, generated per test.
+    struct Pki {
+        dir: PathBuf,
+        ca: rcgen::Certificate,
+    }
+
+    impl Pki {
+        fn new() -> Self {
+            static COUNT: AtomicUsize = AtomicUsize::new(0);
+
+            let dir = std::env::temp_dir().join(format!(
+                "splitwiser-tls-{}-{}",
+                std::process::id(),
+                COUNT.fetch_add(1, Ordering::Relaxed)
+            ));
+            std::fs::create_dir_all(&dir).unwrap();
+
+            let mut params = CertificateParams::new(vec![]);
+            params.is_ca = IsCa::Ca(BasicConstraints::Unconstrained);
+            let ca = rcgen::Certificate::from_params(params).unwrap();
+
+            let pki = Self { dir, ca };
+            pki.write("ca.pem", &pki.ca.serialize_pem().unwrap());
+            pki
+        }
+
+        fn path(&self, name: &str) -> String {
+            self.dir.join(name).to_str().unwrap().to_owned()
+        }
+
+        fn write(&self, name: &str, contents: &str) {
+            std::fs::write(self.path(name), contents).unwrap();
+        }
+
+        /// Issues a certificate for `localhost`, writing `<name>.pem` and `<name>.key`, and
+        /// returns it DER-encoded.
+        fn issue(&self, name: &str) -> Vec<u8> {
+            let cert = rcgen::generate_simple_self_signed(vec!["localhost".to_owned()]).unwrap();
+            let path = self.path(&format!("{name}.pem"));
+
+            std::fs::write(&path, cert.serialize_pem_with_signer(&self.ca).unwrap()).unwrap();
+            self.write(&format!("{name}.key"), &cert.serialize_private_key_pem());
+
+            read_certs(&path).unwrap().remove(0).0
+        }
+
+        fn paths(&self, client_ca: bool) -> TlsPaths {
+            TlsPaths {
+                cert: self.path("server.pem"),
+                key: self.path("server.key"),
+                client_ca: client_ca.then(|| self.path("ca.pem")),
+            }
+        }
+
+        fn client(&self, client_cert: Option<&str>) -> TlsConnector {
+            let mut roots = RootCertStore::empty();
+            for cert in read_certs(&self.path("ca.pem")).unwrap() {
+                roots.add(&cert).unwrap();
+            }
+
+            let builder = ClientConfig::builder()
+                .with_safe_defaults()
+                .with_root_certificates(roots);
+            let config = match client_cert {
+                Some(name) => builder
+                    .with_single_cert(
+                        read_certs(&self.path(&format!("{name}.pem"))).unwrap(),
+                        read_key(&self.path(&format!("{name}.key"))).unwrap(),
+                    )
+                    .unwrap(),
+                None => builder.with_no_client_auth(),
+            };
+
+            TlsConnector::from(Arc::new(config))
+        }
+    }
+
+    impl Drop for Pki {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.dir);
+        }
+    }
+
+    /// Serves `ok` on every accepted connection and returns the bound address.
+    fn serve(tls: Arc<Tls>, listener: Listener) -> SocketAddr {
+        let tcp = bind("127.0.0.1:0".parse().unwrap()).unwrap();
+        let addr = tcp.local_addr().unwrap();
+
+        let mut incoming = Box::pin(incoming(tls, listener, tcp));
+        tokio::spawn(async move {
+            while let Some(Ok(mut stream)) = incoming.next().await {
+                let _ = stream.write_all(b"ok").await;
+                let _ = stream.flush().await;
+            }
+        });
+
+        addr
+    }
+
+    /// Connects and returns the server's leaf certificate, if the server accepted the client.
+    async fn connect(addr: SocketAddr, connector: TlsConnector) -> Option<Vec<u8>> {
+        let tcp = TcpStream::connect(addr).await.unwrap();
+        let name = ServerName::try_from("localhost").unwrap();
+        let mut stream = connector.connect(name, tcp).await.ok()?;
+
+        let mut buf = [0; 2];
+        stream.read_exact(&mut buf).await.ok()?;
+        assert_eq!(&buf, b"ok");
+
+        let (_, session) = stream.get_ref();
+        Some(session.peer_certificates()?[0].0.clone())
+    }
+
+    #[tokio::test]
+    async fn reloads_certificates() {
+        let pki = Pki::new();
+        let first = pki.issue("server");
+
+        let tls = Arc::new(Tls::load(pki.paths(false)).unwrap());
+        let addr = serve(tls.clone(), Listener::Public);
+
+        assert_eq!(connect(addr, pki.client(None)).await.unwrap(), first);
+
+        // An invalid key keeps the previous configuration in place.
+        pki.write("server.key", "");
+        assert!(matches!(tls.reload(), Err(TlsError::NoPrivateKey(_))));
+        assert!(connect(addr, pki.client(None)).await.is_some());
+
+        let second = pki.issue("server");
+        tls.reload().unwrap();
+
+        assert_eq!(connect(addr, pki.client(None)).await.unwrap(), second);
+    }
+
+    #[tokio::test]
+    async fn verifies_client_certificates_on_the_admin_listener_only() {
+        let pki = Pki::new();
+        pki.issue("server");
+        pki.issue("client");
+
+        let tls = Arc::new(Tls::load(pki.paths(true)).unwrap());
+        let public = serve(tls.clone(), Listener::Public);
+        let addr = serve(tls, Listener::Admin);
+
+        assert!(connect(public, pki.client(None)).await.is_some());
+
+        assert!(connect(addr, pki.client(Some("client"))).await.is_some());
+        assert!(connect(addr, pki.client(None)).await.is_none());
+
+        // A certificate the CA did not sign is refused too.
+        let stranger = rcgen::generate_simple_self_signed(vec!["localhost".to_owned()]).unwrap();
+        pki.write("stranger.pem", &stranger.serialize_pem().unwrap());
+        pki.write("stranger.key", &stranger.serialize_private_key_pem());
+        assert!(connect(addr, pki.client(Some("stranger"))).await.is_none());
+    }
+}